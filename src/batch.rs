@@ -0,0 +1,90 @@
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+
+use tokio::sync::Semaphore;
+
+use crate::{
+    create_glb_owned, create_gltf_root, image_from_bytes_with_options,
+    image_to_vertices_with_options, DecodeOptions, VoxelifyError, VoxelifyOptions,
+};
+
+/// Converts every encoded image into the bytes of a GLB, without blocking the async runtime
+/// Every image is converted on a blocking thread of the runtime, at most `concurrency` of them at once, 0 allows
+/// one per core. The results are in the order of the inputs, one failing doesn't stop the others.
+/// Uploads can't be trusted, the images over the limits of `decode` fail without being decoded.
+///
+/// ```
+/// use voxelify::{DecodeLimits, DecodeOptions, VoxelifyError};
+///
+/// let encode = |size| -> Result<Vec<u8>, image::ImageError> {
+///     let mut png = Vec::new();
+///     image::RgbaImage::from_pixel(size, size, image::Rgba([255, 0, 0, 255]))
+///         .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)?;
+///     Ok(png)
+/// };
+/// let options = voxelify::VoxelifyOptions::default();
+/// let decode = DecodeOptions {
+///     limits: DecodeLimits {
+///         max_pixels: Some(16),
+///         ..Default::default()
+///     },
+///     ..Default::default()
+/// };
+///
+/// let inputs = vec![encode(2)?, b"not an image".to_vec(), encode(64)?];
+/// let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+/// let results = runtime.block_on(voxelify::voxelize_all(inputs, &options, decode, 2));
+///
+/// assert_eq!(&results[0].as_ref().unwrap()[..4], b"glTF");
+/// assert!(results[1].is_err());
+/// assert!(matches!(results[2], Err(VoxelifyError::TooManyPixels { width: 64, height: 64, .. })));
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub async fn voxelize_all<B>(
+    inputs: Vec<B>,
+    options: &VoxelifyOptions,
+    decode: DecodeOptions,
+    concurrency: usize,
+) -> Vec<Result<Vec<u8>, VoxelifyError>>
+where
+    B: AsRef<[u8]> + Send + 'static,
+{
+    let concurrency = match concurrency {
+        0 => std::thread::available_parallelism().map_or(1, NonZeroUsize::get),
+        concurrency => concurrency,
+    };
+    let permits = Arc::new(Semaphore::new(concurrency));
+    let options = Arc::new(options.clone());
+
+    // Waiting for a permit before spawning keeps the queued images off the blocking pool.
+    let mut tasks = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        let permit = Arc::clone(&permits)
+            .acquire_owned()
+            .await
+            .expect("the semaphore is never closed");
+        let options = Arc::clone(&options);
+
+        tasks.push(tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            voxelize(input.as_ref(), &options, decode)
+        }));
+    }
+
+    let mut results = Vec::with_capacity(tasks.len());
+    for task in tasks {
+        results.push(task.await.unwrap_or_else(|error| Err(error.into())));
+    }
+    results
+}
+
+fn voxelize(
+    bytes: &[u8],
+    options: &VoxelifyOptions,
+    decode: DecodeOptions,
+) -> Result<Vec<u8>, VoxelifyError> {
+    let image = image_from_bytes_with_options(bytes, decode)?;
+    let vertices = image_to_vertices_with_options(&image, options);
+    let root = create_gltf_root(&vertices, None, options);
+    create_glb_owned(&root, &vertices, options)
+}
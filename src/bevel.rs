@@ -0,0 +1,132 @@
+use nalgebra::Vector2;
+use std::f32::consts::FRAC_1_SQRT_2;
+
+use crate::{create_quad, is_filled, BorderMode, Vertex};
+
+/// Creates the top of a beveled voxel
+/// The top face is inset on every exposed side and joined to the lowered side faces by slanted quads.
+/// Where a beveled neighbor meets a voxel that isn't beveled, the small triangle left between them is filled.
+pub(crate) fn create_bevel_verticies(
+    image: &image::DynamicImage,
+    pos: Vector2<u32>,
+    color: [f32; 3],
+    height: f32,
+    bevel: f32,
+    border: BorderMode,
+) -> Vec<Vertex> {
+    let (x, y) = (pos.x as i64, pos.y as i64);
+    let filled = |dx: i64, dy: i64| is_filled(image, x + dx, y + dy, border);
+
+    let (x0, y0) = (pos.x as f32, pos.y as f32);
+    let (x1, y1) = (x0 + 1.0, y0 + 1.0);
+    let base = height - bevel;
+
+    let left = !filled(-1, 0);
+    let right = !filled(1, 0);
+    let forward = !filled(0, -1);
+    let back = !filled(0, 1);
+
+    let inset = |exposed: bool| if exposed { bevel } else { 0.0 };
+    let (ix0, ix1) = (x0 + inset(left), x1 - inset(right));
+    let (iy0, iy1) = (y0 + inset(forward), y1 - inset(back));
+
+    let mut vertices = Vec::new();
+
+    vertices.extend(create_quad(
+        [
+            [ix0, iy0, height],
+            [ix1, iy0, height],
+            [ix0, iy1, height],
+            [ix1, iy1, height],
+        ],
+        [0.0, 0.0, 1.0],
+        color,
+    ));
+
+    let slopes = [
+        (
+            left,
+            [
+                [x0, y0, base],
+                [x0, y1, base],
+                [ix0, iy0, height],
+                [ix0, iy1, height],
+            ],
+            [-FRAC_1_SQRT_2, 0.0, FRAC_1_SQRT_2],
+        ),
+        (
+            right,
+            [
+                [x1, y0, base],
+                [x1, y1, base],
+                [ix1, iy0, height],
+                [ix1, iy1, height],
+            ],
+            [FRAC_1_SQRT_2, 0.0, FRAC_1_SQRT_2],
+        ),
+        (
+            forward,
+            [
+                [x0, y0, base],
+                [x1, y0, base],
+                [ix0, iy0, height],
+                [ix1, iy0, height],
+            ],
+            [0.0, -FRAC_1_SQRT_2, FRAC_1_SQRT_2],
+        ),
+        (
+            back,
+            [
+                [x0, y1, base],
+                [x1, y1, base],
+                [ix0, iy1, height],
+                [ix1, iy1, height],
+            ],
+            [0.0, FRAC_1_SQRT_2, FRAC_1_SQRT_2],
+        ),
+    ];
+
+    for (exposed, corners, normal) in slopes {
+        if exposed {
+            vertices.extend(create_quad(corners, normal, color));
+        }
+    }
+
+    // A triangle is stored as a quad with a repeated corner so every face keeps six vertices.
+    for (dx, dy) in [(-1, -1), (1, -1), (-1, 1), (1, 1)] {
+        let corner_x = if dx > 0 { x1 } else { x0 };
+        let corner_y = if dy > 0 { y1 } else { y0 };
+
+        // The neighbor along X is beveled towards Y, while this voxel isn't.
+        if filled(dx, 0) && !filled(dx, dy) && filled(0, dy) {
+            let tip = [corner_x, corner_y - dy as f32 * bevel, height];
+            vertices.extend(create_quad(
+                [
+                    [corner_x, corner_y, base],
+                    [corner_x, corner_y, height],
+                    tip,
+                    tip,
+                ],
+                [dx as f32, 0.0, 0.0],
+                color,
+            ));
+        }
+
+        // The neighbor along Y is beveled towards X, while this voxel isn't.
+        if filled(0, dy) && !filled(dx, dy) && filled(dx, 0) {
+            let tip = [corner_x - dx as f32 * bevel, corner_y, height];
+            vertices.extend(create_quad(
+                [
+                    [corner_x, corner_y, base],
+                    [corner_x, corner_y, height],
+                    tip,
+                    tip,
+                ],
+                [0.0, dy as f32, 0.0],
+                color,
+            ));
+        }
+    }
+
+    vertices
+}
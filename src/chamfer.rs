@@ -0,0 +1,137 @@
+use nalgebra::Vector2;
+use std::f32::consts::FRAC_1_SQRT_2;
+
+use crate::{create_quad, is_filled, BorderMode, Vertex};
+
+/// Length of the sides cut off a corner, kept under half a voxel so two cuts on the same side never meet
+const CHAMFER_SIZE: f32 = 1.0 / 3.0;
+
+/// Corners of a voxel in the order they're walked around its outline, with the direction they point to
+const CORNERS: [(f32, f32, i64, i64); 4] = [
+    (0.0, 0.0, -1, -1),
+    (1.0, 0.0, 1, -1),
+    (1.0, 1.0, 1, 1),
+    (0.0, 1.0, -1, 1),
+];
+
+/// Returns true if at least one corner of the voxel is cut
+pub(crate) fn is_chamfered(
+    image: &image::DynamicImage,
+    pos: Vector2<u32>,
+    border: BorderMode,
+) -> bool {
+    cut_corners(image, pos, border).contains(&true)
+}
+
+/// Tells which corners of the voxel are cut, in the order of `CORNERS`
+fn cut_corners(image: &image::DynamicImage, pos: Vector2<u32>, border: BorderMode) -> [bool; 4] {
+    let (x, y) = (pos.x as i64, pos.y as i64);
+    let filled = |dx: i64, dy: i64| is_filled(image, x + dx, y + dy, border);
+
+    CORNERS.map(|(_, _, dx, dy)| {
+        !filled(dx, 0) && !filled(0, dy) && !filled(dx, dy) && (filled(dx, -dy) || filled(-dx, dy))
+    })
+}
+
+/// Creates a voxel whose outer corners on a staircase of the silhouette are cut at 45°
+/// A corner is cut when both of its sides are exposed and a neighbor on either side continues the staircase,
+/// the corners of straight edges are kept. Returns `None` when no corner is cut.
+/// The cuts only touch exposed sides, so the voxel still meets its neighbors on whole edges.
+pub(crate) fn create_chamfered_verticies(
+    image: &image::DynamicImage,
+    pos: Vector2<u32>,
+    color: [f32; 3],
+    floor: f32,
+    ceiling: f32,
+    border: BorderMode,
+) -> Option<Vec<Vertex>> {
+    let (x, y) = (pos.x as i64, pos.y as i64);
+    let filled = |dx: i64, dy: i64| is_filled(image, x + dx, y + dy, border);
+    let cuts = cut_corners(image, pos, border);
+
+    // The outline of the top face, every point holds the normal of the wall on the edge starting at it.
+    let mut outline: Vec<([f32; 2], Option<[f32; 3]>)> = Vec::with_capacity(9);
+
+    for (index, &(cx, cy, dx, dy)) in CORNERS.iter().enumerate() {
+        let (px, py, ..) = CORNERS[(index + 3) % 4];
+        let (nx, ny, ..) = CORNERS[(index + 1) % 4];
+        let outgoing = [nx - cx, ny - cy];
+        // The edge towards the next corner is exposed when the neighbor on its outer side is empty.
+        let (sx, sy) = (outgoing[1] as i64, -outgoing[0] as i64);
+        let wall = (!filled(sx, sy)).then_some([sx as f32, sy as f32, 0.0]);
+
+        let corner = [pos.x as f32 + cx, pos.y as f32 + cy];
+        if cuts[index] {
+            let incoming = [cx - px, cy - py];
+            outline.push((
+                [
+                    corner[0] - incoming[0] * CHAMFER_SIZE,
+                    corner[1] - incoming[1] * CHAMFER_SIZE,
+                ],
+                Some([dx as f32 * FRAC_1_SQRT_2, dy as f32 * FRAC_1_SQRT_2, 0.0]),
+            ));
+            outline.push((
+                [
+                    corner[0] + outgoing[0] * CHAMFER_SIZE,
+                    corner[1] + outgoing[1] * CHAMFER_SIZE,
+                ],
+                wall,
+            ));
+        } else {
+            outline.push((corner, wall));
+        }
+    }
+
+    if outline.len() == CORNERS.len() {
+        return None;
+    }
+
+    // The top is made of quads, an odd outline gets an extra point halfway along an exposed edge.
+    // Starting the outline at that point keeps it out of the middle of a quad, where it would make a sliver.
+    if outline.len() % 2 == 1 {
+        let index = outline.iter().position(|(_, wall)| wall.is_some())?;
+        let (start, wall) = outline[index];
+        let (end, _) = outline[(index + 1) % outline.len()];
+        let middle = [(start[0] + end[0]) / 2.0, (start[1] + end[1]) / 2.0];
+
+        outline.insert(index + 1, (middle, wall));
+        outline.rotate_left(index + 1);
+    }
+
+    let mut vertices = Vec::new();
+
+    for (z, normal) in [(ceiling, [0.0, 0.0, 1.0]), (floor, [0.0, 0.0, -1.0])] {
+        let point = |index: usize| {
+            let [x, y] = outline[index].0;
+            [x, y, z]
+        };
+
+        for index in (1..outline.len() - 1).step_by(2) {
+            vertices.extend(create_quad(
+                [point(0), point(index), point(index + 2), point(index + 1)],
+                normal,
+                color,
+            ));
+        }
+    }
+
+    for (index, &(start, wall)) in outline.iter().enumerate() {
+        let Some(normal) = wall else {
+            continue;
+        };
+        let (end, _) = outline[(index + 1) % outline.len()];
+
+        vertices.extend(create_quad(
+            [
+                [start[0], start[1], floor],
+                [end[0], end[1], floor],
+                [start[0], start[1], ceiling],
+                [end[0], end[1], ceiling],
+            ],
+            normal,
+            color,
+        ));
+    }
+
+    Some(vertices)
+}
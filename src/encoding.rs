@@ -0,0 +1,214 @@
+use gltf::json::accessor::{ComponentType, Type};
+use gltf::json::mesh::Semantic;
+
+use crate::{align4, bounding_coords, ColorTarget, Vertex, VoxelifyOptions};
+
+/// Vertex attributes stored in the BIN buffer, in the order they're laid out in a vertex
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(crate) enum Attribute {
+    Position,
+    Normal,
+    Color,
+    TexCoord,
+    Tangent,
+}
+
+impl Attribute {
+    pub fn semantic(self) -> Semantic {
+        match self {
+            Attribute::Position => Semantic::Positions,
+            Attribute::Normal => Semantic::Normals,
+            Attribute::Color => Semantic::Colors(0),
+            Attribute::TexCoord => Semantic::TexCoords(0),
+            Attribute::Tangent => Semantic::Tangents,
+        }
+    }
+
+    /// Name of the glTF attribute
+    pub fn name(self) -> &'static str {
+        match self {
+            Attribute::Position => "POSITION",
+            Attribute::Normal => "NORMAL",
+            Attribute::Color => "COLOR_0",
+            Attribute::TexCoord => "TEXCOORD_0",
+            Attribute::Tangent => "TANGENT",
+        }
+    }
+}
+
+/// Maps positions to unsigned integers for `KHR_mesh_quantization`
+/// The original positions are restored by the node translation and scale
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct Quantization {
+    pub offset: [f32; 3],
+    pub step: [f32; 3],
+}
+
+impl Quantization {
+    /// Voxel grids are made of integers, those axes are stored exactly with a step of one
+    /// Any other axis is spread over the whole `u16` range
+    pub fn new(vertices: &[Vertex]) -> Self {
+        let (min, max) = bounding_coords(vertices);
+        let mut step = [1.0; 3];
+
+        for axis in 0..3 {
+            let range = max[axis] - min[axis];
+            let integral = vertices
+                .iter()
+                .all(|vertex| vertex.position[axis].fract() == 0.0);
+
+            if range > 0.0 && !(integral && range <= u16::MAX as f32) {
+                step[axis] = range / u16::MAX as f32;
+            }
+        }
+
+        Self { offset: min, step }
+    }
+
+    pub fn quantize(&self, position: [f32; 3]) -> [u16; 3] {
+        let mut quantized = [0; 3];
+
+        for axis in 0..3 {
+            let value = (position[axis] - self.offset[axis]) / self.step[axis];
+            quantized[axis] = value.round().clamp(0.0, u16::MAX as f32) as u16;
+        }
+
+        quantized
+    }
+}
+
+/// Describes how each vertex attribute is written to the BIN buffer
+#[derive(Clone, Debug)]
+pub(crate) struct VertexEncoding {
+    pub quantization: Option<Quantization>,
+    /// Normals are stored as normalized `i8`
+    pub quantized_normals: bool,
+    /// Attributes stored for every vertex, in the order they're laid out
+    pub attributes: Vec<Attribute>,
+    /// Colors carry the alpha of the pixel as a fourth component
+    pub color_alpha: bool,
+    pub color_target: ColorTarget,
+}
+
+impl VertexEncoding {
+    pub fn new(vertices: &[Vertex], options: &VoxelifyOptions) -> Self {
+        let mut attributes = if options.points {
+            vec![Attribute::Position, Attribute::Color]
+        } else if options.embed_texture {
+            // The embedded texture holds the colors, the vertex colors would only darken it.
+            vec![Attribute::Position, Attribute::Normal, Attribute::TexCoord]
+        } else {
+            let mut attributes = vec![Attribute::Position, Attribute::Normal, Attribute::Color];
+            // Tangents are only meaningful alongside the texture coordinates they're derived from.
+            if options.texture_coordinates || options.tangents {
+                attributes.push(Attribute::TexCoord);
+            }
+            if options.tangents {
+                attributes.push(Attribute::Tangent);
+            }
+            attributes
+        };
+        if !has_vertex_colors(options) {
+            attributes.retain(|&attribute| attribute != Attribute::Color);
+        }
+
+        Self {
+            quantization: options
+                .quantize_positions
+                .then(|| Quantization::new(vertices)),
+            quantized_normals: options.quantize_normals,
+            attributes,
+            color_alpha: options.vertex_alpha,
+            color_target: options.color_target,
+        }
+    }
+
+    /// Color stored for the vertex, converted for the target
+    pub fn rgb(&self, vertex: &Vertex) -> [f32; 3] {
+        self.color_target.convert(vertex.color)
+    }
+
+    /// Same as `rgb` with the alpha of the vertex, for colors that carry it
+    pub fn rgba(&self, vertex: &Vertex) -> [f32; 4] {
+        let [r, g, b] = self.rgb(vertex);
+        [r, g, b, vertex.alpha]
+    }
+
+    pub fn accessor_type(&self, attribute: Attribute) -> Type {
+        match attribute {
+            Attribute::Color if self.color_alpha => Type::Vec4,
+            Attribute::TexCoord => Type::Vec2,
+            Attribute::Tangent => Type::Vec4,
+            _ => Type::Vec3,
+        }
+    }
+
+    pub fn component_type(&self, attribute: Attribute) -> ComponentType {
+        match attribute {
+            Attribute::Position if self.quantization.is_some() => ComponentType::U16,
+            Attribute::Normal if self.quantized_normals => ComponentType::I8,
+            _ => ComponentType::F32,
+        }
+    }
+
+    /// Integers of the attribute stand for values from -1 to 1
+    pub fn normalized(&self, attribute: Attribute) -> bool {
+        attribute == Attribute::Normal && self.quantized_normals
+    }
+
+    /// Normal stored for the vertex, the components of quantized normals are multiples of 1/127
+    pub fn quantize_normal(&self, vertex: &Vertex) -> [i8; 3] {
+        vertex
+            .normal
+            .map(|component| (component * i8::MAX as f32).round() as i8)
+    }
+
+    /// Size of a single element of the attribute, padded to four bytes as required by glTF
+    pub fn element_size(&self, attribute: Attribute) -> usize {
+        align4(self.accessor_type(attribute).multiplicity() * self.component_type(attribute).size())
+    }
+
+    /// Size of a whole vertex when the attributes are interleaved
+    pub fn stride(&self) -> usize {
+        self.attributes
+            .iter()
+            .map(|attribute| self.element_size(*attribute))
+            .sum()
+    }
+
+    /// Appends the little endian bytes of the vertex attribute, including the padding
+    pub fn write(&self, attribute: Attribute, vertex: &Vertex, buffer: &mut Vec<u8>) {
+        let start = buffer.len();
+
+        match (attribute, self.quantization) {
+            (Attribute::Position, Some(quantization)) => {
+                for component in quantization.quantize(vertex.position) {
+                    buffer.extend_from_slice(&component.to_le_bytes());
+                }
+            }
+            (Attribute::Position, None) => write_f32s(&vertex.position, buffer),
+            (Attribute::Normal, _) if self.quantized_normals => buffer.extend(
+                self.quantize_normal(vertex)
+                    .map(|component| component.to_le_bytes()[0]),
+            ),
+            (Attribute::Normal, _) => write_f32s(&vertex.normal, buffer),
+            (Attribute::Color, _) if self.color_alpha => write_f32s(&self.rgba(vertex), buffer),
+            (Attribute::Color, _) => write_f32s(&self.rgb(vertex), buffer),
+            (Attribute::TexCoord, _) => write_f32s(&vertex.uv, buffer),
+            (Attribute::Tangent, _) => write_f32s(&vertex.tangent, buffer),
+        }
+
+        buffer.resize(start + self.element_size(attribute), 0);
+    }
+}
+
+/// A monochrome model takes its color from the material, unless the colors carry the alpha
+pub(crate) fn has_vertex_colors(options: &VoxelifyOptions) -> bool {
+    options.monochrome.is_none() || options.vertex_alpha
+}
+
+fn write_f32s(components: &[f32], buffer: &mut Vec<u8>) {
+    for component in components {
+        buffer.extend_from_slice(&component.to_le_bytes());
+    }
+}
@@ -2,10 +2,58 @@ use std::fmt::Debug;
 use std::num::TryFromIntError;
 use thiserror::Error;
 
+use crate::OpenEdge;
+
 #[derive(Error, Debug)]
 pub enum VoxelifyError {
     #[error("Failed to serialize glTF")]
     SerializationError(#[from] gltf::json::Error),
     #[error("File size exceeds binary glTF limit")]
     SizeError(#[from] TryFromIntError),
+    #[error("Failed to decode image")]
+    DecodeError(#[from] image::ImageError),
+    #[error("Failed to decode PNG")]
+    PngError(#[from] png::DecodingError),
+    #[error("Failed to write glTF binary")]
+    GlbError(#[from] gltf::Error),
+    #[error("Failed to read image")]
+    IoError(#[from] std::io::Error),
+    #[error("Depth map is {}x{} but the image is {}x{}", .depth.0, .depth.1, .image.0, .image.1)]
+    DimensionMismatch {
+        image: (u32, u32),
+        depth: (u32, u32),
+    },
+    #[error("The {channel} channel is {}x{} but the red one is {}x{}", .found.0, .found.1, .red.0, .red.1)]
+    ChannelMismatch {
+        channel: &'static str,
+        red: (u32, u32),
+        found: (u32, u32),
+    },
+    #[error("Image is {width}x{height}, which is over the limit of {max_pixels} pixels")]
+    TooManyPixels {
+        width: u32,
+        height: u32,
+        max_pixels: u64,
+    },
+    #[cfg(feature = "icc")]
+    #[error("Failed to apply the ICC profile of the image")]
+    ColorProfileError(#[from] moxcms::CmsError),
+    #[error("Failed to fetch remote image: {0}")]
+    RemoteError(String),
+    #[error("Failed to rasterize the SVG: {0}")]
+    SvgError(String),
+    #[error("Generated glTF is invalid: {}", .0.join("; "))]
+    ValidationError(Vec<String>),
+    #[error("A tile of a single voxel has {vertices} vertices, over the limit of {max_vertices}")]
+    TooManyVertices {
+        vertices: usize,
+        max_vertices: usize,
+    },
+    #[error("Can't append to the GLB, {0}")]
+    AppendError(String),
+    #[error("Mesh isn't watertight, {} edges aren't shared by exactly two triangles", .0.len())]
+    NotWatertight(Vec<OpenEdge>),
+    #[cfg(feature = "tokio")]
+    #[error("Conversion task panicked or was cancelled")]
+    TaskError(#[from] tokio::task::JoinError),
 }
@@ -0,0 +1,57 @@
+use moxcms::{ColorProfile, DataColorSpace, Layout, TransformOptions};
+
+use crate::VoxelifyError;
+
+/// Levels every channel may move by for a profile to still count as sRGB, for rounding in the conversion
+const SRGB_TOLERANCE: u8 = 1;
+
+/// Converts the colors of the image from the ICC profile it was decoded with to sRGB
+/// The image is converted to 8 bits per channel, which is all the voxels keep of it.
+pub(crate) fn convert_to_srgb(
+    image: &image::DynamicImage,
+    icc: &[u8],
+) -> Result<image::DynamicImage, VoxelifyError> {
+    let profile = ColorProfile::new_from_slice(icc)?;
+    let srgb = ColorProfile::new_srgb();
+    let mut converted = image::RgbaImage::new(image.width(), image.height());
+
+    // Gray profiles only describe one channel, the gray is spread to the three channels of sRGB.
+    if profile.color_space == DataColorSpace::Gray {
+        let transform = profile.create_transform_8bit(
+            Layout::GrayAlpha,
+            &srgb,
+            Layout::Rgba,
+            TransformOptions::default(),
+        )?;
+        transform.transform(image.to_luma_alpha8().as_raw(), &mut converted)?;
+    } else {
+        let transform = profile.create_transform_8bit(
+            Layout::Rgba,
+            &srgb,
+            Layout::Rgba,
+            TransformOptions::default(),
+        )?;
+        transform.transform(image.to_rgba8().as_raw(), &mut converted)?;
+    }
+
+    Ok(image::DynamicImage::ImageRgba8(converted))
+}
+
+/// Tells whether the profile leaves the colors as they are, like the sRGB profiles embedded by most editors
+/// The profile is checked on a grid of colors instead of by its name, which isn't standardized.
+pub(crate) fn is_srgb(icc: &[u8]) -> bool {
+    // Every channel takes 16 levels, from 0 to 255 by steps of 17.
+    let grid = image::RgbaImage::from_fn(16 * 16 * 16, 1, |index, _| {
+        let level = |shift: u32| ((index >> shift) & 0x0F) as u8 * 17;
+        image::Rgba([level(8), level(4), level(0), u8::MAX])
+    });
+    let grid = image::DynamicImage::ImageRgba8(grid);
+
+    convert_to_srgb(&grid, icc).is_ok_and(|converted| {
+        converted
+            .as_bytes()
+            .iter()
+            .zip(grid.as_bytes())
+            .all(|(converted, original)| converted.abs_diff(*original) <= SRGB_TOLERANCE)
+    })
+}
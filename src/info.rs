@@ -0,0 +1,61 @@
+use std::collections::HashSet;
+
+use image::GenericImageView;
+
+/// Facts about an image that decide how to voxelify it, see `ImageInfo::inspect`
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ImageInfo {
+    pub width: u32,
+    pub height: u32,
+    /// The pixel format stores an alpha channel, even when every pixel is opaque
+    pub has_alpha_channel: bool,
+    /// At least one pixel isn't fully opaque, so the alpha culls or blends voxels
+    pub contains_alpha: bool,
+    /// Every pixel is a shade of gray, whatever the pixel format
+    pub grayscale: bool,
+    /// Distinct RGBA colors among the pixels, in 8 bits per channel
+    pub distinct_colors: usize,
+}
+
+impl ImageInfo {
+    /// Reads the facts in a single pass over the pixels
+    ///
+    /// ```
+    /// use voxelify::ImageInfo;
+    ///
+    /// let mut sprite = image::RgbaImage::from_pixel(4, 2, image::Rgba([255, 0, 0, 255]));
+    /// sprite.put_pixel(0, 0, image::Rgba([0, 0, 0, 0]));
+    /// let info = ImageInfo::inspect(&image::DynamicImage::ImageRgba8(sprite));
+    /// assert_eq!((info.width, info.height), (4, 2));
+    /// assert!(info.has_alpha_channel && info.contains_alpha);
+    /// assert!(!info.grayscale);
+    /// assert_eq!(info.distinct_colors, 2);
+    ///
+    /// let mask = image::GrayImage::from_fn(3, 3, |x, _| image::Luma([x as u8 * 100]));
+    /// let info = ImageInfo::inspect(&image::DynamicImage::ImageLuma8(mask));
+    /// assert!(!info.has_alpha_channel && !info.contains_alpha);
+    /// assert!(info.grayscale);
+    /// assert_eq!(info.distinct_colors, 3);
+    /// ```
+    pub fn inspect(image: &image::DynamicImage) -> Self {
+        let (width, height) = image.dimensions();
+        let mut colors = HashSet::new();
+        let (mut contains_alpha, mut grayscale) = (false, true);
+
+        for (_, _, pixel) in image.pixels() {
+            let [r, g, b, a] = pixel.0;
+            contains_alpha |= a < u8::MAX;
+            grayscale &= r == g && g == b;
+            colors.insert(pixel.0);
+        }
+
+        Self {
+            width,
+            height,
+            has_alpha_channel: image.color().has_alpha(),
+            contains_alpha,
+            grayscale,
+            distinct_colors: colors.len(),
+        }
+    }
+}
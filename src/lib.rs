@@ -1,17 +1,82 @@
 use gltf::json::validation::USize64;
 use gltf::json::{self, Index};
 use gltf::json::{buffer, validation::Checked::Valid};
-use image::GenericImageView;
-use nalgebra::Vector2;
+use image::{GenericImageView, ImageDecoder, Pixel};
+use nalgebra::{Quaternion, UnitQuaternion, Vector2, Vector3};
 use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{BufReader, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::Path;
 
-use self::error::VoxelifyError;
+use self::encoding::{has_vertex_colors, Attribute, VertexEncoding};
+use self::mask::Mask;
+use self::rounding::RoundedVoxel;
 
+#[cfg(feature = "tokio")]
+pub use self::batch::voxelize_all;
+pub use self::error::VoxelifyError;
+pub use self::info::ImageInfo;
+pub use self::options::{
+    Axis, Background, Base, BorderMode, BufferLayout, ColorAdjustment, ColorHeights, ColorJitter,
+    ColorMapping, ColorTarget, DecodeLimits, DecodeOptions, Handedness, HeightNoise, LabelColors,
+    ModelScale, PixelOrigin, Provenance, VoxelifyOptions,
+};
+pub use self::palette::IndexedImage;
+#[cfg(feature = "remote")]
+pub use self::remote::{fetch_image, is_remote_url};
+#[cfg(feature = "svg")]
+pub use self::svg::rasterize_svg;
+pub use self::thumbnail::render_thumbnail;
+#[cfg(feature = "usdz")]
+pub use self::usdz::{create_usda, write_usdz};
+pub use self::validate::{check_watertight, validate, OpenEdge};
+
+#[cfg(feature = "tokio")]
+mod batch;
+mod bevel;
+mod chamfer;
+mod encoding;
 mod error;
+#[cfg(feature = "icc")]
+mod icc;
+mod info;
+mod mask;
+mod options;
+mod palette;
+#[cfg(feature = "remote")]
+mod remote;
+mod rounding;
+mod seal;
+#[cfg(feature = "svg")]
+mod svg;
+mod terrain;
+mod thumbnail;
+#[cfg(feature = "usdz")]
+mod usdz;
+mod uv;
+mod validate;
+#[cfg(feature = "wasm")]
+mod wasm;
 
 const ALPHA_CHANNEL_INDEX: usize = 3;
 const RGB_MAX_VALUE: f32 = 255.0;
 const PADDING: usize = 4;
+const VERTICES_PER_FACE: usize = 6;
+const LINE_INDICES_PER_FACE: usize = 8;
+const MAX_BEVEL: f32 = 0.49;
+/// Triangles above which meshing with rounded voxels warns about the size of the model
+#[cfg(feature = "logging")]
+const ROUNDED_TRIANGLES_WARNING: usize = 1_000_000;
+/// Width and height in pixels of the tiles meshed in parallel
+#[cfg(feature = "parallel")]
+const TILE_SIZE: u32 = 256;
+const MESH_QUANTIZATION_EXTENSION: &str = "KHR_mesh_quantization";
+const EMISSIVE_STRENGTH_EXTENSION: &str = "KHR_materials_emissive_strength";
+const UNLIT_EXTENSION: &str = "KHR_materials_unlit";
+/// The header of a GLB file followed by the headers of its JSON and BIN chunks
+const GLB_HEADERS_LENGTH: usize = 12 + 8 + 8;
 
 #[derive(Copy, Clone, Debug)]
 #[repr(C)]
@@ -19,159 +84,3204 @@ pub struct Vertex {
     position: [f32; 3],
     normal: [f32; 3],
     color: [f32; 3],
+    alpha: f32,
+    uv: [f32; 2],
+    /// The bitangent is the cross product of the normal and the tangent, multiplied by `w`
+    tangent: [f32; 4],
+}
+
+/// Placement of a node, the scale is applied first, then the rotation and then the translation like in glTF
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct NodeTransform {
+    pub translation: [f32; 3],
+    /// Unit quaternion as (x, y, z, w)
+    pub rotation: [f32; 4],
+    pub scale: [f32; 3],
+}
+
+impl NodeTransform {
+    pub fn from_translation(translation: [f32; 3]) -> Self {
+        Self {
+            translation,
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for NodeTransform {
+    fn default() -> Self {
+        Self {
+            translation: [0.0; 3],
+            rotation: [0.0, 0.0, 0.0, 1.0],
+            scale: [1.0; 3],
+        }
+    }
+}
+
+/// A mesh and where it's placed in a glTF holding several meshes
+#[derive(Clone, Debug)]
+pub struct MeshEntry<'a> {
+    pub vertices: &'a [Vertex],
+    pub name: Option<String>,
+    pub transform: NodeTransform,
+    /// PNG from `bake_texture` coloring the mesh, only used with `embed_texture`
+    pub texture: Option<&'a [u8]>,
+}
+
+impl<'a> MeshEntry<'a> {
+    /// An unnamed mesh at the origin
+    pub fn new(vertices: &'a [Vertex]) -> Self {
+        Self {
+            vertices,
+            name: None,
+            transform: NodeTransform::default(),
+            texture: None,
+        }
+    }
+}
+
+impl Vertex {
+    #[inline]
+    pub fn position(&self) -> [f32; 3] {
+        self.position
+    }
+
+    #[inline]
+    pub fn normal(&self) -> [f32; 3] {
+        self.normal
+    }
+
+    /// Color of the pixel the vertex comes from, in sRGB
+    #[inline]
+    pub fn color(&self) -> [f32; 3] {
+        self.color
+    }
+
+    #[inline]
+    pub fn set_position(&mut self, position: [f32; 3]) {
+        self.position = position;
+    }
+
+    #[inline]
+    pub fn set_normal(&mut self, normal: [f32; 3]) {
+        self.normal = normal;
+    }
+
+    #[inline]
+    pub fn set_color(&mut self, color: [f32; 3]) {
+        self.color = color;
+    }
+}
+
+/// Creates a GLB object that can be written to a file, it owns all of its data
+pub fn create_glb(
+    root: &gltf::json::Root,
+    vertices: &[Vertex],
+    options: &VoxelifyOptions,
+) -> Result<gltf::binary::Glb<'static>, VoxelifyError> {
+    create_glb_multi(root, &[MeshEntry::new(vertices)], options)
+}
+
+/// Same as `create_glb`, serialized straight into the bytes of a GLB file
+///
+/// ```
+/// let image = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+///     2,
+///     2,
+///     image::Rgba([255, 0, 0, 255]),
+/// ));
+/// let options = voxelify::VoxelifyOptions::default();
+///
+/// let vertices = voxelify::image_to_vertices_with_options(&image, &options);
+/// let root = voxelify::create_gltf_root(&vertices, None, &options);
+/// let bytes = voxelify::create_glb_owned(&root, &vertices, &options)?;
+///
+/// assert_eq!(&bytes[..4], b"glTF");
+/// // The header ends with the length of the whole file.
+/// let length = u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]);
+/// assert_eq!(length as usize, bytes.len());
+/// # Ok::<(), voxelify::VoxelifyError>(())
+/// ```
+pub fn create_glb_owned(
+    root: &gltf::json::Root,
+    vertices: &[Vertex],
+    options: &VoxelifyOptions,
+) -> Result<Vec<u8>, VoxelifyError> {
+    glb_to_vec(&create_glb(root, vertices, options)?)
+}
+
+/// Same as `create_glb`, parsed back into a `gltf::Gltf` document to inspect or post-process the model
+///
+/// ```
+/// let image = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+///     2,
+///     2,
+///     image::Rgba([255, 0, 0, 255]),
+/// ));
+/// let options = voxelify::VoxelifyOptions::default();
+///
+/// let vertices = voxelify::image_to_vertices_with_options(&image, &options);
+/// let root = voxelify::create_gltf_root(&vertices, None, &options);
+/// let document = voxelify::create_gltf_document(&root, &vertices, &options)?;
+///
+/// let primitive = document.meshes().next().unwrap().primitives().next().unwrap();
+/// let reader = primitive.reader(|_| document.blob.as_deref());
+/// assert_eq!(reader.read_positions().unwrap().count(), vertices.len());
+/// # Ok::<(), voxelify::VoxelifyError>(())
+/// ```
+pub fn create_gltf_document(
+    root: &gltf::json::Root,
+    vertices: &[Vertex],
+    options: &VoxelifyOptions,
+) -> Result<gltf::Gltf, VoxelifyError> {
+    glb_to_document(&create_glb(root, vertices, options)?)
+}
+
+/// Parses a GLB object into a `gltf::Gltf` document, which holds the BIN in its `blob`
+pub fn glb_to_document(glb: &gltf::binary::Glb) -> Result<gltf::Gltf, VoxelifyError> {
+    // The validation of the `gltf` crate fails on the required extensions it doesn't know, like
+    // `KHR_mesh_quantization`, even though the model is valid.
+    Ok(gltf::Gltf::from_slice_without_validation(&glb_to_vec(
+        glb,
+    )?)?)
+}
+
+/// Vertex attribute of a raw buffer, see `write_raw_buffer`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RawAttribute {
+    /// Name of the glTF attribute holding the same data, like `POSITION`
+    pub name: &'static str,
+    /// Bytes from the start of the vertex
+    pub offset: usize,
+    pub components: usize,
+    /// `f32`, `u16` for quantized positions or `i8` for quantized normals, which are normalized
+    pub component_type: &'static str,
+}
+
+/// Layout of the vertices written by `write_raw_buffer`
+#[derive(Clone, Debug, PartialEq)]
+pub struct RawLayout {
+    pub vertex_count: usize,
+    /// Bytes between the starts of two consecutive vertices
+    pub stride: usize,
+    pub attributes: Vec<RawAttribute>,
+    /// Quantized positions are restored by multiplying them by the scale and adding the offset
+    pub position_offset: [f32; 3],
+    pub position_scale: [f32; 3],
 }
 
-/// Creates a GLB object that can be written to a file
-pub fn create_glb<'a>(
+impl RawLayout {
+    pub fn to_json(&self) -> String {
+        serde_json::json!({
+            "vertex_count": self.vertex_count,
+            "stride": self.stride,
+            "attributes": self
+                .attributes
+                .iter()
+                .map(|attribute| serde_json::json!({
+                    "name": attribute.name,
+                    "offset": attribute.offset,
+                    "components": attribute.components,
+                    "component_type": attribute.component_type,
+                }))
+                .collect::<Vec<_>>(),
+            "position_offset": self.position_offset,
+            "position_scale": self.position_scale,
+        })
+        .to_string()
+    }
+}
+
+/// Writes only the interleaved vertex attributes, for engines that set up their own vertex layout
+/// Every attribute is padded to four bytes like in the glTF buffer, whatever the layout of the options.
+/// Every three vertices make a triangle, or every vertex is a point with `points`.
+///
+/// ```
+/// let image = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+///     2,
+///     2,
+///     image::Rgba([255, 0, 0, 255]),
+/// ));
+/// let options = voxelify::VoxelifyOptions {
+///     quantize_normals: true,
+///     ..Default::default()
+/// };
+///
+/// let vertices = voxelify::image_to_vertices_with_options(&image, &options);
+/// let mut buffer = Vec::new();
+/// let layout = voxelify::write_raw_buffer(&mut buffer, &vertices, &options)?;
+///
+/// // The normals of the faces along the axes are stored exactly.
+/// let normal = layout.attributes.iter().find(|attribute| attribute.name == "NORMAL").unwrap();
+/// assert_eq!(normal.component_type, "i8");
+/// for (index, vertex) in vertices.iter().enumerate() {
+///     let start = index * layout.stride + normal.offset;
+///     let decoded = buffer[start..start + 3].iter().map(|&byte| byte as i8 as f32 / 127.0);
+///     assert!(decoded.eq(vertex.normal()));
+/// }
+/// # Ok::<(), voxelify::VoxelifyError>(())
+/// ```
+pub fn write_raw_buffer<W: Write>(
+    mut writer: W,
+    vertices: &[Vertex],
+    options: &VoxelifyOptions,
+) -> Result<RawLayout, VoxelifyError> {
+    let encoding = VertexEncoding::new(vertices, options);
+    writer.write_all(&create_vertex_buffer(
+        vertices,
+        &encoding,
+        BufferLayout::Interleaved,
+    ))?;
+
+    let mut offset = 0;
+    let attributes = encoding
+        .attributes
+        .iter()
+        .map(|&attribute| {
+            let raw = RawAttribute {
+                name: attribute.name(),
+                offset,
+                components: encoding.accessor_type(attribute).multiplicity(),
+                component_type: match encoding.component_type(attribute) {
+                    json::accessor::ComponentType::U16 => "u16",
+                    json::accessor::ComponentType::I8 => "i8",
+                    _ => "f32",
+                },
+            };
+            offset += encoding.element_size(attribute);
+            raw
+        })
+        .collect();
+
+    Ok(RawLayout {
+        vertex_count: vertices.len(),
+        stride: encoding.stride(),
+        attributes,
+        position_offset: encoding
+            .quantization
+            .map_or([0.0; 3], |quantization| quantization.offset),
+        position_scale: encoding
+            .quantization
+            .map_or([1.0; 3], |quantization| quantization.step),
+    })
+}
+
+/// Serializes the JSON of the root and creates its BIN apart, for packaging them in another container than GLB
+/// The BIN is padded to four bytes, the JSON isn't as only GLB needs it.
+///
+/// ```
+/// let image = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+///     2,
+///     2,
+///     image::Rgba([255, 0, 0, 255]),
+/// ));
+/// let options = voxelify::VoxelifyOptions::default();
+///
+/// let vertices = voxelify::image_to_vertices_with_options(&image, &options);
+/// let root = voxelify::create_gltf_root(&vertices, None, &options);
+/// let (json, bin) = voxelify::create_parts(&root, &vertices, &options)?;
+///
+/// let parsed: serde_json::Value = serde_json::from_str(&json)?;
+/// assert_eq!(parsed["buffers"][0]["byteLength"], bin.len());
+/// assert_eq!(bin.len() % 4, 0);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn create_parts(
     root: &gltf::json::Root,
     vertices: &[Vertex],
-) -> Result<gltf::binary::Glb<'a>, VoxelifyError> {
+    options: &VoxelifyOptions,
+) -> Result<(String, Vec<u8>), VoxelifyError> {
+    create_parts_multi(root, &[MeshEntry::new(vertices)], options)
+}
+
+/// Same as `create_parts` for the entries of `create_glb_multi`
+/// The length of the buffer in the root is calculated up front, it's the exact length of the BIN.
+///
+/// ```
+/// let image = image::DynamicImage::ImageRgba8(image::RgbaImage::from_fn(3, 3, |x, y| {
+///     image::Rgba([255, 0, 0, if (x + y) % 2 == 0 { 255 } else { 0 }])
+/// }));
+/// let base = voxelify::VoxelifyOptions::default();
+/// let variants = [
+///     voxelify::VoxelifyOptions { layout: voxelify::BufferLayout::Separate, ..base.clone() },
+///     voxelify::VoxelifyOptions { quantize_positions: true, quantize_normals: true, ..base.clone() },
+///     voxelify::VoxelifyOptions {
+///         quantize_positions: true,
+///         layout: voxelify::BufferLayout::Separate,
+///         ..base.clone()
+///     },
+///     voxelify::VoxelifyOptions { wireframe: true, ..base.clone() },
+///     voxelify::VoxelifyOptions { points: true, ..base.clone() },
+///     voxelify::VoxelifyOptions { tangents: true, vertex_alpha: true, ..base.clone() },
+///     voxelify::VoxelifyOptions { embed_texture: true, ..base.clone() },
+///     base,
+/// ];
+///
+/// for options in variants {
+///     let first = voxelify::image_to_vertices_with_options(&image, &options);
+///     let second = voxelify::image_to_vertices_with_options(&image.crop_imm(0, 0, 1, 1), &options);
+///     let png = voxelify::bake_texture(&image, &options)?;
+///     let entries = [&first, &second].map(|vertices| voxelify::MeshEntry {
+///         texture: Some(&png),
+///         ..voxelify::MeshEntry::new(vertices)
+///     });
+///
+///     let root = voxelify::create_gltf_root_multi(&entries, None, &options);
+///     let (json, bin) = voxelify::create_parts_multi(&root, &entries, &options)?;
+///     let parsed: serde_json::Value = serde_json::from_str(&json)?;
+///     assert_eq!(parsed["buffers"][0]["byteLength"], bin.len(), "{options:?}");
+///
+///     let glb = voxelify::create_glb_multi(&root, &entries, &options)?;
+///     let bytes = voxelify::glb_to_vec(&glb)?;
+///     assert_eq!(u32::from_le_bytes(bytes[8..12].try_into()?) as usize, bytes.len());
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn create_parts_multi(
+    root: &gltf::json::Root,
+    entries: &[MeshEntry],
+    options: &VoxelifyOptions,
+) -> Result<(String, Vec<u8>), VoxelifyError> {
     let json_string = json::serialize::to_string(&root)?;
+    let bin = create_bin(entries, options);
+
+    Ok((json_string, bin))
+}
+
+/// Creates the data of the entries in the order `push_mesh` lays them out in the buffer
+fn create_bin(entries: &[MeshEntry], options: &VoxelifyOptions) -> Vec<u8> {
+    let mut bin_length: usize = 0;
+    let mut bin = Vec::new();
+    for entry in entries {
+        let encoding = VertexEncoding::new(entry.vertices, options);
+        bin_length = bin_length.saturating_add(calculate_buffer_length(
+            entry.vertices.len(),
+            &encoding,
+            options,
+        ));
+        bin.extend(create_buffer(entry.vertices, &encoding, options));
+
+        if let Some(png) = embedded_texture(entry, options) {
+            bin_length = bin_length.saturating_add(align4(png.len()));
+            bin.extend_from_slice(png);
+            pad_to_4(&mut bin);
+        }
+    }
+    debug_assert_eq!(
+        bin.len(),
+        bin_length,
+        "BIN length doesn't match its calculation"
+    );
+
+    bin
+}
+
+/// Same as `create_glb` for a root created by `create_gltf_root_multi` with the same entries,
+/// or by `add_mesh_to_root` when given the meshes in the order they were added
+pub fn create_glb_multi(
+    root: &gltf::json::Root,
+    entries: &[MeshEntry],
+    options: &VoxelifyOptions,
+) -> Result<gltf::binary::Glb<'static>, VoxelifyError> {
+    #[cfg(feature = "logging")]
+    let start = std::time::Instant::now();
+
+    let (json_string, bin) = create_parts_multi(root, entries, options)?;
+    let glb = parts_to_glb(json_string, bin)?;
+
+    #[cfg(feature = "logging")]
+    log::debug!(
+        "Serialized GLB with {} bytes of JSON and {} bytes of BIN in {:?}",
+        glb.json.len(),
+        glb.bin.as_ref().map_or(0, |bin| bin.len()),
+        start.elapsed()
+    );
+
+    Ok(glb)
+}
+
+fn parts_to_glb(
+    json_string: String,
+    bin: Vec<u8>,
+) -> Result<gltf::binary::Glb<'static>, VoxelifyError> {
+    let json_offset = align4(json_string.len());
+    let bin_length = bin.len();
+
+    Ok(gltf::binary::Glb {
+        header: gltf::binary::Header {
+            magic: *b"glTF",
+            version: 2,
+            // N.B., the size of binary glTF file is limited to range of `u32`.
+            length: json_offset.saturating_add(bin_length).try_into()?,
+        },
+        bin: Some(Cow::Owned(bin)),
+        json: Cow::Owned(json_string.into_bytes()),
+    })
+}
+
+/// Adds the entries to the default scene of an existing GLB, after the meshes already in it, and returns the new
+/// root with its GLB
+/// Their data goes at the end of the BIN, the nodes, materials and extensions of the GLB are kept. The materials
+/// of the entries reuse the same ones of the GLB. With a scale in the options only the new nodes are scaled.
+///
+/// ```
+/// let image = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(2, 2, image::Rgba([255, 0, 0, 255])));
+/// let options = voxelify::VoxelifyOptions::default();
+/// let first = voxelify::image_to_vertices(&image, 1.0);
+/// let glb = voxelify::create_glb(&voxelify::create_gltf_root(&first, None, &options), &first, &options)?;
+///
+/// let second = voxelify::image_to_vertices(&image.crop_imm(0, 0, 1, 1), 3.0);
+/// let entry = voxelify::MeshEntry {
+///     transform: voxelify::NodeTransform::from_translation([4.0, 0.0, 0.0]),
+///     ..voxelify::MeshEntry::new(&second)
+/// };
+/// let (_, glb) = voxelify::append_to_glb(&glb, &[entry], &options)?;
+///
+/// let document = voxelify::glb_to_document(&glb)?;
+/// assert_eq!(document.scenes().next().unwrap().nodes().count(), 2);
+/// let counts: Vec<_> = document
+///     .meshes()
+///     .map(|mesh| {
+///         let primitive = mesh.primitives().next().unwrap();
+///         let reader = primitive.reader(|_| document.blob.as_deref());
+///         reader.read_positions().unwrap().count()
+///     })
+///     .collect();
+/// assert_eq!(counts, [first.len(), second.len()]);
+/// # Ok::<(), voxelify::VoxelifyError>(())
+/// ```
+pub fn append_to_glb(
+    glb: &gltf::binary::Glb,
+    entries: &[MeshEntry],
+    options: &VoxelifyOptions,
+) -> Result<(gltf::json::Root, gltf::binary::Glb<'static>), VoxelifyError> {
+    let mut root = json::Root::from_slice(&glb.json)?;
+    let mut bin = glb.bin.as_deref().unwrap_or_default().to_vec();
+
+    // The GLB pads its BIN, the new data starts on the boundary after the data of the buffer.
+    if let Some(buffer) = root.buffers.first_mut() {
+        if buffer.uri.is_some() {
+            return Err(VoxelifyError::AppendError(
+                "its first buffer isn't the BIN".to_string(),
+            ));
+        }
+        let length = buffer.byte_length.0 as usize;
+        if length > bin.len() {
+            return Err(VoxelifyError::AppendError(format!(
+                "its buffer has {length} bytes but the BIN only {}",
+                bin.len()
+            )));
+        }
+        bin.resize(align4(length), 0);
+        buffer.byte_length = USize64::from(bin.len());
+    }
+
+    let mut nodes: Vec<_> = entries
+        .iter()
+        .map(|entry| {
+            push_mesh(
+                &mut root,
+                entry.vertices,
+                entry.name.clone(),
+                entry.transform,
+                embedded_texture(entry, options),
+                options,
+            )
+        })
+        .collect();
+    if let Some(scale) = model_scale(entries, options) {
+        nodes = vec![root.push(json::Node {
+            children: Some(nodes),
+            scale: Some([scale; 3]),
+            ..Default::default()
+        })];
+    }
+
+    let scene = match root.scene {
+        Some(scene) => scene.value(),
+        None if root.scenes.is_empty() => {
+            root.push(json::Scene {
+                extensions: Default::default(),
+                extras: Default::default(),
+                name: None,
+                nodes: Vec::new(),
+            });
+            0
+        }
+        None => 0,
+    };
+    root.scenes[scene].nodes.extend(nodes);
+    declare_extensions(&mut root);
+
+    bin.extend(create_bin(entries, options));
+    let glb = parts_to_glb(json::serialize::to_string(&root)?, bin)?;
+    Ok((root, glb))
+}
+
+/// Serializes a GLB object into the bytes of a GLB file
+/// Both chunks and every buffer view start on a four byte boundary, as glTF requires of the accessors.
+///
+/// ```
+/// let image = image::DynamicImage::ImageRgba8(image::RgbaImage::from_fn(5, 3, |x, y| {
+///     image::Rgba([255, x as u8 * 40, 0, if (x * y) % 3 == 1 { 0 } else { 255 }])
+/// }));
+/// let base = voxelify::VoxelifyOptions::default();
+/// let variants = [
+///     voxelify::VoxelifyOptions { quantize_normals: true, layout: voxelify::BufferLayout::Separate, ..base.clone() },
+///     voxelify::VoxelifyOptions { quantize_positions: true, layout: voxelify::BufferLayout::Separate, ..base.clone() },
+///     voxelify::VoxelifyOptions { quantize_positions: true, quantize_normals: true, ..base.clone() },
+///     voxelify::VoxelifyOptions { wireframe: true, ..base.clone() },
+///     voxelify::VoxelifyOptions { embed_texture: true, ..base.clone() },
+///     base,
+/// ];
+///
+/// for options in variants {
+///     let first = voxelify::image_to_vertices_with_options(&image, &options);
+///     let second = voxelify::image_to_vertices_with_options(&image.crop_imm(1, 0, 1, 1), &options);
+///     let png = voxelify::bake_texture(&image, &options)?;
+///     let entries = [&first, &second].map(|vertices| voxelify::MeshEntry {
+///         texture: Some(&png),
+///         ..voxelify::MeshEntry::new(vertices)
+///     });
+///     let root = voxelify::create_gltf_root_multi(&entries, None, &options);
+///     let bytes = voxelify::glb_to_vec(&voxelify::create_glb_multi(&root, &entries, &options)?)?;
+///
+///     let json_length = u32::from_le_bytes(bytes[12..16].try_into()?) as usize;
+///     assert_eq!(json_length % 4, 0);
+///     assert_eq!(bytes.len() % 4, 0);
+///     let document = gltf::Gltf::from_slice_without_validation(&bytes)?;
+///     for view in document.views() {
+///         assert_eq!(view.offset() % 4, 0, "{options:?}");
+///     }
+///     for accessor in document.accessors() {
+///         assert_eq!(accessor.offset() % 4, 0, "{options:?}");
+///     }
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn glb_to_vec(glb: &gltf::binary::Glb) -> Result<Vec<u8>, VoxelifyError> {
+    Ok(glb.to_vec()?)
+}
+
+/// Serializes the root into the JSON of a GLTF file, pretty JSON is easier to read and diff while compact JSON
+/// is smaller
+///
+/// ```
+/// let options = voxelify::VoxelifyOptions::default();
+/// let root = voxelify::create_gltf_root(&[], None, &options);
+///
+/// let pretty = voxelify::gltf_to_json(&root, true)?;
+/// let compact = voxelify::gltf_to_json(&root, false)?;
+///
+/// assert!(pretty.contains('\n') && !compact.contains('\n'));
+/// assert_eq!(
+///     serde_json::from_str::<serde_json::Value>(&pretty)?,
+///     serde_json::from_str::<serde_json::Value>(&compact)?,
+/// );
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn gltf_to_json(root: &gltf::json::Root, pretty: bool) -> Result<String, VoxelifyError> {
+    Ok(if pretty {
+        json::serialize::to_string_pretty(root)?
+    } else {
+        json::serialize::to_string(root)?
+    })
+}
+
+/// Writes a GLB object as the bytes of a GLB file, for files, stdout or any other sink
+pub fn write_glb<W: Write>(glb: &gltf::binary::Glb, writer: W) -> Result<(), VoxelifyError> {
+    Ok(glb.to_writer(writer)?)
+}
+
+/// Decodes an image from an in-memory buffer, the format is guessed from the content unless the options force it
+pub fn image_from_bytes(bytes: &[u8]) -> Result<image::DynamicImage, VoxelifyError> {
+    image_from_bytes_with_limits(bytes, DecodeLimits::default())
+}
+
+/// Same as `image_from_bytes`, images over the limits are refused with an error instead of being decoded
+///
+/// ```
+/// use voxelify::{image_from_bytes_with_limits, DecodeLimits, VoxelifyError};
+///
+/// let mut png = std::io::Cursor::new(Vec::new());
+/// image::RgbaImage::new(4, 4).write_to(&mut png, image::ImageFormat::Png)?;
+///
+/// let limits = DecodeLimits {
+///     max_pixels: Some(8),
+///     ..Default::default()
+/// };
+/// let result = image_from_bytes_with_limits(png.get_ref(), limits);
+/// assert!(matches!(result, Err(VoxelifyError::TooManyPixels { width: 4, height: 4, .. })));
+/// # Ok::<(), image::ImageError>(())
+/// ```
+pub fn image_from_bytes_with_limits(
+    bytes: &[u8],
+    limits: DecodeLimits,
+) -> Result<image::DynamicImage, VoxelifyError> {
+    image_from_bytes_with_options(
+        bytes,
+        DecodeOptions {
+            limits,
+            ..Default::default()
+        },
+    )
+}
+
+/// Same as `image_from_bytes_with_limits`, with the other decoding options
+///
+/// ```
+/// use voxelify::{image_from_bytes_with_options, DecodeOptions, VoxelifyError};
+///
+/// let mut png = std::io::Cursor::new(Vec::new());
+/// image::RgbaImage::new(4, 4).write_to(&mut png, image::ImageFormat::Png)?;
+///
+/// let forced = |format| DecodeOptions {
+///     format: Some(format),
+///     ..Default::default()
+/// };
+/// assert!(image_from_bytes_with_options(png.get_ref(), forced(image::ImageFormat::Png)).is_ok());
+/// let result = image_from_bytes_with_options(png.get_ref(), forced(image::ImageFormat::Jpeg));
+/// assert!(matches!(result, Err(VoxelifyError::DecodeError(_))));
+/// # Ok::<(), image::ImageError>(())
+/// ```
+pub fn image_from_bytes_with_options(
+    bytes: &[u8],
+    options: DecodeOptions,
+) -> Result<image::DynamicImage, VoxelifyError> {
+    decode_with_options(
+        image::ImageReader::new(std::io::Cursor::new(bytes)),
+        options,
+    )
+}
+
+/// Decodes an image from any seekable source, the format is guessed from the content
+pub fn load_image_reader<R: Read + Seek>(reader: R) -> Result<image::DynamicImage, VoxelifyError> {
+    load_image_reader_with_limits(reader, DecodeLimits::default())
+}
+
+/// Same as `load_image_reader`, images over the limits are refused with an error instead of being decoded
+pub fn load_image_reader_with_limits<R: Read + Seek>(
+    reader: R,
+    limits: DecodeLimits,
+) -> Result<image::DynamicImage, VoxelifyError> {
+    load_image_reader_with_options(
+        reader,
+        DecodeOptions {
+            limits,
+            ..Default::default()
+        },
+    )
+}
+
+/// Same as `load_image_reader_with_limits`, with the other decoding options
+pub fn load_image_reader_with_options<R: Read + Seek>(
+    reader: R,
+    options: DecodeOptions,
+) -> Result<image::DynamicImage, VoxelifyError> {
+    decode_with_options(image::ImageReader::new(BufReader::new(reader)), options)
+}
+
+/// Decodes the image file at the path, falling back to the extension when the content doesn't tell the format
+pub fn load_image_path(path: impl AsRef<Path>) -> Result<image::DynamicImage, VoxelifyError> {
+    load_image_path_with_limits(path, DecodeLimits::default())
+}
+
+/// Same as `load_image_path`, images over the limits are refused with an error instead of being decoded
+pub fn load_image_path_with_limits(
+    path: impl AsRef<Path>,
+    limits: DecodeLimits,
+) -> Result<image::DynamicImage, VoxelifyError> {
+    load_image_path_with_options(
+        path,
+        DecodeOptions {
+            limits,
+            ..Default::default()
+        },
+    )
+}
+
+/// Same as `load_image_path_with_limits`, with the other decoding options
+pub fn load_image_path_with_options(
+    path: impl AsRef<Path>,
+    options: DecodeOptions,
+) -> Result<image::DynamicImage, VoxelifyError> {
+    decode_with_options(image::ImageReader::open(path)?, options)
+}
+
+fn decode_with_options<R: std::io::BufRead + Seek>(
+    mut reader: image::ImageReader<R>,
+    options: DecodeOptions,
+) -> Result<image::DynamicImage, VoxelifyError> {
+    // A forced format replaces the guess, the one from the extension of a path included.
+    match options.format {
+        Some(format) => reader.set_format(format),
+        None => reader = reader.with_guessed_format()?,
+    }
+    let limits = options.limits;
+
+    // Indexed PNGs are resolved from their palette, so every pixel gets exactly the color of its entry.
+    if reader.format() == Some(image::ImageFormat::Png) {
+        let mut source = reader.into_inner();
+        let start = source.stream_position()?;
+        if let Some(indexed) = IndexedImage::from_png_with_limits(&mut source, limits)? {
+            return with_color_profile(indexed.to_image(), indexed.icc_profile.as_deref(), options);
+        }
+        source.seek(SeekFrom::Start(start))?;
+        reader = image::ImageReader::with_format(source, image::ImageFormat::Png);
+    }
+
+    let mut decoder_limits = image::Limits::default();
+    if limits.max_alloc.is_some() {
+        decoder_limits.max_alloc = limits.max_alloc;
+    }
+    reader.limits(decoder_limits.clone());
+
+    // Only the header is read so far, the pixels are checked before allocating anything for them.
+    let mut decoder = reader.into_decoder()?;
+    let (width, height) = decoder.dimensions();
+    if let Some(max_pixels) = limits.max_pixels {
+        if width as u64 * height as u64 > max_pixels {
+            return Err(VoxelifyError::TooManyPixels {
+                width,
+                height,
+                max_pixels,
+            });
+        }
+    }
+
+    // `ImageReader::decode` also counts the decoded image against the allocation limit.
+    decoder_limits.reserve(decoder.total_bytes())?;
+
+    let icc = decoder.icc_profile()?;
+    let image = image::DynamicImage::from_decoder(decoder)?;
+    with_color_profile(image, icc.as_deref(), options)
+}
+
+/// Converts the decoded image from its embedded profile when the options apply it
+#[cfg(feature = "icc")]
+fn with_color_profile(
+    image: image::DynamicImage,
+    icc: Option<&[u8]>,
+    options: DecodeOptions,
+) -> Result<image::DynamicImage, VoxelifyError> {
+    // Profiles that leave the colors as they are don't need a conversion, nor a warning.
+    match icc.filter(|icc| !icc::is_srgb(icc)) {
+        Some(icc) if options.apply_icc => icc::convert_to_srgb(&image, icc),
+        Some(_) => {
+            #[cfg(feature = "logging")]
+            log::warn!(
+                "The image has a color profile other than sRGB, its colors may differ from editors"
+            );
+            Ok(image)
+        }
+        None => Ok(image),
+    }
+}
+
+/// Without the `icc` feature the profile is ignored and the colors are used as they are
+#[cfg(not(feature = "icc"))]
+fn with_color_profile(
+    image: image::DynamicImage,
+    _icc: Option<&[u8]>,
+    _options: DecodeOptions,
+) -> Result<image::DynamicImage, VoxelifyError> {
+    Ok(image)
+}
+
+/// Where the BIN that a GLTF file at `gltf_path` references by `uri` is written
+/// A relative URI is resolved from the directory of the GLTF file. An absolute URI, with a scheme like `https:` or
+/// starting with a `/`, points outside of the output and gives `None`, the BIN is only referenced.
+///
+/// ```
+/// use std::path::{Path, PathBuf};
+/// use voxelify::bin_path;
+///
+/// let gltf = Path::new("out/model.gltf");
+/// assert_eq!(bin_path(gltf, "model.bin"), Some(PathBuf::from("out/model.bin")));
+/// assert_eq!(bin_path(gltf, "buffers/model.bin"), Some(PathBuf::from("out/buffers/model.bin")));
+/// assert_eq!(bin_path(gltf, "https://cdn.example.com/model.bin"), None);
+/// assert_eq!(bin_path(gltf, "/static/model.bin"), None);
+/// ```
+pub fn bin_path(gltf_path: &Path, uri: &str) -> Option<std::path::PathBuf> {
+    // A scheme is a letter followed by letters, digits, `+`, `-` or `.` up to a colon.
+    let scheme = uri.split_once(':').is_some_and(|(scheme, _)| {
+        scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+            && scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+    });
+    if scheme || uri.starts_with('/') {
+        return None;
+    }
+
+    let directory = gltf_path.parent().unwrap_or(Path::new(""));
+    Some(directory.join(uri))
+}
+
+/// Creates the root JSON object for GLTF
+/// The asset names voxelify and its version as the generator, with the copyright of the options if there's one
+///
+/// ```
+/// let options = voxelify::VoxelifyOptions {
+///     copyright: Some("CC0".to_string()),
+///     ..Default::default()
+/// };
+/// let root = voxelify::create_gltf_root(&[], None, &options);
+/// let json = gltf::json::serialize::to_string(&root)?;
+///
+/// assert!(json.contains(concat!("\"generator\":\"voxelify ", env!("CARGO_PKG_VERSION"), "\"")));
+/// assert!(json.contains("\"copyright\":\"CC0\""));
+/// assert!(json.contains("\"version\":\"2.0\""));
+/// assert!(!json.contains("extensionsRequired"));
+/// # Ok::<(), gltf::json::Error>(())
+/// ```
+pub fn create_gltf_root(
+    vertices: &[Vertex],
+    uri: Option<String>,
+    options: &VoxelifyOptions,
+) -> gltf::json::Root {
+    create_gltf_root_multi(&[MeshEntry::new(vertices)], uri, options)
+}
+
+/// Creates the root JSON object for several meshes sharing one buffer, with a node per mesh in a single scene
+/// Every accessor has the bounds of its own data.
+///
+/// ```
+/// let image = image::DynamicImage::ImageRgba8(image::RgbaImage::from_fn(3, 2, |x, y| {
+///     image::Rgba([x as u8 * 60 + 10, 200 - y as u8 * 90, 30, 255])
+/// }));
+/// let options = voxelify::VoxelifyOptions::default();
+/// let first = voxelify::image_to_vertices_with_options(&image, &options);
+/// let second = voxelify::image_to_vertices(&image.crop_imm(1, 0, 1, 1), 4.0);
+/// let entries = [voxelify::MeshEntry::new(&first), voxelify::MeshEntry::new(&second)];
+///
+/// let root = voxelify::create_gltf_root_multi(&entries, None, &options);
+/// let document = voxelify::glb_to_document(&voxelify::create_glb_multi(&root, &entries, &options)?)?;
+/// let bounds = |values: Vec<[f32; 3]>| {
+///     let fold = |pick: fn(f32, f32) -> f32, start| {
+///         values.iter().fold([start; 3], |bound, value| std::array::from_fn(|axis| pick(bound[axis], value[axis])))
+///     };
+///     (fold(f32::min, f32::MAX), fold(f32::max, f32::MIN))
+/// };
+/// let json_bounds = |accessor: gltf::Accessor| {
+///     let read = |value: gltf::json::Value| -> Vec<f32> {
+///         value.as_array().unwrap().iter().map(|value| value.as_f64().unwrap() as f32).collect()
+///     };
+///     (read(accessor.min().unwrap()), read(accessor.max().unwrap()))
+/// };
+///
+/// for mesh in document.meshes() {
+///     let primitive = mesh.primitives().next().unwrap();
+///     let reader = primitive.reader(|_| document.blob.as_deref());
+///     let attributes = [
+///         (gltf::Semantic::Positions, reader.read_positions().unwrap().collect::<Vec<_>>()),
+///         (gltf::Semantic::Normals, reader.read_normals().unwrap().collect()),
+///         (gltf::Semantic::Colors(0), reader.read_colors(0).unwrap().into_rgb_f32().collect()),
+///     ];
+///     for (semantic, values) in attributes {
+///         let (min, max) = bounds(values);
+///         assert_eq!(json_bounds(primitive.get(&semantic).unwrap()), (min.to_vec(), max.to_vec()));
+///     }
+/// }
+/// # Ok::<(), voxelify::VoxelifyError>(())
+/// ```
+pub fn create_gltf_root_multi(
+    entries: &[MeshEntry],
+    uri: Option<String>,
+    options: &VoxelifyOptions,
+) -> gltf::json::Root {
+    #[cfg(feature = "logging")]
+    let start = std::time::Instant::now();
+
+    let mut root = gltf::json::Root {
+        asset: json::Asset {
+            version: "2.0".to_string(),
+            min_version: options.min_version.clone(),
+            copyright: options.copyright.clone(),
+            generator: Some(concat!("voxelify ", env!("CARGO_PKG_VERSION")).to_string()),
+            ..Default::default()
+        },
+        extras: options
+            .provenance
+            .as_ref()
+            .map(|provenance| provenance_extras(provenance, options)),
+        ..Default::default()
+    };
+
+    root.push(gltf::json::Buffer {
+        byte_length: USize64(0),
+        extensions: Default::default(),
+        extras: Default::default(),
+        name: None,
+        uri,
+    });
+
+    let mut nodes = entries
+        .iter()
+        .map(|entry| {
+            push_mesh(
+                &mut root,
+                entry.vertices,
+                entry.name.clone(),
+                entry.transform,
+                embedded_texture(entry, options),
+                options,
+            )
+        })
+        .collect();
+
+    if let Some(scale) = model_scale(entries, options) {
+        nodes = vec![root.push(json::Node {
+            children: Some(nodes),
+            scale: Some([scale; 3]),
+            ..Default::default()
+        })];
+    }
+
+    root.push(json::Scene {
+        extensions: Default::default(),
+        extras: Default::default(),
+        name: None,
+        nodes,
+    });
+    declare_extensions(&mut root);
+
+    #[cfg(feature = "logging")]
+    log::debug!(
+        "Created glTF root for {} vertices in {:?}",
+        entries
+            .iter()
+            .map(|entry| entry.vertices.len())
+            .sum::<usize>(),
+        start.elapsed()
+    );
+
+    root
+}
+
+/// Factor of the scale of the model, `None` when it keeps its size
+/// The size to fit is measured on the bounding box of all the meshes at their translations and scale, their rotation
+/// is left out.
+fn model_scale(entries: &[MeshEntry], options: &VoxelifyOptions) -> Option<f32> {
+    match options.scale? {
+        ModelScale::Factor(factor) => Some(factor),
+        ModelScale::Fit(size) => {
+            let mut min = [f32::MAX; 3];
+            let mut max = [f32::MIN; 3];
+            for entry in entries.iter().filter(|entry| !entry.vertices.is_empty()) {
+                let (entry_min, entry_max) = bounding_coords(entry.vertices);
+                for axis in 0..3 {
+                    let offset = entry.transform.translation[axis];
+                    let scale = entry.transform.scale[axis];
+                    // A negative scale mirrors the mesh, which swaps its sides.
+                    let (low, high) = (entry_min[axis] * scale, entry_max[axis] * scale);
+                    min[axis] = min[axis].min(low.min(high) + offset);
+                    max[axis] = max[axis].max(low.max(high) + offset);
+                }
+            }
+            let largest = (0..3).map(|axis| max[axis] - min[axis]).fold(0.0, f32::max);
+
+            (largest > 0.0).then(|| size / largest)
+        }
+    }
+}
+
+/// Adds the views, accessors, mesh and node of the vertices to an existing root and returns the node
+/// The node isn't added to a scene. The data goes at the end of the first buffer, which is created if needed,
+/// so `create_glb_multi` writes the BIN when given the meshes in the order they were added.
+pub fn add_mesh_to_root(
+    root: &mut gltf::json::Root,
+    vertices: &[Vertex],
+    transform: NodeTransform,
+    options: &VoxelifyOptions,
+) -> Index<json::Node> {
+    let node = push_mesh(root, vertices, None, transform, None, options);
+    declare_extensions(root);
+    node
+}
+
+fn push_mesh(
+    root: &mut gltf::json::Root,
+    vertices: &[Vertex],
+    name: Option<String>,
+    transform: NodeTransform,
+    texture: Option<&[u8]>,
+    options: &VoxelifyOptions,
+) -> Index<json::Node> {
+    if root.buffers.is_empty() {
+        root.push(gltf::json::Buffer {
+            byte_length: USize64(0),
+            extensions: Default::default(),
+            extras: Default::default(),
+            name: None,
+            uri: None,
+        });
+    }
+    let buffer = Index::new(0);
+
+    let encoding = VertexEncoding::new(vertices, options);
+    let byte_offset = root.buffers[0].byte_length.0 as usize;
+    let mesh_length = calculate_buffer_length(vertices.len(), &encoding, options);
+    root.buffers[0].byte_length = USize64::from(
+        byte_offset
+            .saturating_add(mesh_length)
+            .saturating_add(texture.map_or(0, |png| align4(png.len()))),
+    );
+
+    let vertex_buffer_length =
+        calculate_vertex_buffer_length(vertices.len(), &encoding, options.layout);
+    let index_buffer_length = calculate_index_buffer_length(vertices.len(), options);
+
+    let views = create_buffer_views(
+        root,
+        buffer,
+        byte_offset,
+        vertices,
+        &encoding,
+        options.layout,
+    );
+
+    let index_view = has_line_indices(options).then(|| {
+        debug_assert_eq!(vertex_buffer_length % PADDING, 0, "unaligned buffer view");
+        root.push(buffer::View {
+            buffer,
+            byte_length: USize64::from(index_buffer_length),
+            byte_offset: Some(USize64::from(byte_offset + vertex_buffer_length)),
+            byte_stride: None,
+            extensions: Default::default(),
+            extras: Default::default(),
+            name: None,
+            target: Some(Valid(buffer::Target::ElementArrayBuffer)),
+        })
+    });
+    // The texture goes right after the data of the mesh, like `create_parts_multi` writes it.
+    let texture = texture.map(|png| {
+        let byte_offset = byte_offset.saturating_add(mesh_length);
+        push_texture(root, buffer, byte_offset, png, options.nearest_sampling)
+    });
+    let mode = if options.points {
+        json::mesh::Mode::Points
+    } else if options.wireframe {
+        json::mesh::Mode::Lines
+    } else {
+        json::mesh::Mode::Triangles
+    };
+
+    // Every run of faces shares the views, its accessors only cover its own part of them.
+    let primitives = primitive_ranges(vertices, options)
+        .into_iter()
+        .map(|(range, emissive)| {
+            let accessors = create_accessors(
+                root,
+                &views,
+                range.start,
+                &vertices[range.clone()],
+                &encoding,
+            );
+
+            let indices = index_view.map(|view| {
+                let first = range.start / VERTICES_PER_FACE * LINE_INDICES_PER_FACE;
+                root.push(json::Accessor {
+                    buffer_view: Some(view),
+                    byte_offset: Some(USize64::from(first * std::mem::size_of::<u32>())),
+                    count: USize64::from(range.len() / VERTICES_PER_FACE * LINE_INDICES_PER_FACE),
+                    component_type: Valid(json::accessor::GenericComponentType(
+                        json::accessor::ComponentType::U32,
+                    )),
+                    extensions: Default::default(),
+                    extras: Default::default(),
+                    type_: Valid(json::accessor::Type::Scalar),
+                    min: None,
+                    max: None,
+                    name: None,
+                    normalized: false,
+                    sparse: None,
+                })
+            });
+
+            json::mesh::Primitive {
+                attributes: accessors
+                    .into_iter()
+                    .map(|(attribute, accessor)| (Valid(attribute.semantic()), accessor))
+                    .collect(),
+                extensions: Default::default(),
+                extras: Default::default(),
+                indices,
+                material: find_or_create_material(
+                    root,
+                    options,
+                    &vertices[range.clone()],
+                    emissive,
+                    texture,
+                ),
+                mode: Valid(mode),
+                targets: None,
+            }
+        })
+        .collect();
+
+    let mesh = root.push(json::Mesh {
+        extensions: Default::default(),
+        extras: Default::default(),
+        name: name.clone(),
+        primitives,
+        weights: None,
+    });
+
+    let Some(quantization) = encoding.quantization else {
+        return root.push(transformed_node(Some(mesh), name, transform));
+    };
+
+    // Quantized positions are mapped back to their original values through the node transform.
+    let offset = quantization.offset;
+    if transform.rotation == NodeTransform::default().rotation && transform.scale == [1.0; 3] {
+        // A plain translation can be folded into the dequantization.
+        return root.push(json::Node {
+            mesh: Some(mesh),
+            name,
+            translation: Some(std::array::from_fn(|axis| {
+                transform.translation[axis] + offset[axis]
+            })),
+            scale: Some(quantization.step),
+            ..Default::default()
+        });
+    }
+
+    let dequantized = root.push(json::Node {
+        mesh: Some(mesh),
+        translation: Some(offset),
+        scale: Some(quantization.step),
+        ..Default::default()
+    });
+    let mut node = transformed_node(None, name, transform);
+    node.children = Some(vec![dequantized]);
+    root.push(node)
+}
+
+/// Node with the transform, the identity parts of the transform are left out of the JSON
+fn transformed_node(
+    mesh: Option<Index<json::Mesh>>,
+    name: Option<String>,
+    transform: NodeTransform,
+) -> json::Node {
+    let identity = NodeTransform::default();
+
+    json::Node {
+        mesh,
+        name,
+        translation: (transform.translation != identity.translation)
+            .then_some(transform.translation),
+        rotation: (transform.rotation != identity.rotation)
+            .then_some(json::scene::UnitQuaternion(transform.rotation)),
+        scale: (transform.scale != identity.scale).then_some(transform.scale),
+        ..Default::default()
+    }
+}
+
+/// Converts an image to list of vertices that can be used to create a mesh
+/// Each pixel will be converted to a voxel with height determined by the height argument
+/// The faces of the voxel will be culled if they are not visible
+/// Grayscale images are masks, their black pixels are empty like the transparent pixels of the other images.
+///
+/// ```
+/// // The same shape as a grayscale mask and as an image with transparent pixels
+/// let filled = |x: u32, y: u32| (x + y) % 3 != 0;
+/// let mask = image::GrayImage::from_fn(4, 3, |x, y| image::Luma([if filled(x, y) { 200 } else { 0 }]));
+/// let rgba = image::RgbaImage::from_fn(4, 3, |x, y| image::Rgba([200, 200, 200, if filled(x, y) { 255 } else { 0 }]));
+///
+/// let positions = |image: image::DynamicImage| -> Vec<_> {
+///     voxelify::image_to_vertices(&image, 1.0).iter().map(|vertex| vertex.position()).collect()
+/// };
+/// let from_mask = positions(image::DynamicImage::ImageLuma8(mask));
+/// assert!(!from_mask.is_empty());
+/// assert_eq!(from_mask, positions(image::DynamicImage::ImageRgba8(rgba)));
+/// ```
+pub fn image_to_vertices(image: &image::DynamicImage, height: f32) -> Vec<Vertex> {
+    image_to_vertices_with_options(
+        image,
+        &VoxelifyOptions {
+            z_ceiling: height,
+            ..Default::default()
+        },
+    )
+}
+
+/// Same as `image_to_vertices`, with the height and the shape of the voxels taken from the options
+///
+/// ```
+/// let image = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+///     1,
+///     1,
+///     image::Rgba([255, 255, 255, 255]),
+/// ));
+/// let options = voxelify::VoxelifyOptions {
+///     z_floor: 0.25,
+///     z_ceiling: 1.75,
+///     ..Default::default()
+/// };
+/// let vertices = voxelify::image_to_vertices_with_options(&image, &options);
+///
+/// // The faces of a lone voxel in the order they're meshed, the heights are 0 for the floor and 1 for the ceiling.
+/// let faces: [([f32; 3], [[u8; 3]; 6]); 6] = [
+///     ([0.0, 0.0, 1.0], [[0, 0, 1], [1, 0, 1], [0, 1, 1], [0, 1, 1], [1, 0, 1], [1, 1, 1]]),
+///     ([0.0, 0.0, -1.0], [[0, 0, 0], [0, 1, 0], [1, 0, 0], [1, 0, 0], [0, 1, 0], [1, 1, 0]]),
+///     ([1.0, 0.0, 0.0], [[1, 0, 0], [1, 1, 0], [1, 0, 1], [1, 0, 1], [1, 1, 0], [1, 1, 1]]),
+///     ([-1.0, 0.0, 0.0], [[0, 0, 0], [0, 0, 1], [0, 1, 0], [0, 1, 0], [0, 0, 1], [0, 1, 1]]),
+///     ([0.0, 1.0, 0.0], [[0, 1, 0], [0, 1, 1], [1, 1, 0], [1, 1, 0], [0, 1, 1], [1, 1, 1]]),
+///     ([0.0, -1.0, 0.0], [[0, 0, 0], [1, 0, 0], [0, 0, 1], [0, 0, 1], [1, 0, 0], [1, 0, 1]]),
+/// ];
+/// let bits = |components: [f32; 3]| components.map(f32::to_bits);
+/// let expected: Vec<_> = faces
+///     .iter()
+///     .flat_map(|(normal, corners)| {
+///         corners.iter().map(|&[x, y, z]| {
+///             let height = if z == 1 { 1.75 } else { 0.25 };
+///             (bits([x as f32, y as f32, height]), bits(*normal))
+///         })
+///     })
+///     .collect();
+///
+/// let meshed: Vec<_> = vertices
+///     .iter()
+///     .map(|vertex| (bits(vertex.position()), bits(vertex.normal())))
+///     .collect();
+/// assert_eq!(meshed, expected);
+/// ```
+pub fn image_to_vertices_with_options(
+    image: &image::DynamicImage,
+    options: &VoxelifyOptions,
+) -> Vec<Vertex> {
+    mesh_image(image, None, options)
+}
+
+/// Meshes the image like `image_to_vertices_with_options` and only counts the vertices, to benchmark the meshing
+/// Nothing of the glTF is built. The vertices go into a buffer kept by the thread, which is reused by the next call,
+/// so only the meshing is measured once it has grown.
+///
+/// ```
+/// let image = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(3, 2, image::Rgba([255, 0, 0, 255])));
+/// let options = voxelify::VoxelifyOptions::default();
+///
+/// let count = voxelify::voxelize_geometry_only(&image, &options);
+/// assert_eq!(voxelify::voxelize_geometry_only(&image, &options), count);
+/// assert_eq!(count, voxelify::image_to_vertices_with_options(&image, &options).len());
+/// assert_eq!(count, voxelify::estimate(&image, &options).triangles * 3);
+/// ```
+pub fn voxelize_geometry_only(image: &image::DynamicImage, options: &VoxelifyOptions) -> usize {
+    thread_local! {
+        static BUFFER: RefCell<Vec<Vertex>> = const { RefCell::new(Vec::new()) };
+    }
+
+    BUFFER.with_borrow_mut(|buffer| {
+        mesh_image_into(image, None, options, None, buffer);
+        buffer.len()
+    })
+}
+
+/// Same as `image_to_vertices_with_options`, the transform gets the finished mesh and can change it in place
+/// The root and the GLB have to be created from the vertices it leaves, so the accessors match the buffer.
+///
+/// ```
+/// let image = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+///     4,
+///     1,
+///     image::Rgba([255, 255, 255, 255]),
+/// ));
+/// let options = voxelify::VoxelifyOptions {
+///     z_ceiling: 1.0,
+///     ..Default::default()
+/// };
+///
+/// // Leans the voxels over, the further along X the higher they are.
+/// let vertices = voxelify::image_to_vertices_with_transform(&image, &options, |vertices| {
+///     for vertex in vertices.iter_mut() {
+///         let [x, y, z] = vertex.position();
+///         vertex.set_position([x, y, z + x * 0.5]);
+///     }
+/// });
+///
+/// let top = vertices.iter().map(|vertex| vertex.position()[2]).fold(f32::MIN, f32::max);
+/// assert_eq!(top, 3.0);
+/// ```
+pub fn image_to_vertices_with_transform<F: FnMut(&mut Vec<Vertex>)>(
+    image: &image::DynamicImage,
+    options: &VoxelifyOptions,
+    mut transform: F,
+) -> Vec<Vertex> {
+    let mut vertices = mesh_image(image, None, options);
+    transform(&mut vertices);
+    vertices
+}
+
+/// Same as `image_to_vertices`, with the height of every voxel taken from a grayscale depth map of the same size
+/// Black pixels of the depth map are flat and aren't extruded, white ones are `max_height` tall
+pub fn image_to_vertices_with_depth(
+    image: &image::DynamicImage,
+    depth: &image::DynamicImage,
+    max_height: f32,
+) -> Result<Vec<Vertex>, VoxelifyError> {
+    image_to_vertices_with_depth_and_options(
+        image,
+        depth,
+        &VoxelifyOptions {
+            z_ceiling: max_height,
+            ..Default::default()
+        },
+    )
+}
+
+/// Same as `image_to_vertices_with_depth`, the depth map spans from the floor to the ceiling of the options
+/// The voxels aren't beveled, as the bevels can't follow the steps between neighbors of different heights
+pub fn image_to_vertices_with_depth_and_options(
+    image: &image::DynamicImage,
+    depth: &image::DynamicImage,
+    options: &VoxelifyOptions,
+) -> Result<Vec<Vertex>, VoxelifyError> {
+    let depth = depth_map_from_image(image, depth, options)?;
+    Ok(mesh_image(image, Some(&depth), options))
+}
+
+/// Same as `image_to_vertices_with_options`, with the grid position, the color and the height of every voxel
+/// The voxels are listed in the order of the pixels, the mirrored copies and the base aren't. The heights come from
+/// the depth map when there's one, like with `image_to_vertices_with_depth_and_options`.
+///
+/// ```
+/// let mut image = image::RgbaImage::from_pixel(2, 1, image::Rgba([255, 0, 0, 255]));
+/// image.put_pixel(0, 0, image::Rgba([0, 0, 0, 0]));
+/// let image = image::DynamicImage::ImageRgba8(image);
+/// let options = voxelify::VoxelifyOptions::default();
+///
+/// let (vertices, metadata) = voxelify::image_to_vertices_with_metadata(&image, None, &options)?;
+/// assert_eq!(vertices.len(), voxelify::image_to_vertices_with_options(&image, &options).len());
+/// assert_eq!(
+///     metadata,
+///     [voxelify::VoxelMetadata { x: 1, y: 0, color: [255, 0, 0], height: options.z_ceiling }]
+/// );
+/// # Ok::<(), voxelify::VoxelifyError>(())
+/// ```
+pub fn image_to_vertices_with_metadata(
+    image: &image::DynamicImage,
+    depth: Option<&image::DynamicImage>,
+    options: &VoxelifyOptions,
+) -> Result<(Vec<Vertex>, Vec<VoxelMetadata>), VoxelifyError> {
+    let depth = depth
+        .map(|depth| depth_map_from_image(image, depth, options))
+        .transpose()?;
+    let mut metadata = Vec::new();
+    let vertices = mesh_image_with_metadata(image, depth.as_ref(), options, Some(&mut metadata));
+
+    Ok((vertices, metadata))
+}
+
+/// Heights of a grayscale depth map of the same size as the image, spanning from the floor to the ceiling
+fn depth_map_from_image(
+    image: &image::DynamicImage,
+    depth: &image::DynamicImage,
+    options: &VoxelifyOptions,
+) -> Result<DepthMap, VoxelifyError> {
+    if image.dimensions() != depth.dimensions() {
+        return Err(VoxelifyError::DimensionMismatch {
+            image: image.dimensions(),
+            depth: depth.dimensions(),
+        });
+    }
+
+    // 16 bits keep the precision of depth maps that have it, 8 bit ones are scaled up.
+    let luma = depth.to_luma16();
+    let range = options.z_ceiling - options.z_floor;
+    let (width, height) = image.dimensions();
+
+    Ok(DepthMap::from_fn(width, height, options.z_floor, |x, y| {
+        luma.get_pixel(x, y).0[0] as f32 / u16::MAX as f32 * range
+    }))
+}
+
+/// Same as `image_to_vertices`, with the height of every voxel given by a function of its position and color
+/// The pixels it gives a height of zero or less are flat and aren't extruded
+pub fn image_to_vertices_with<F: Fn(u32, u32, image::Rgb<u8>) -> f32>(
+    image: &image::DynamicImage,
+    height_fn: F,
+) -> Vec<Vertex> {
+    image_to_vertices_with_heights(image, height_fn, &VoxelifyOptions::default())
+}
+
+/// Same as `image_to_vertices_with`, the heights are counted from the floor of the options
+/// The voxels aren't beveled, as the bevels can't follow the steps between neighbors of different heights
+pub fn image_to_vertices_with_heights<F: Fn(u32, u32, image::Rgb<u8>) -> f32>(
+    image: &image::DynamicImage,
+    height_fn: F,
+    options: &VoxelifyOptions,
+) -> Vec<Vertex> {
+    let (width, height) = image.dimensions();
+    let depth = DepthMap::from_fn(width, height, options.z_floor, |x, y| {
+        height_fn(x, y, image.get_pixel(x, y).to_rgb())
+    });
+
+    mesh_image(image, Some(&depth), options)
+}
+
+/// Height of the voxel of every pixel, for voxels that aren't all as tall
+#[derive(Clone)]
+struct DepthMap {
+    ceilings: Vec<f32>,
+    width: u32,
+    floor: f32,
+}
+
+impl DepthMap {
+    /// Takes the height of every voxel above the floor from the function
+    fn from_fn(width: u32, height: u32, floor: f32, height_fn: impl Fn(u32, u32) -> f32) -> Self {
+        Self {
+            ceilings: (0..height)
+                .flat_map(|y| (0..width).map(move |x| (x, y)))
+                .map(|(x, y)| floor + height_fn(x, y))
+                .collect(),
+            width,
+            floor,
+        }
+    }
+
+    /// Every voxel is as tall as the ceiling of the options
+    fn flat(width: u32, height: u32, options: &VoxelifyOptions) -> Self {
+        Self {
+            ceilings: vec![options.z_ceiling; (width * height) as usize],
+            width,
+            floor: options.z_floor,
+        }
+    }
+
+    /// Raises the top of the voxels that aren't flat by the noise
+    fn add_noise(&mut self, noise: HeightNoise) {
+        for (index, ceiling) in self.ceilings.iter_mut().enumerate() {
+            if *ceiling > self.floor {
+                let (x, y) = (index as u32 % self.width, index as u32 / self.width);
+                *ceiling += noise.sample(x, y);
+            }
+        }
+    }
+
+    /// Rounds the top of every voxel to the closest of `layers` even steps from the floor to the ceiling
+    /// Voxels above the ceiling are brought down to it.
+    fn snap_to_layers(&mut self, layers: u32, ceiling: f32) {
+        let step = (ceiling - self.floor) / layers as f32;
+        if step <= 0.0 {
+            return;
+        }
+
+        for top in &mut self.ceilings {
+            let layer = ((*top - self.floor) / step)
+                .round()
+                .clamp(0.0, layers as f32);
+            *top = self.floor + layer * step;
+        }
+    }
+
+    /// Top of the voxel of the pixel, `None` when it's flat
+    fn ceiling(&self, x: u32, y: u32) -> Option<f32> {
+        let ceiling = self.ceilings[(y * self.width + x) as usize];
+        (ceiling > self.floor).then_some(ceiling)
+    }
+}
+
+fn mesh_image(
+    image: &image::DynamicImage,
+    depth: Option<&DepthMap>,
+    options: &VoxelifyOptions,
+) -> Vec<Vertex> {
+    mesh_image_with_metadata(image, depth, options, None)
+}
+
+/// Meshes the image, and lists its voxels in the metadata when it's given
+fn mesh_image_with_metadata(
+    image: &image::DynamicImage,
+    depth: Option<&DepthMap>,
+    options: &VoxelifyOptions,
+    metadata: Option<&mut Vec<VoxelMetadata>>,
+) -> Vec<Vertex> {
+    let mut vertices = Vec::new();
+    mesh_image_into(image, depth, options, metadata, &mut vertices);
+    vertices
+}
+
+/// Meshes the image into the vertices, which are cleared first and keep their capacity
+fn mesh_image_into(
+    image: &image::DynamicImage,
+    depth: Option<&DepthMap>,
+    options: &VoxelifyOptions,
+    metadata: Option<&mut Vec<VoxelMetadata>>,
+    vertices: &mut Vec<Vertex>,
+) {
+    #[cfg(feature = "logging")]
+    let start = std::time::Instant::now();
+
+    let image = preprocess_image(image, options);
+    let image = image.as_ref();
+
+    let (image_width, image_height) = image.dimensions();
+
+    // Voxels of different heights are meshed like the ones of a depth map.
+    let color_depth = options
+        .color_heights
+        .as_ref()
+        .filter(|_| depth.is_none())
+        .map(|heights| {
+            let range = options.z_ceiling - options.z_floor;
+            DepthMap::from_fn(image_width, image_height, options.z_floor, |x, y| {
+                heights
+                    .height(image.get_pixel(x, y).to_rgb())
+                    .unwrap_or(range)
+            })
+        });
+    let depth = depth.or(color_depth.as_ref());
+    let alpha_depth = (options.alpha_height && depth.is_none()).then(|| {
+        let range = options.z_ceiling - options.z_floor;
+        DepthMap::from_fn(image_width, image_height, options.z_floor, |x, y| {
+            image.get_pixel(x, y).0[ALPHA_CHANNEL_INDEX] as f32 / RGB_MAX_VALUE * range
+        })
+    });
+    let depth = depth.or(alpha_depth.as_ref());
+    let noisy = options.height_noise.map(|noise| {
+        let mut depth = depth
+            .cloned()
+            .unwrap_or_else(|| DepthMap::flat(image_width, image_height, options));
+        depth.add_noise(noise);
+        depth
+    });
+    let depth = noisy.as_ref().or(depth);
+    let layered = options
+        .z_layers
+        .filter(|&layers| layers > 0)
+        .zip(depth)
+        .map(|(layers, depth)| {
+            let mut depth = depth.clone();
+            depth.snap_to_layers(layers, options.z_ceiling);
+            depth
+        });
+    let depth = layered.as_ref().or(depth);
+
+    if let Some(metadata) = metadata {
+        metadata.extend(voxel_metadata(image, depth, options));
+    }
+
+    vertices.clear();
+    if options.points {
+        vertices.extend(generate_points(image, depth, options));
+        return;
+    }
+
+    // Counting the faces up front is much cheaper than growing the vector while meshing. The count only holds for
+    // the plain boxes, the other shapes and the steps of a depth map have faces of their own.
+    let plain_boxes = depth.is_none()
+        && !options.terrain
+        && !options.full_cubes
+        && !options.smooth_edges
+        && !rounds_voxels(depth, options)
+        && effective_bevel(depth, options) <= 0.0;
+    if plain_boxes {
+        vertices.reserve(Mask::from_image(image).visible_face_count() * VERTICES_PER_FACE);
+    }
+
+    #[cfg(feature = "parallel")]
+    let tiled = image_width > TILE_SIZE || image_height > TILE_SIZE;
+    #[cfg(not(feature = "parallel"))]
+    let tiled = false;
+
+    if options.terrain {
+        vertices.extend(generate_terrain(image, depth, options));
+    } else if tiled {
+        #[cfg(feature = "parallel")]
+        mesh_tiles(image, depth, options, vertices);
+    } else {
+        for y in 0..image_height {
+            for x in 0..image_width {
+                mesh_pixel(image, depth, options, x, y, vertices);
+            }
+        }
+    }
+
+    if options.seal_bottom
+        && !options.full_cubes
+        && !options.terrain
+        && !options.embed_texture
+        && !rounds_voxels(depth, options)
+    {
+        vertices.extend(sealed_bottom(image, depth, options));
+    }
+
+    if let Some(axis) = options.mirror {
+        *vertices = mirror_vertices(vertices, axis);
+    }
+
+    if let Some(base) = options.base.filter(|_| !options.embed_texture) {
+        let base = create_base(vertices, base, options.z_floor);
+        vertices.extend(base);
+    }
+
+    if options.flip_winding {
+        reverse_winding(vertices);
+    }
+    if options.flip_normals {
+        for vertex in vertices.iter_mut() {
+            vertex.normal = vertex.normal.map(|component| -component);
+        }
+    }
+
+    if (options.texture_coordinates || options.tangents) && !options.embed_texture {
+        uv::generate_texture_coordinates(vertices, image_width, image_height);
+    }
+
+    if let Some(threshold) = options.emissive_threshold {
+        *vertices = group_glowing_faces(vertices, threshold);
+    }
+
+    place_vertices(vertices, options);
+
+    #[cfg(feature = "logging")]
+    if options.rounding > 0.0 && vertices.len() / 3 > ROUNDED_TRIANGLES_WARNING {
+        log::warn!(
+            "Rounding the voxels made {} triangles, the model may be slow to load",
+            vertices.len() / 3
+        );
+    }
+
+    #[cfg(feature = "logging")]
+    log::debug!(
+        "Converted {}x{} image into {} vertices in {:?}",
+        image_width,
+        image_height,
+        vertices.len(),
+        start.elapsed()
+    );
+}
+
+/// Moves the faces from the corners of the pixels to the origin, the handedness and the extrusion axis of the options
+fn place_vertices(vertices: &mut [Vertex], options: &VoxelifyOptions) {
+    // Only the positions move, the texture coordinates are still projected from the corners of the pixels.
+    if options.pixel_origin == PixelOrigin::Center {
+        for vertex in vertices.iter_mut() {
+            vertex.position[0] -= 0.5;
+            vertex.position[1] -= 0.5;
+        }
+    }
+
+    // Reflecting the model turns its faces inside out, reversing the winding turns them back.
+    if options.handedness == Handedness::Left {
+        for vertex in vertices.iter_mut() {
+            vertex.position[0] = -vertex.position[0];
+            vertex.normal[0] = -vertex.normal[0];
+            vertex.tangent[0] = -vertex.tangent[0];
+            vertex.tangent[3] = -vertex.tangent[3];
+        }
+        reverse_winding(vertices);
+    }
+
+    // A rotation, unlike the reflection, keeps the winding.
+    let axis = options.extrude_axis;
+    if axis != Axis::Z {
+        for vertex in vertices.iter_mut() {
+            vertex.position = axis.extrude(vertex.position);
+            vertex.normal = axis.extrude(vertex.normal);
+            let [x, y, z, w] = vertex.tangent;
+            let [x, y, z] = axis.extrude([x, y, z]);
+            vertex.tangent = [x, y, z, w];
+        }
+    }
+}
+
+/// Lists the pixels that become a voxel with the top of their voxel
+fn voxel_metadata(
+    image: &image::DynamicImage,
+    depth: Option<&DepthMap>,
+    options: &VoxelifyOptions,
+) -> Vec<VoxelMetadata> {
+    let (image_width, image_height) = image.dimensions();
+    let mut metadata = Vec::new();
+
+    for y in 0..image_height {
+        for x in 0..image_width {
+            if is_empty_pixel_at(image, x, y) {
+                continue;
+            }
+            let Some(height) = depth.map_or(Some(options.z_ceiling), |depth| depth.ceiling(x, y))
+            else {
+                continue;
+            };
+
+            metadata.push(VoxelMetadata {
+                x,
+                y,
+                color: image.get_pixel(x, y).to_rgb().0,
+                height,
+            });
+        }
+    }
+
+    metadata
+}
+
+/// Creates a point on the center of the top of every voxel, in the order of the pixels
+/// The points are mirrored, centered, reflected and rotated like the faces would be.
+fn generate_points(
+    image: &image::DynamicImage,
+    depth: Option<&DepthMap>,
+    options: &VoxelifyOptions,
+) -> Vec<Vertex> {
+    let (image_width, image_height) = image.dimensions();
+    let mut points = Vec::new();
+
+    for y in 0..image_height {
+        for x in 0..image_width {
+            if is_empty_pixel_at(image, x, y) {
+                continue;
+            }
+            let Some(ceiling) = depth.map_or(Some(options.z_ceiling), |depth| depth.ceiling(x, y))
+            else {
+                continue;
+            };
+            let alpha = if options.vertex_alpha {
+                image.get_pixel(x, y).0[ALPHA_CHANNEL_INDEX] as f32 / RGB_MAX_VALUE
+            } else {
+                1.0
+            };
+
+            points.push(Vertex {
+                position: [x as f32 + 0.5, y as f32 + 0.5, ceiling],
+                normal: [0.0, 0.0, 1.0],
+                color: voxel_color(image, options, x, y),
+                alpha,
+                uv: [0.0; 2],
+                tangent: [0.0; 4],
+            });
+        }
+    }
+
+    // The mirrored half starts on the far side of the voxels, so no point lands on the seam.
+    if let Some(axis) = options.mirror {
+        let axis = axis as usize;
+        let seam = bounding_coords(&points).1[axis] + 0.5;
+        let mirrored: Vec<Vertex> = points
+            .iter()
+            .map(|point| {
+                let mut point = *point;
+                point.position[axis] = 2.0 * seam - point.position[axis];
+                point
+            })
+            .collect();
+        points.extend(mirrored);
+    }
+
+    for point in &mut points {
+        if options.pixel_origin == PixelOrigin::Center {
+            point.position[0] -= 0.5;
+            point.position[1] -= 0.5;
+        }
+        if options.handedness == Handedness::Left {
+            point.position[0] = -point.position[0];
+        }
+        point.position = options.extrude_axis.extrude(point.position);
+        point.normal = options.extrude_axis.extrude(point.normal);
+    }
+
+    points
+}
+
+/// Creates the surface of `terrain`, with the heights of the depth map or the ceiling
+/// The flat pixels of a depth map lie on the floor, only the empty pixels make holes.
+fn generate_terrain(
+    image: &image::DynamicImage,
+    depth: Option<&DepthMap>,
+    options: &VoxelifyOptions,
+) -> Vec<Vertex> {
+    let (width, height) = image.dimensions();
+    let top = |x: u32, y: u32| {
+        (!is_empty_pixel_at(image, x, y)).then(|| {
+            depth.map_or(options.z_ceiling, |depth| {
+                depth.ceiling(x, y).unwrap_or(depth.floor)
+            })
+        })
+    };
+    let mut vertices = terrain::create_terrain(width, height, top, |x, y| {
+        let alpha = if options.vertex_alpha {
+            image.get_pixel(x, y).0[ALPHA_CHANNEL_INDEX] as f32 / RGB_MAX_VALUE
+        } else {
+            1.0
+        };
+        (voxel_color(image, options, x, y), alpha)
+    });
+
+    // The vertices sit on the centers of the pixels, where the texture is sampled.
+    if options.embed_texture {
+        for vertex in &mut vertices {
+            let [x, y, _] = vertex.position;
+            vertex.uv = [x / width as f32, y / height as f32];
+        }
+    }
+
+    vertices
+}
+
+/// Moves the faces that glow after the others, grouped by their color, so each group can be drawn with its own material
+/// The sort is stable, the faces of a group keep the order they were meshed in.
+fn group_glowing_faces(vertices: &[Vertex], threshold: u8) -> Vec<Vertex> {
+    let mut faces: Vec<&[Vertex]> = vertices.chunks(VERTICES_PER_FACE).collect();
+    faces.sort_by_key(|face| {
+        glow_color(face[0].color, threshold).map(|color| color.map(f32::to_bits))
+    });
+
+    faces.concat()
+}
+
+/// Emissive factor of a face of this color, `None` when it's too dark to glow
+fn glow_color(color: [f32; 3], threshold: u8) -> Option<[f32; 3]> {
+    let [r, g, b] = color.map(|component| component * RGB_MAX_VALUE);
+    let luminance = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+
+    (luminance >= threshold as f32).then_some(color)
+}
+
+/// Splits the vertices into the runs of faces drawn by a primitive each, with the emissive factor of the run
+/// Without an emissive threshold all the vertices are a single run.
+fn primitive_ranges(
+    vertices: &[Vertex],
+    options: &VoxelifyOptions,
+) -> Vec<(Range<usize>, Option<[f32; 3]>)> {
+    // Points aren't grouped into faces, they're all drawn by one primitive.
+    let Some(threshold) = options.emissive_threshold.filter(|_| !options.points) else {
+        return vec![(0..vertices.len(), None)];
+    };
+
+    let mut ranges: Vec<(Range<usize>, Option<[f32; 3]>)> = Vec::new();
+    for (index, face) in vertices.chunks(VERTICES_PER_FACE).enumerate() {
+        let emissive = glow_color(face[0].color, threshold);
+        let end = index * VERTICES_PER_FACE + face.len();
+
+        match ranges.last_mut() {
+            Some((range, last)) if *last == emissive => range.end = end,
+            _ => ranges.push((index * VERTICES_PER_FACE..end, emissive)),
+        }
+    }
+
+    if ranges.is_empty() {
+        ranges.push((0..0, None));
+    }
+    ranges
+}
+
+/// Appends the visible faces of the voxel of a single pixel
+fn mesh_pixel(
+    image: &image::DynamicImage,
+    depth: Option<&DepthMap>,
+    options: &VoxelifyOptions,
+    x: u32,
+    y: u32,
+    vertices: &mut Vec<Vertex>,
+) {
+    if is_empty_pixel_at(image, x, y) {
+        return;
+    }
+    let Some(ceiling) = depth.map_or(Some(options.z_ceiling), |depth| depth.ceiling(x, y)) else {
+        return;
+    };
+    let pixel = image.get_pixel(x, y);
+    let color = voxel_color(image, options, x, y);
+    let start = vertices.len();
+
+    let bevel = effective_bevel(depth, options);
+    let chamfered = (options.smooth_edges
+        && bevel <= 0.0
+        && !options.full_cubes
+        && !rounds_voxels(depth, options))
+    .then(|| {
+        chamfer::create_chamfered_verticies(
+            image,
+            Vector2::new(x, y),
+            color,
+            options.z_floor,
+            ceiling,
+            options.border,
+        )
+    })
+    .flatten();
+
+    if options.full_cubes {
+        vertices.extend(create_pixel_verticies(
+            [x as f32, y as f32],
+            color,
+            options.z_floor,
+            ceiling,
+        ));
+    } else if rounds_voxels(depth, options) {
+        let voxel = RoundedVoxel::new(
+            image,
+            Vector2::new(x, y),
+            options.z_floor,
+            ceiling,
+            options.rounding,
+            options.border,
+        );
+        vertices.extend(voxel.vertices(image, Vector2::new(x, y), options.border, color));
+    } else if let Some(chamfered) = chamfered {
+        vertices.extend(chamfered);
+    } else {
+        mesh_voxel_faces(
+            image,
+            options,
+            Vector2::new(x, y),
+            color,
+            ceiling,
+            bevel,
+            vertices,
+        );
+    }
+
+    if let Some(depth) = depth.filter(|_| !options.full_cubes) {
+        for (face, floor) in step_faces(image, depth, x, y, ceiling) {
+            vertices.extend(create_pixel_verticies_face(
+                [x as f32, y as f32],
+                color,
+                floor,
+                ceiling,
+                &face,
+            ));
+        }
+    }
+
+    if options.vertex_alpha {
+        let alpha = pixel.0[ALPHA_CHANNEL_INDEX] as f32 / RGB_MAX_VALUE;
+        for vertex in &mut vertices[start..] {
+            vertex.alpha = alpha;
+        }
+    }
+
+    if options.embed_texture {
+        let (width, height) = image.dimensions();
+        let uv = [
+            (x as f32 + 0.5) / width as f32,
+            (y as f32 + 0.5) / height as f32,
+        ];
+        for vertex in &mut vertices[start..] {
+            vertex.uv = uv;
+        }
+    }
+}
+
+/// Color of the voxel of a pixel, after the adjustment and the jitter
+fn voxel_color(image: &image::DynamicImage, options: &VoxelifyOptions, x: u32, y: u32) -> [f32; 3] {
+    if let Some(color) = options.monochrome {
+        return color.0.map(|channel| channel as f32 / RGB_MAX_VALUE);
+    }
+
+    let color = options
+        .color_adjustment
+        .apply(pixel_color(image.get_pixel(x, y)));
+
+    match options.color_jitter {
+        Some(jitter) => jitter.apply(color, x, y),
+        None => color,
+    }
+}
+
+/// Bevel actually applied to the voxels
+fn effective_bevel(depth: Option<&DepthMap>, options: &VoxelifyOptions) -> f32 {
+    match depth {
+        Some(_) => 0.0,
+        None => options
+            .bevel
+            .clamp(0.0, MAX_BEVEL)
+            .min(options.z_ceiling - options.z_floor),
+    }
+}
+
+/// Whether the voxels are rounded, the steps of a depth map can't follow the rounded edges
+fn rounds_voxels(depth: Option<&DepthMap>, options: &VoxelifyOptions) -> bool {
+    options.rounding > 0.0 && depth.is_none()
+}
+
+/// Creates the bottom of the voxels for `seal_bottom`
+/// Chamfered voxels keep their own bottom and flat voxels of a depth map have none, both are left out of the regions.
+fn sealed_bottom(
+    image: &image::DynamicImage,
+    depth: Option<&DepthMap>,
+    options: &VoxelifyOptions,
+) -> Vec<Vertex> {
+    let chamfers = options.smooth_edges
+        && effective_bevel(depth, options) <= 0.0
+        && !rounds_voxels(depth, options);
+
+    let mut mask = Mask::from_image(image);
+    for y in 0..mask.height {
+        for x in 0..mask.width {
+            let index = mask.index(x, y);
+            let flat = depth.is_some_and(|depth| depth.ceiling(x, y).is_none());
+            let chamfered = chamfers
+                && mask.filled[index]
+                && chamfer::is_chamfered(image, Vector2::new(x, y), options.border);
+
+            if flat || chamfered {
+                mask.filled[index] = false;
+            }
+        }
+    }
+
+    seal::create_sealed_bottom(&mask, options.z_floor, |x, y| {
+        let alpha = if options.vertex_alpha {
+            image.get_pixel(x, y).0[ALPHA_CHANNEL_INDEX] as f32 / RGB_MAX_VALUE
+        } else {
+            1.0
+        };
+        (voxel_color(image, options, x, y), alpha)
+    })
+}
+
+/// Appends the culled faces of a box shaped voxel, with its top beveled if the bevel isn't zero
+fn mesh_voxel_faces(
+    image: &image::DynamicImage,
+    options: &VoxelifyOptions,
+    pos: Vector2<u32>,
+    color: [f32; 3],
+    ceiling: f32,
+    bevel: f32,
+    vertices: &mut Vec<Vertex>,
+) {
+    let faces = cull_faces(image, pos, options.border);
+
+    if bevel > 0.0 {
+        vertices.extend(bevel::create_bevel_verticies(
+            image,
+            pos,
+            color,
+            ceiling,
+            bevel,
+            options.border,
+        ));
+    }
+
+    for face in faces {
+        // The top of a beveled voxel is created separately, and its sides end where the bevel starts.
+        let ceiling = match face {
+            Face::Up if bevel > 0.0 => continue,
+            // The bottom is created for the whole region at once.
+            Face::Down if options.seal_bottom => continue,
+            _ => ceiling - bevel,
+        };
+
+        vertices.extend(create_pixel_verticies_face(
+            [pos.x as f32, pos.y as f32],
+            color,
+            options.z_floor,
+            ceiling,
+            &face,
+        ));
+    }
+}
+
+/// Returns the side faces of a voxel above its lower neighbors and the height they start from
+/// Only filled neighbors inside the image are considered, the exposed sides are culled like with a flat top.
+fn step_faces(
+    image: &image::DynamicImage,
+    depth: &DepthMap,
+    x: u32,
+    y: u32,
+    ceiling: f32,
+) -> Vec<(Face, f32)> {
+    let (x, y) = (x as i64, y as i64);
+
+    [
+        (Face::Right, 1, 0),
+        (Face::Left, -1, 0),
+        (Face::Back, 0, 1),
+        (Face::Forward, 0, -1),
+    ]
+    .into_iter()
+    .filter(|&(_, dx, dy)| is_filled(image, x + dx, y + dy, BorderMode::OpenFaces))
+    .filter_map(|(face, dx, dy)| {
+        let neighbor = depth
+            .ceiling((x + dx) as u32, (y + dy) as u32)
+            .unwrap_or(depth.floor);
+        (neighbor < ceiling).then_some((face, neighbor))
+    })
+    .collect()
+}
+
+/// Meshes the image in square tiles on the current rayon thread pool
+/// Culling reads the neighbors straight from the image, so the pixels on the border of a tile see the same
+/// neighbors as in the sequential path. Each tile keeps its rows apart, and the rows are stitched back in
+/// image order so the result is identical to meshing the pixels one by one.
+#[cfg(feature = "parallel")]
+fn mesh_tiles(
+    image: &image::DynamicImage,
+    depth: Option<&DepthMap>,
+    options: &VoxelifyOptions,
+    vertices: &mut Vec<Vertex>,
+) {
+    use rayon::prelude::*;
+
+    let (image_width, image_height) = image.dimensions();
+    let tiles_per_row = image_width.div_ceil(TILE_SIZE) as usize;
+
+    let tiles: Vec<(u32, u32)> = (0..image_height)
+        .step_by(TILE_SIZE as usize)
+        .flat_map(|y| {
+            (0..image_width)
+                .step_by(TILE_SIZE as usize)
+                .map(move |x| (x, y))
+        })
+        .collect();
+
+    let meshed: Vec<Vec<Vec<Vertex>>> = tiles
+        .par_iter()
+        .map(|&(tile_x, tile_y)| {
+            (tile_y..(tile_y + TILE_SIZE).min(image_height))
+                .map(|y| {
+                    let mut row = Vec::new();
+                    for x in tile_x..(tile_x + TILE_SIZE).min(image_width) {
+                        mesh_pixel(image, depth, options, x, y, &mut row);
+                    }
+                    row
+                })
+                .collect()
+        })
+        .collect();
+
+    for band in meshed.chunks(tiles_per_row) {
+        for row in 0..band[0].len() {
+            for tile in band {
+                vertices.extend_from_slice(&tile[row]);
+            }
+        }
+    }
+}
+
+/// Voxel of a pixel, see `image_to_vertices_with_metadata`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct VoxelMetadata {
+    /// Position of the pixel in the image
+    pub x: u32,
+    pub y: u32,
+    /// Color of the pixel once the options replaced the colors, before they're adjusted
+    pub color: [u8; 3],
+    /// Z coordinate of the top of the voxel, before the model is placed
+    pub height: f32,
+}
+
+impl VoxelMetadata {
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "x": self.x,
+            "y": self.y,
+            "color": self.color,
+            "height": self.height,
+        })
+    }
+}
+
+/// Projected size of the model of an image, see `estimate`
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct MeshEstimate {
+    /// Pixels that become a voxel
+    pub voxels: usize,
+    pub faces: usize,
+    pub triangles: usize,
+    pub vertices: usize,
+    /// Size of the GLB file
+    pub bytes: usize,
+}
+
+/// Counts the voxels and the visible faces of an image without creating any vertices, which is much cheaper than meshing it
+/// The slanted faces of bevels and smooth edges, the merged bottom of `seal_bottom` and the seam removed by mirroring
+/// aren't accounted for
+pub fn estimate(image: &image::DynamicImage, options: &VoxelifyOptions) -> MeshEstimate {
+    let image = preprocess_image(image, options);
+    let image = image.as_ref();
+    let (image_width, image_height) = image.dimensions();
+
+    let mut estimate = MeshEstimate::default();
+    for y in 0..image_height {
+        for x in 0..image_width {
+            if is_empty_pixel_at(image, x, y) {
+                continue;
+            }
+
+            estimate.voxels += 1;
+            estimate.faces += if options.points {
+                0
+            } else if options.terrain {
+                // Every pixel starts the cell that spans it and its neighbors to the right and below.
+                let filled = |x: u32, y: u32| {
+                    x < image_width && y < image_height && !is_empty_pixel_at(image, x, y)
+                };
+                (filled(x + 1, y) && filled(x, y + 1) && filled(x + 1, y + 1)) as usize
+            } else if options.full_cubes {
+                FACES.len()
+            } else if options.rounding > 0.0 {
+                RoundedVoxel::new(
+                    image,
+                    Vector2::new(x, y),
+                    options.z_floor,
+                    options.z_ceiling,
+                    options.rounding,
+                    options.border,
+                )
+                .quads()
+            } else {
+                cull_faces(image, Vector2::new(x, y), options.border).len()
+            };
+        }
+    }
+
+    if options.mirror.is_some() {
+        estimate.voxels *= 2;
+        estimate.faces *= 2;
+    }
+
+    estimate.triangles = estimate.faces * 2;
+    estimate.vertices = if options.points {
+        estimate.voxels
+    } else {
+        estimate.faces * VERTICES_PER_FACE
+    };
+
+    // The JSON barely depends on the vertices, so the one of an empty model is close enough.
+    let json_length = align4(
+        json::serialize::to_string(&create_gltf_root(&[], None, options))
+            .map_or(0, |json| json.len()),
+    );
+
+    let encoding = VertexEncoding::new(&[], options);
+    estimate.bytes = GLB_HEADERS_LENGTH
+        .saturating_add(json_length)
+        .saturating_add(calculate_buffer_length(
+            estimate.vertices,
+            &encoding,
+            options,
+        ));
+
+    estimate
+}
+
+/// Creates the box bounding the vertices, the cheapest collision shape for a model
+pub fn bounding_box_mesh(vertices: &[Vertex]) -> Vec<Vertex> {
+    if vertices.is_empty() {
+        return Vec::new();
+    }
+
+    let (min, max) = bounding_coords(vertices);
+    create_box(min, max, [1.0; 3])
+}
+
+/// Stretches every color channel so its darkest and brightest values across the filled pixels become 0 and 255
+/// Empty pixels aren't counted, a channel with the same value everywhere is kept as it is.
+///
+/// ```
+/// use image::GenericImageView;
+///
+/// let faded = image::RgbaImage::from_fn(3, 1, |x, _| image::Rgba([100 + x as u8 * 20, 80, 0, 255]));
+/// let normalized = voxelify::normalize_colors(&image::DynamicImage::ImageRgba8(faded));
+/// let red: Vec<_> = normalized.pixels().map(|(_, _, pixel)| pixel[0]).collect();
+/// assert_eq!(red, [0, 128, 255]);
+/// assert_eq!(normalized.get_pixel(0, 0)[1], 80);
+/// ```
+pub fn normalize_colors(image: &image::DynamicImage) -> image::DynamicImage {
+    let mask = Mask::from_image(image);
+    let mut output = image.to_rgba8();
+
+    let mut min = [u8::MAX; 3];
+    let mut max = [u8::MIN; 3];
+    for (x, y, pixel) in output.enumerate_pixels() {
+        if mask.filled[mask.index(x, y)] {
+            for channel in 0..3 {
+                min[channel] = min[channel].min(pixel.0[channel]);
+                max[channel] = max[channel].max(pixel.0[channel]);
+            }
+        }
+    }
+
+    for pixel in output.pixels_mut() {
+        for channel in 0..3 {
+            if min[channel] < max[channel] {
+                let range = (max[channel] - min[channel]) as f32;
+                let value = pixel.0[channel].clamp(min[channel], max[channel]) - min[channel];
+                pixel.0[channel] = (value as f32 * 255.0 / range).round() as u8;
+            }
+        }
+    }
+
+    mask.apply(&image::DynamicImage::ImageRgba8(output))
+}
+
+/// Meshes the image with voxels of `factor` by `factor` pixels, a collision shape that follows the silhouette
+/// The voxels are plain culled cubes as tall as the ceiling, the options that only change their look or their
+/// heights are ignored. The vertices line up with the mesh of the full image.
+/// The holes are filled once on the full image like for the model; the coarse image isn't filled again, since the
+/// radius counts pixels of the full image.
+///
+/// ```
+/// use voxelify::{Axis, VoxelifyOptions};
+///
+/// let image = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+///     4,
+///     4,
+///     image::Rgba([255, 255, 255, 255]),
+/// ));
+/// let options = VoxelifyOptions {
+///     extrude_axis: Axis::X,
+///     ..Default::default()
+/// };
+/// let bounds = |vertices: &[voxelify::Vertex]| {
+///     let mut min = [f32::MAX; 3];
+///     let mut max = [f32::MIN; 3];
+///     for position in vertices.iter().map(|vertex| vertex.position()) {
+///         for axis in 0..3 {
+///             min[axis] = min[axis].min(position[axis]);
+///             max[axis] = max[axis].max(position[axis]);
+///         }
+///     }
+///     (min, max)
+/// };
+///
+/// let model = voxelify::image_to_vertices_with_options(&image, &options);
+/// let collision = voxelify::collision_voxels(&image, 2, &options);
+/// assert_eq!(bounds(&collision), bounds(&model));
+/// ```
+pub fn collision_voxels(
+    image: &image::DynamicImage,
+    factor: u32,
+    options: &VoxelifyOptions,
+) -> Vec<Vertex> {
+    let factor = factor.max(1);
+    // The pixels are culled at full resolution, the coarse image is only meshed.
+    let coarse = downscale_image(&preprocess_image(image, options), factor);
+    // Only the options that decide where the collider is are inherited, the new ones are left out by default. The
+    // emptiness was decided on the full image already, the placement and the axis are applied below.
+    let coarse_options = VoxelifyOptions {
+        z_floor: options.z_floor,
+        z_ceiling: options.z_ceiling,
+        border: options.border,
+        mirror: options.mirror,
+        flip_winding: options.flip_winding,
+        flip_normals: options.flip_normals,
+        threads: options.threads,
+        ..Default::default()
+    };
+
+    let mut vertices = mesh_image(&coarse, None, &coarse_options);
+    for vertex in &mut vertices {
+        vertex.position[0] *= factor as f32;
+        vertex.position[1] *= factor as f32;
+    }
+    place_vertices(&mut vertices, options);
+
+    vertices
+}
+
+/// Repeats the image on a grid of `columns` by `rows` copies
+/// Meshing the result culls the faces between the copies, only the outside of the whole grid gets side faces.
+pub fn tile_image(image: &image::DynamicImage, columns: u32, rows: u32) -> image::DynamicImage {
+    let (width, height) = image.dimensions();
+    let mut tiled = image::DynamicImage::new(
+        width.saturating_mul(columns),
+        height.saturating_mul(rows),
+        image.color(),
+    );
+    image::imageops::tile(&mut tiled, image);
+
+    tiled
+}
+
+/// Enlarges the image `factor` times, `Nearest` repeats every pixel on a block of `factor` by `factor` pixels
+/// Every feature becomes `factor` voxels thick, which keeps thin lines sturdy when printing the model.
+/// The other filters blend the pixels, which suits photos more than pixel art.
+pub fn upscale_image(
+    image: &image::DynamicImage,
+    factor: u32,
+    filter: image::imageops::FilterType,
+) -> image::DynamicImage {
+    let factor = factor.max(1);
+    let (width, height) = image.dimensions();
+
+    image.resize_exact(
+        width.saturating_mul(factor),
+        height.saturating_mul(factor),
+        filter,
+    )
+}
+
+/// Smallest factor of `downscale_image` that keeps the estimated triangles of the image within the budget
+/// The factors are binary searched, assuming that coarser images never have more triangles. When even a single
+/// voxel is over the budget, the factor shrinking the image to one pixel is returned.
+pub fn fit_triangle_budget(
+    image: &image::DynamicImage,
+    options: &VoxelifyOptions,
+    max_triangles: usize,
+) -> u32 {
+    let fits = |factor: u32| {
+        let image = if factor == 1 {
+            Cow::Borrowed(image)
+        } else {
+            Cow::Owned(downscale_image(image, factor))
+        };
+        estimate(&image, options).triangles <= max_triangles
+    };
+
+    let (mut fitting, mut over) = (image.width().max(image.height()).max(1), 0);
+    if !fits(fitting) {
+        return fitting;
+    }
+    while fitting - over > 1 {
+        let factor = over + (fitting - over) / 2;
+        if fits(factor) {
+            fitting = factor;
+        } else {
+            over = factor;
+        }
+    }
+
+    fitting
+}
+
+/// Shrinks the image by merging every block of `factor` by `factor` pixels into one, for coarser voxels
+/// A block is filled when at least half of its pixels are, with the average color of its filled pixels. Blocks on
+/// the right and bottom edges can be smaller when the size of the image isn't a multiple of the factor.
+pub fn downscale_image(image: &image::DynamicImage, factor: u32) -> image::DynamicImage {
+    let factor = factor.max(1);
+    let (width, height) = image.dimensions();
+
+    let downscaled = image::RgbaImage::from_fn(
+        width.div_ceil(factor),
+        height.div_ceil(factor),
+        |column, row| {
+            let (mut filled, mut total) = (0u32, 0u32);
+            let mut sum = [0u32; 4];
+
+            for y in row * factor..((row + 1) * factor).min(height) {
+                for x in column * factor..((column + 1) * factor).min(width) {
+                    total += 1;
+                    if is_empty_pixel_at(image, x, y) {
+                        continue;
+                    }
+                    filled += 1;
+                    for (channel, value) in image.get_pixel(x, y).0.into_iter().enumerate() {
+                        sum[channel] += value as u32;
+                    }
+                }
+            }
+
+            if filled * 2 < total || filled == 0 {
+                return image::Rgba([0, 0, 0, 0]);
+            }
+            image::Rgba(sum.map(|channel| (channel / filled) as u8))
+        },
+    );
+
+    image::DynamicImage::ImageRgba8(downscaled)
+}
+
+/// Keeps only the pixels within `thickness` pixels from the edge of the shape, hollowing out its interior
+/// A thickness of one keeps the pixels that touch an empty pixel or the border of the image
+pub fn outline_image(image: &image::DynamicImage, thickness: u32) -> image::DynamicImage {
+    let mut mask = Mask::from_image(image);
+    let distances = mask.distance_to_empty();
+
+    for (filled, distance) in mask.filled.iter_mut().zip(distances) {
+        *filled &= distance <= thickness;
+    }
+
+    mask.apply(image)
+}
+
+/// Fills the gaps up to twice `radius` pixels wide between the pixels of the shape, for dithered pixel art
+/// The mask of the shape is closed, its dilation by `radius` pixels followed by an erosion as large, so the empty
+/// pixels of a dither become solid while the outline keeps its shape. A filled pixel takes the color of the closest
+/// pixel of the shape.
+pub fn fill_holes(image: &image::DynamicImage, radius: u32) -> image::DynamicImage {
+    let mask = Mask::from_image(image);
+    let (closed, sources) = mask.close(radius);
+    let source = image.to_rgba8();
+
+    let mut output = source.clone();
+    for (index, pixel) in output.pixels_mut().enumerate() {
+        if !closed.filled[index] {
+            pixel.0 = [0, 0, 0, 0];
+        } else if !mask.filled[index] {
+            let nearest = sources[index] as u32;
+            *pixel = *source.get_pixel(nearest % mask.width, nearest / mask.width);
+        }
+    }
+
+    image::DynamicImage::ImageRgba8(output)
+}
+
+/// Connected shape of an image, see `split_components`
+#[derive(Clone, Debug)]
+pub struct ImageComponent {
+    /// Position of the top left corner of the image of the shape in the original image
+    pub x: u32,
+    pub y: u32,
+    /// The part of the original image around the shape, the pixels of the other shapes are transparent
+    pub image: image::DynamicImage,
+    /// Pixels that become a voxel
+    pub voxels: usize,
+}
+
+/// Splits the image into its shapes of 4-connected voxels, in the order of their first pixel in the rows
+/// The shapes with fewer than `min_size` voxels are dropped. The pixels are culled with the options first, but the
+/// images of the shapes are cut from the original image, so they're meshed with the same options as the whole image.
+/// Every image keeps an empty margin of one pixel within the original image, so the borders of the image still seal
+/// the same faces. Smooth edges only follow their own shape, shapes touching at a corner are no longer a staircase.
+pub fn split_components(
+    image: &image::DynamicImage,
+    options: &VoxelifyOptions,
+    min_size: usize,
+) -> Vec<ImageComponent> {
+    let (image_width, image_height) = image.dimensions();
+    let mask = Mask::from_image(&preprocess_image(image, options));
+
+    mask.components()
+        .into_iter()
+        .filter(|pixels| pixels.len() >= min_size)
+        .map(|pixels| {
+            let (mut min, mut max) = ((u32::MAX, u32::MAX), (0, 0));
+            for &(x, y) in &pixels {
+                min = (min.0.min(x), min.1.min(y));
+                max = (max.0.max(x), max.1.max(y));
+            }
+            let (x, y) = (min.0.saturating_sub(1), min.1.saturating_sub(1));
+            let width = (max.0 + 2).min(image_width) - x;
+            let height = (max.1 + 2).min(image_height) - y;
+
+            let mut component = Mask {
+                width,
+                height,
+                filled: vec![false; (width * height) as usize],
+            };
+            for &(px, py) in &pixels {
+                let index = component.index(px - x, py - y);
+                component.filled[index] = true;
+            }
+
+            ImageComponent {
+                x,
+                y,
+                image: component.apply(&image.crop_imm(x, y, width, height)),
+                voxels: pixels.len(),
+            }
+        })
+        .collect()
+}
+
+/// Makes the pixels matching the background transparent
+pub fn remove_background(
+    image: &image::DynamicImage,
+    background: Background,
+) -> image::DynamicImage {
+    let mut mask = Mask::from_image(image);
+
+    for (x, y, pixel) in image.pixels() {
+        if background.matches(pixel) {
+            let index = mask.index(x, y);
+            mask.filled[index] = false;
+        }
+    }
+
+    mask.apply(image)
+}
+
+/// Makes the pixels whose luminance is below the threshold transparent
+///
+/// ```
+/// use image::GenericImageView;
+///
+/// // A near-black gradient, like the background of a JPEG
+/// let gradient = image::RgbaImage::from_fn(20, 1, |x, _| image::Rgba([x as u8, x as u8, x as u8, 255]));
+/// let kept = voxelify::remove_dark_pixels(&image::DynamicImage::ImageRgba8(gradient), 12);
+///
+/// let filled: Vec<_> = kept.pixels().filter(|(_, _, pixel)| pixel[3] > 0).map(|(x, _, _)| x).collect();
+/// assert_eq!(filled, (12..20).collect::<Vec<_>>());
+/// ```
+pub fn remove_dark_pixels(image: &image::DynamicImage, min_brightness: u8) -> image::DynamicImage {
+    let mut mask = Mask::from_image(image);
+
+    for (x, y, pixel) in image.pixels() {
+        if pixel_luminance(pixel) < min_brightness as f32 {
+            let index = mask.index(x, y);
+            mask.filled[index] = false;
+        }
+    }
+
+    mask.apply(image)
+}
+
+/// Combines three grayscale images of the same size into the red, green and blue channels of an opaque image
+/// Images that aren't grayscale are converted to their luminance first.
+pub fn compose_channels(
+    red: &image::DynamicImage,
+    green: &image::DynamicImage,
+    blue: &image::DynamicImage,
+) -> Result<image::DynamicImage, VoxelifyError> {
+    for (channel, image) in [("green", green), ("blue", blue)] {
+        if image.dimensions() != red.dimensions() {
+            return Err(VoxelifyError::ChannelMismatch {
+                channel,
+                red: red.dimensions(),
+                found: image.dimensions(),
+            });
+        }
+    }
+
+    let [red, green, blue] = [red, green, blue].map(image::DynamicImage::to_luma8);
+    let composed = image::RgbImage::from_fn(red.width(), red.height(), |x, y| {
+        image::Rgb([&red, &green, &blue].map(|channel| channel.get_pixel(x, y).0[0]))
+    });
+
+    Ok(image::DynamicImage::ImageRgb8(composed))
+}
+
+/// Replaces the colors of the image according to the mapping, the alpha of the pixels is kept
+pub fn remap_colors(
+    image: &image::DynamicImage,
+    mapping: &HashMap<image::Rgb<u8>, ColorMapping>,
+) -> image::DynamicImage {
+    let mut mask = Mask::from_image(image);
+    let mut output = image.to_rgba8();
+
+    for (x, y, pixel) in output.enumerate_pixels_mut() {
+        let color = image::Rgb([pixel.0[0], pixel.0[1], pixel.0[2]]);
+
+        match mapping.get(&color) {
+            Some(ColorMapping::Color(target)) => pixel.0[..3].copy_from_slice(&target.0),
+            Some(ColorMapping::Empty) => {
+                let index = mask.index(x, y);
+                mask.filled[index] = false;
+            }
+            None => {}
+        }
+    }
+
+    mask.apply(&image::DynamicImage::ImageRgba8(output))
+}
 
-    let mut json_offset = json_string.len();
-    align_to_multiple_of_four(&mut json_offset);
+/// Replaces the color of every pixel by the closest color of the palette, the alpha of the pixels is kept
+/// Colors are compared by their euclidean distance in sRGB, the first of the colors as close wins.
+///
+/// ```
+/// use image::GenericImageView;
+///
+/// let gradient = image::RgbaImage::from_fn(256, 1, |x, _| image::Rgba([x as u8, x as u8, 255 - x as u8, 255]));
+/// let palette = [
+///     image::Rgb([0, 0, 255]),
+///     image::Rgb([85, 85, 170]),
+///     image::Rgb([170, 170, 85]),
+///     image::Rgb([255, 255, 0]),
+/// ];
+///
+/// let snapped = voxelify::snap_to_palette(&image::DynamicImage::ImageRgba8(gradient), &palette);
+/// let colors: std::collections::HashSet<_> = snapped.pixels().map(|(_, _, pixel)| pixel).collect();
+/// assert_eq!(colors.len(), 4);
+/// assert!(colors.iter().all(|pixel| palette.contains(&image::Rgb([pixel[0], pixel[1], pixel[2]]))));
+/// ```
+pub fn snap_to_palette(
+    image: &image::DynamicImage,
+    palette: &[image::Rgb<u8>],
+) -> image::DynamicImage {
+    let mask = Mask::from_image(image);
+    let mut output = image.to_rgba8();
+    let mut snapped = HashMap::new();
 
-    let glb = gltf::binary::Glb {
-        header: gltf::binary::Header {
-            magic: *b"glTF",
-            version: 2,
-            // N.B., the size of binary glTF file is limited to range of `u32`.
-            length: (json_offset + calculate_buffer_length(vertices)).try_into()?,
-        },
-        bin: Some(Cow::Owned(to_padded_byte_vector(vertices.to_vec()))),
-        json: Cow::Owned(json_string.into_bytes()),
-    };
+    for pixel in output.pixels_mut() {
+        let color = image::Rgb([pixel.0[0], pixel.0[1], pixel.0[2]]);
+        // Pixel art repeats a few colors, the closest entry is only searched once per color.
+        let closest = *snapped.entry(color).or_insert_with(|| {
+            palette
+                .iter()
+                .min_by_key(|entry| {
+                    entry
+                        .0
+                        .iter()
+                        .zip(color.0)
+                        .map(|(&a, b)| (a as i32 - b as i32).pow(2))
+                        .sum::<i32>()
+                })
+                .copied()
+                .unwrap_or(color)
+        });
+        pixel.0[..3].copy_from_slice(&closest.0);
+    }
 
-    Ok(glb)
+    mask.apply(&image::DynamicImage::ImageRgba8(output))
 }
 
-/// Creates the root JSON object for GLTF
-pub fn create_gltf_root(vertices: &[Vertex], uri: Option<String>) -> gltf::json::Root {
-    let mut root = gltf::json::Root::default();
+/// Gives every label of the image its color from `label_colors`, the alpha of the pixels is kept
+///
+/// ```
+/// use image::GenericImageView;
+///
+/// let mask = image::GrayImage::from_fn(3, 1, |x, _| image::Luma([x as u8]));
+/// let colored = voxelify::color_labels(&image::DynamicImage::ImageLuma8(mask), voxelify::LabelColors { seed: 7 });
+///
+/// let colors: std::collections::HashSet<_> = colored.pixels().map(|(_, _, pixel)| pixel).collect();
+/// assert_eq!(colors.len(), 3);
+/// assert_eq!(colored.get_pixel(1, 0).0[..3], voxelify::LabelColors { seed: 7 }.color(0x01_01_01).0);
+/// ```
+pub fn color_labels(image: &image::DynamicImage, label_colors: LabelColors) -> image::DynamicImage {
+    let mask = Mask::from_image(image);
+    let mut output = image.to_rgba8();
+    let mut colors = HashMap::new();
 
-    let vertex_buffer_length = calculate_buffer_length(vertices);
+    for pixel in output.pixels_mut() {
+        let label = u32::from_be_bytes([0, pixel.0[0], pixel.0[1], pixel.0[2]]);
+        let color = *colors
+            .entry(label)
+            .or_insert_with(|| label_colors.color(label));
+        pixel.0[..3].copy_from_slice(&color.0);
+    }
 
-    let buffer = root.push(gltf::json::Buffer {
-        byte_length: USize64::from(vertex_buffer_length),
-        extensions: Default::default(),
-        extras: Default::default(),
-        name: None,
-        uri,
+    mask.apply(&image::DynamicImage::ImageRgba8(output))
+}
+
+/// Encodes the colors of the voxels of the image as the PNG of `embed_texture`, for the `texture` of its mesh entry
+/// Every pixel gets the color of its voxel after the image operations, the adjustment and the jitter. The colors
+/// stay in sRGB whatever the color target, as glTF expects of base color textures.
+///
+/// ```
+/// let image = image::DynamicImage::ImageRgba8(image::RgbaImage::from_fn(2, 1, |x, _| {
+///     image::Rgba([255 * (1 - x as u8), 255 * x as u8, 0, 255])
+/// }));
+/// let options = voxelify::VoxelifyOptions {
+///     embed_texture: true,
+///     ..Default::default()
+/// };
+///
+/// let vertices = voxelify::image_to_vertices_with_options(&image, &options);
+/// let png = voxelify::bake_texture(&image, &options)?;
+/// let entries = [voxelify::MeshEntry {
+///     texture: Some(&png),
+///     ..voxelify::MeshEntry::new(&vertices)
+/// }];
+/// let root = voxelify::create_gltf_root_multi(&entries, None, &options);
+/// let (json, bin) = voxelify::create_parts_multi(&root, &entries, &options)?;
+///
+/// let parsed: serde_json::Value = serde_json::from_str(&json)?;
+/// assert_eq!(parsed["materials"][0]["pbrMetallicRoughness"]["baseColorTexture"]["index"], 0);
+/// assert_eq!(parsed["images"][0]["mimeType"], "image/png");
+/// assert!(parsed["meshes"][0]["primitives"][0]["attributes"]["COLOR_0"].is_null());
+///
+/// let view = &parsed["bufferViews"][parsed["images"][0]["bufferView"].as_u64().unwrap() as usize];
+/// let start = view["byteOffset"].as_u64().unwrap() as usize;
+/// let end = start + view["byteLength"].as_u64().unwrap() as usize;
+/// assert_eq!(image::load_from_memory(&bin[start..end])?.to_rgba8(), image.to_rgba8());
+///
+/// // LINEAR and LINEAR_MIPMAP_LINEAR by default
+/// assert_eq!(parsed["samplers"][0]["magFilter"], 9729);
+/// assert_eq!(parsed["samplers"][0]["minFilter"], 9987);
+///
+/// // NEAREST, the texels stay crisp however close the faces are
+/// let options = voxelify::VoxelifyOptions { nearest_sampling: true, ..options };
+/// let root = voxelify::create_gltf_root_multi(&entries, None, &options);
+/// let parsed: serde_json::Value = serde_json::from_str(&voxelify::create_parts_multi(&root, &entries, &options)?.0)?;
+/// assert_eq!(parsed["samplers"][0]["magFilter"], 9728);
+/// assert_eq!(parsed["samplers"][0]["minFilter"], 9728);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn bake_texture(
+    image: &image::DynamicImage,
+    options: &VoxelifyOptions,
+) -> Result<Vec<u8>, VoxelifyError> {
+    let image = preprocess_image(image, options);
+    let texture = image::RgbaImage::from_fn(image.width(), image.height(), |x, y| {
+        let [r, g, b] = voxel_color(&image, options, x, y)
+            .map(|channel| (channel * RGB_MAX_VALUE).round().clamp(0.0, RGB_MAX_VALUE) as u8);
+        image::Rgba([r, g, b, image.get_pixel(x, y).0[ALPHA_CHANNEL_INDEX]])
     });
 
-    let vertex_buffer_view = root.push(buffer::View {
+    let mut png = Vec::new();
+    texture.write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)?;
+    Ok(png)
+}
+
+/// The texture of the entry, when the options embed it
+fn embedded_texture<'a>(entry: &MeshEntry<'a>, options: &VoxelifyOptions) -> Option<&'a [u8]> {
+    entry
+        .texture
+        .filter(|_| options.embed_texture && !options.points)
+}
+
+/// Adds the PNG stored at the offset of the buffer as a texture, filtered unless it's sampled at the nearest texel
+/// The faces sample the centers of their texels, so the filtering and the mipmaps don't bleed their neighbors in.
+fn push_texture(
+    root: &mut gltf::json::Root,
+    buffer: Index<json::Buffer>,
+    byte_offset: usize,
+    png: &[u8],
+    nearest: bool,
+) -> Index<json::Texture> {
+    let view = root.push(buffer::View {
         buffer,
-        byte_length: USize64::from(vertex_buffer_length),
-        byte_offset: None,
-        byte_stride: Some(buffer::Stride(std::mem::size_of::<Vertex>())),
+        byte_length: USize64::from(png.len()),
+        byte_offset: Some(USize64::from(byte_offset)),
+        byte_stride: None,
         extensions: Default::default(),
         extras: Default::default(),
         name: None,
-        target: Some(Valid(buffer::Target::ArrayBuffer)),
+        target: None,
     });
-
-    let VoxelAccessors {
-        positions,
-        normals,
-        colors,
-    } = create_accessors(&mut root, &vertex_buffer_view, vertices);
-
-    let primitive = json::mesh::Primitive {
-        attributes: {
-            let mut map = std::collections::BTreeMap::new();
-            map.insert(Valid(json::mesh::Semantic::Positions), positions);
-            map.insert(Valid(json::mesh::Semantic::Normals), normals);
-            map.insert(Valid(json::mesh::Semantic::Colors(0)), colors);
-            map
-        },
-        extensions: Default::default(),
-        extras: Default::default(),
-        indices: None,
-        material: None,
-        mode: Valid(json::mesh::Mode::Triangles),
-        targets: None,
-    };
-
-    let mesh = root.push(json::Mesh {
+    let source = root.push(json::Image {
+        buffer_view: Some(view),
+        mime_type: Some(json::image::MimeType("image/png".to_string())),
+        name: None,
+        uri: None,
         extensions: Default::default(),
         extras: Default::default(),
-        name: None,
-        primitives: vec![primitive],
-        weights: None,
     });
-
-    let node = root.push(json::Node {
-        mesh: Some(mesh),
+    let (mag_filter, min_filter) = if nearest {
+        (
+            json::texture::MagFilter::Nearest,
+            json::texture::MinFilter::Nearest,
+        )
+    } else {
+        (
+            json::texture::MagFilter::Linear,
+            json::texture::MinFilter::LinearMipmapLinear,
+        )
+    };
+    let sampler = root.push(json::texture::Sampler {
+        mag_filter: Some(Valid(mag_filter)),
+        min_filter: Some(Valid(min_filter)),
+        wrap_s: Valid(json::texture::WrappingMode::ClampToEdge),
+        wrap_t: Valid(json::texture::WrappingMode::ClampToEdge),
         ..Default::default()
     });
 
-    root.push(json::Scene {
+    root.push(json::Texture {
+        name: None,
+        sampler: Some(sampler),
+        source,
         extensions: Default::default(),
         extras: Default::default(),
-        name: None,
-        nodes: vec![node],
+    })
+}
+
+/// Applies the image operations from the options before the image is meshed
+fn preprocess_image<'a>(
+    image: &'a image::DynamicImage,
+    options: &VoxelifyOptions,
+) -> Cow<'a, image::DynamicImage> {
+    let mut image = Cow::Borrowed(image);
+
+    if let Some(background) = options.background {
+        image = Cow::Owned(remove_background(&image, background));
+    }
+
+    if options.min_brightness > 0 {
+        image = Cow::Owned(remove_dark_pixels(&image, options.min_brightness));
+    }
+
+    if options.normalize_colors {
+        image = Cow::Owned(normalize_colors(&image));
+    }
+
+    if let Some(label_colors) = options.label_colors {
+        image = Cow::Owned(color_labels(&image, label_colors));
+    }
+
+    if !options.color_map.is_empty() {
+        image = Cow::Owned(remap_colors(&image, &options.color_map));
+    }
+
+    if !options.palette.is_empty() {
+        image = Cow::Owned(snap_to_palette(&image, &options.palette));
+    }
+
+    if let Some(radius) = options.fill_holes {
+        image = Cow::Owned(fill_holes(&image, radius));
+    }
+
+    if let Some(thickness) = options.outline {
+        image = Cow::Owned(outline_image(&image, thickness));
+    }
+
+    image
+}
+
+/// Appends a reflected copy of the vertices across the far side of their bounds on the given axis
+/// The faces lying on the seam between the two halves are removed, as they would end up inside the model
+pub fn mirror_vertices(vertices: &[Vertex], axis: Axis) -> Vec<Vertex> {
+    if vertices.is_empty() {
+        return Vec::new();
+    }
+
+    let axis = axis as usize;
+    let (_, max) = bounding_coords(vertices);
+    let seam = max[axis];
+
+    let faces: Vec<&[Vertex]> = vertices
+        .chunks(VERTICES_PER_FACE)
+        .filter(|face| {
+            !face
+                .iter()
+                .all(|vertex| vertex.position[axis] == seam && vertex.normal[axis] > 0.0)
+        })
+        .collect();
+
+    let mut mirrored = Vec::with_capacity(faces.len() * VERTICES_PER_FACE * 2);
+    mirrored.extend(faces.iter().flat_map(|face| face.iter().copied()));
+
+    for face in faces {
+        let mut face = face.to_vec();
+        for vertex in &mut face {
+            vertex.position[axis] = 2.0 * seam - vertex.position[axis];
+            vertex.normal[axis] = -vertex.normal[axis];
+        }
+
+        // A reflection turns the faces inside out, so their winding is reversed.
+        reverse_winding(&mut face);
+        mirrored.extend(face);
+    }
+
+    mirrored
+}
+
+/// Part of a model, see `split_into_tiles`
+#[derive(Clone, Debug)]
+pub struct MeshTile {
+    /// Position of the tile on the grid, from the corner of the model where the image starts
+    pub column: u32,
+    pub row: u32,
+    pub vertices: Vec<Vertex>,
+}
+
+/// Splits the vertices into square tiles on the plane of the image, so no tile has more than `max_vertices`
+/// The tiles are the largest that fit, halving their side until every one does. Every face goes to the tile its
+/// center is in, so the faces between the tiles stay culled like in the whole model. Only the tiles with faces are
+/// returned, in the order of their rows.
+///
+/// ```
+/// let image = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(8, 8, image::Rgba([255, 0, 0, 255])));
+/// let vertices = voxelify::image_to_vertices(&image, 1.0);
+///
+/// let tiles = voxelify::split_into_tiles(&vertices, &Default::default(), 100)?;
+/// assert!(tiles.len() > 1);
+/// assert!(tiles.iter().all(|tile| tile.vertices.len() <= 100));
+/// assert_eq!(tiles.iter().map(|tile| tile.vertices.len()).sum::<usize>(), vertices.len());
+/// # Ok::<(), voxelify::VoxelifyError>(())
+/// ```
+pub fn split_into_tiles(
+    vertices: &[Vertex],
+    options: &VoxelifyOptions,
+    max_vertices: usize,
+) -> Result<Vec<MeshTile>, VoxelifyError> {
+    if vertices.len() <= max_vertices {
+        return Ok(vec![MeshTile {
+            column: 0,
+            row: 0,
+            vertices: vertices.to_vec(),
+        }]);
+    }
+
+    let face_length = if options.points { 1 } else { VERTICES_PER_FACE };
+    // The axes the X and the Y of the image went to.
+    let axes = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0]].map(|direction| {
+        options
+            .extrude_axis
+            .extrude(direction)
+            .iter()
+            .position(|&component| component != 0.0)
+            .expect("the extrusion is a rotation")
     });
+    let (min, max) = bounding_coords(vertices);
+    let extent = axes.map(|axis| max[axis] - min[axis]);
+    let centers: Vec<[f32; 2]> = vertices
+        .chunks(face_length)
+        .map(|face| {
+            axes.map(|axis| {
+                let sum: f32 = face.iter().map(|vertex| vertex.position[axis]).sum();
+                sum / face.len() as f32 - min[axis]
+            })
+        })
+        .collect();
 
-    root
+    let mut side = (extent[0].max(extent[1]).ceil() as u32).max(1);
+    loop {
+        // The sides on the far edges of the model go to the last tiles rather than to tiles of their own.
+        let cell = |center: [f32; 2]| {
+            std::array::from_fn(|index| {
+                let last = (extent[index] / side as f32).ceil().max(1.0) as u32 - 1;
+                ((center[index] / side as f32) as u32).min(last)
+            })
+        };
+        let mut tiles: Vec<MeshTile> = Vec::new();
+        let mut indices: HashMap<[u32; 2], usize> = HashMap::new();
+        for (face, &center) in vertices.chunks(face_length).zip(&centers) {
+            let [column, row]: [u32; 2] = cell(center);
+            let index = *indices.entry([column, row]).or_insert_with(|| {
+                tiles.push(MeshTile {
+                    column,
+                    row,
+                    vertices: Vec::new(),
+                });
+                tiles.len() - 1
+            });
+            tiles[index].vertices.extend_from_slice(face);
+        }
+
+        let largest = tiles.iter().map(|tile| tile.vertices.len()).max();
+        if largest.is_some_and(|largest| largest <= max_vertices) {
+            tiles.sort_by_key(|tile| (tile.row, tile.column));
+            return Ok(tiles);
+        }
+        if side == 1 {
+            return Err(VoxelifyError::TooManyVertices {
+                vertices: largest.unwrap_or_default(),
+                max_vertices,
+            });
+        }
+        side = side.div_ceil(2);
+    }
 }
 
-/// Converts an image to list of vertices that can be used to create a mesh
-/// Each pixel will be converted to a voxel with height determined by the height argument
-/// The faces of the voxel will be culled if they are not visible
-pub fn image_to_vertices(image: &image::DynamicImage, height: f32) -> Vec<Vertex> {
-    let (image_width, image_height) = image.dimensions();
-    let mut vertices = Vec::new();
+/// Moves every mesh by its transform and concatenates them into a single one
+/// The normals and the tangents follow the rotation and the scale, a mirroring scale also reverses the winding so
+/// the faces keep pointing outwards.
+///
+/// ```
+/// use voxelify::{merge_meshes, NodeTransform};
+///
+/// let image = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+///     1,
+///     1,
+///     image::Rgba([255, 255, 255, 255]),
+/// ));
+/// let cube = voxelify::image_to_vertices(&image, 1.0);
+///
+/// // A quarter turn around Z takes (x, y, z) to (-y, x, z).
+/// let half = std::f32::consts::FRAC_PI_4;
+/// let rotation = NodeTransform {
+///     rotation: [0.0, 0.0, half.sin(), half.cos()],
+///     ..Default::default()
+/// };
+/// let merged = merge_meshes(&[(cube, rotation)]);
+///
+/// let close = |a: [f32; 3], b: [f32; 3]| (0..3).all(|axis| (a[axis] - b[axis]).abs() < 1e-5);
+/// assert!(merged.iter().any(|vertex| close(vertex.position(), [-1.0, 1.0, 1.0])));
+/// assert!(merged.iter().all(|vertex| vertex.position()[0] <= 1e-5));
+/// assert!(merged.iter().any(|vertex| close(vertex.normal(), [0.0, 1.0, 0.0])));
+/// ```
+pub fn merge_meshes(meshes: &[(Vec<Vertex>, NodeTransform)]) -> Vec<Vertex> {
+    let mut merged = Vec::with_capacity(meshes.iter().map(|(vertices, _)| vertices.len()).sum());
 
-    for y in 0..image_height {
-        for x in 0..image_width {
-            let pixel = image.get_pixel(x, y);
-            if is_empty_pixel(pixel) {
-                continue;
-            }
+    for (vertices, transform) in meshes {
+        let [x, y, z, w] = transform.rotation;
+        let rotation = UnitQuaternion::from_quaternion(Quaternion::new(w, x, y, z));
+        let scale = Vector3::from(transform.scale);
+        let translation = Vector3::from(transform.translation);
+        let mirrored = scale.x * scale.y * scale.z < 0.0;
 
-            let faces = cull_faces(image, Vector2::new(x, y));
+        let start = merged.len();
+        merged.extend(vertices.iter().map(|vertex| {
+            let position = rotation * Vector3::from(vertex.position).component_mul(&scale);
+            // Normals go through the inverse transpose, which is the inverse scale for a rotation and a scale.
+            let normal = rotation * Vector3::from(vertex.normal).component_div(&scale);
+            let [tx, ty, tz, tw] = vertex.tangent;
+            let tangent = rotation * Vector3::new(tx, ty, tz).component_mul(&scale);
+            let tangent = tangent.try_normalize(0.0).unwrap_or(tangent);
 
-            for face in faces {
-                vertices.extend(create_pixel_verticies_face(
-                    [x as f32, y as f32],
-                    pixel,
-                    height,
-                    &face,
-                ));
+            Vertex {
+                position: (position + translation).into(),
+                normal: normal.try_normalize(0.0).unwrap_or(normal).into(),
+                tangent: [
+                    tangent.x,
+                    tangent.y,
+                    tangent.z,
+                    if mirrored { -tw } else { tw },
+                ],
+                ..*vertex
             }
+        }));
+
+        if mirrored {
+            reverse_winding(&mut merged[start..]);
         }
     }
 
-    vertices
+    merged
 }
 
-/// Creates a padded byte vector from a generic vector
-/// Needed for creating the GLB/GLTF file
-pub fn to_padded_byte_vector<T>(vec: Vec<T>) -> Vec<u8> {
-    let byte_length = vec.len() * std::mem::size_of::<T>();
-    let byte_capacity = vec.capacity() * std::mem::size_of::<T>();
+/// Reverses the winding of every face, while keeping the (a, b, c) and (c, b, d) order of the quads
+pub fn reverse_winding(vertices: &mut [Vertex]) {
+    for face in vertices.chunks_exact_mut(VERTICES_PER_FACE) {
+        face.swap(1, 2);
+        face.swap(3, 4);
+    }
+}
 
-    // Ensure the capacity of the vector is rounded up to the nearest multiple of four bytes.
-    let padding = (PADDING - byte_length % PADDING) % PADDING;
-    let padded_capacity = byte_capacity + padding;
+/// Removes the triangles with two corners at the same position and returns how many were removed
+/// The faces lose their (a, b, c) and (c, b, d) order when only one of their triangles goes, so the remaining
+/// vertices can't be drawn as a wireframe or grouped by their glow.
+///
+/// ```
+/// use voxelify::{merge_meshes, remove_degenerate_triangles, NodeTransform};
+///
+/// let image = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+///     1,
+///     1,
+///     image::Rgba([255, 255, 255, 255]),
+/// ));
+/// let cube = voxelify::image_to_vertices(&image, 1.0);
+///
+/// // Squashing the cube flat collapses its four sides to lines.
+/// let flat = NodeTransform {
+///     scale: [1.0, 1.0, 0.0],
+///     ..Default::default()
+/// };
+/// let mut vertices = merge_meshes(&[(cube, flat)]);
+/// assert_eq!(remove_degenerate_triangles(&mut vertices), 8);
+///
+/// // The top and the bottom are left.
+/// assert_eq!(vertices.len(), 12);
+/// assert!(vertices.chunks_exact(3).all(|triangle| {
+///     let [a, b, c] = [0, 1, 2].map(|index| triangle[index].position());
+///     a != b && b != c && c != a
+/// }));
+/// ```
+pub fn remove_degenerate_triangles(vertices: &mut Vec<Vertex>) -> usize {
+    let triangles = vertices.len() / 3;
+    let kept: Vec<Vertex> = vertices
+        .chunks_exact(3)
+        .filter(|triangle| {
+            let [a, b, c] = [0, 1, 2].map(|index| triangle[index].position);
+            a != b && b != c && c != a
+        })
+        .flatten()
+        .copied()
+        .collect();
 
-    let alloc = vec.into_boxed_slice();
-    let ptr = Box::into_raw(alloc) as *mut u8;
+    *vertices = kept;
+    triangles - vertices.len() / 3
+}
 
-    // Assumes the content is properly aligned as u8.
-    let mut padded_vec = unsafe { Vec::from_raw_parts(ptr, byte_length, padded_capacity) };
+/// Generates the indices of the edges of each face, to be drawn as lines
+/// Each face is made of 6 vertices, only the outline of the quad is kept and the diagonal is skipped
+pub fn generate_line_indices(vertex_count: usize) -> Vec<u32> {
+    let mut indices = Vec::with_capacity(vertex_count / VERTICES_PER_FACE * LINE_INDICES_PER_FACE);
 
-    // Add zero-padding to ensure the length is a multiple of four bytes.
-    padded_vec.resize(padded_vec.len() + padding, 0);
+    for face in 0..(vertex_count / VERTICES_PER_FACE) as u32 {
+        let base = face * VERTICES_PER_FACE as u32;
+        // The quad corners a, b, c, d are stored at offsets 0, 1, 2 and 5.
+        indices.extend(
+            [0, 1, 1, 5, 5, 2, 2, 0]
+                .into_iter()
+                .map(|offset| base + offset),
+        );
+    }
 
-    padded_vec
+    indices
 }
 
-/// Struct for storing accessors for the GLTF vertex buffer
-struct VoxelAccessors {
-    positions: Index<json::Accessor>,
-    normals: Index<json::Accessor>,
-    colors: Index<json::Accessor>,
+/// Location of a single vertex attribute inside the GLTF buffer
+struct AttributeView {
+    view: Index<buffer::View>,
+    offset: usize,
+    /// Distance between the attribute of two consecutive vertices
+    stride: usize,
 }
 
 // Enum for the faces of a voxel
@@ -186,107 +3296,520 @@ enum Face {
     Back,
 }
 
+/// Face of the unit voxel, which spans from zero to one on every axis
+struct FaceGeometry {
+    /// Offsets of the a, b, c, d corners of `create_quad` from the voxel, a Z of one is the ceiling
+    corners: [[f32; 3]; 4],
+    normal: [f32; 3],
+}
+
+/// Faces of the unit voxel in the order of the variants of `Face`
+const UNIT_CUBE: [FaceGeometry; 6] = [
+    FaceGeometry {
+        corners: [
+            [0.0, 0.0, 1.0],
+            [1.0, 0.0, 1.0],
+            [0.0, 1.0, 1.0],
+            [1.0, 1.0, 1.0],
+        ],
+        normal: [0.0, 0.0, 1.0],
+    },
+    FaceGeometry {
+        corners: [
+            [0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+        ],
+        normal: [0.0, 0.0, -1.0],
+    },
+    FaceGeometry {
+        corners: [
+            [0.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0],
+            [0.0, 1.0, 0.0],
+            [0.0, 1.0, 1.0],
+        ],
+        normal: [-1.0, 0.0, 0.0],
+    },
+    FaceGeometry {
+        corners: [
+            [1.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [1.0, 0.0, 1.0],
+            [1.0, 1.0, 1.0],
+        ],
+        normal: [1.0, 0.0, 0.0],
+    },
+    FaceGeometry {
+        corners: [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0],
+            [1.0, 0.0, 1.0],
+        ],
+        normal: [0.0, -1.0, 0.0],
+    },
+    FaceGeometry {
+        corners: [
+            [0.0, 1.0, 0.0],
+            [0.0, 1.0, 1.0],
+            [1.0, 1.0, 0.0],
+            [1.0, 1.0, 1.0],
+        ],
+        normal: [0.0, 1.0, 0.0],
+    },
+];
+
+impl Face {
+    fn geometry(self) -> &'static FaceGeometry {
+        &UNIT_CUBE[self as usize]
+    }
+}
+
+const FACES: [Face; 6] = [
+    Face::Up,
+    Face::Down,
+    Face::Left,
+    Face::Right,
+    Face::Forward,
+    Face::Back,
+];
+
 /// Returns the faces that should be visible for a given pixel
-fn cull_faces(image: &image::DynamicImage, pos: Vector2<u32>) -> Vec<Face> {
-    let (image_width, image_height) = image.dimensions();
+fn cull_faces(image: &image::DynamicImage, pos: Vector2<u32>, border: BorderMode) -> Vec<Face> {
+    let (x, y) = (pos.x as i64, pos.y as i64);
+    let exposed = |dx: i64, dy: i64| !is_filled(image, x + dx, y + dy, border);
 
     // Initialize with Up and Down faces as they're always visible in a 2D image
     let mut faces = vec![Face::Up, Face::Down];
 
-    match pos.x {
-        0 => faces.push(Face::Left),
-        x if x == image_width - 1 => faces.push(Face::Right),
-        _ => {
-            // Check on the x-axis for adjacent pixels that are empty.
-            if is_empty_pixel(image.get_pixel(pos.x + 1, pos.y)) {
-                faces.push(Face::Right);
-            }
-            if is_empty_pixel(image.get_pixel(pos.x - 1, pos.y)) {
-                faces.push(Face::Left);
-            }
+    // Check on the x-axis for adjacent pixels that are empty.
+    if exposed(1, 0) {
+        faces.push(Face::Right);
+    }
+    if exposed(-1, 0) {
+        faces.push(Face::Left);
+    }
+
+    // Check on the y-axis for adjacent pixels that are empty.
+    if exposed(0, 1) {
+        faces.push(Face::Back);
+    }
+    if exposed(0, -1) {
+        faces.push(Face::Forward);
+    }
+
+    faces
+}
+
+/// Serializes the provenance of a model into the `extras` of the GLTF root
+fn provenance_extras(
+    provenance: &Provenance,
+    options: &VoxelifyOptions,
+) -> Box<json::extras::RawValue> {
+    let extras = serde_json::json!({
+        "voxelify": {
+            "version": env!("CARGO_PKG_VERSION"),
+            "source": {
+                "file_name": provenance.file_name,
+                "width": provenance.width,
+                "height": provenance.height,
+            },
+            "options": {
+                "z_floor": options.z_floor,
+                "z_ceiling": options.z_ceiling,
+                "layout": format!("{:?}", options.layout).to_lowercase(),
+            },
+        }
+    });
+
+    // A `serde_json::Value` always serializes into valid JSON.
+    serde_json::value::to_raw_value(&extras).expect("extras are valid JSON")
+}
+
+/// Creates the buffer views for the vertex attributes according to the layout
+/// Must match the byte layout produced by `create_buffer`, the views are returned in the order of the encoded attributes
+fn create_buffer_views(
+    root: &mut gltf::json::Root,
+    buffer: Index<json::Buffer>,
+    byte_offset: usize,
+    vertices: &[Vertex],
+    encoding: &VertexEncoding,
+    layout: BufferLayout,
+) -> Vec<AttributeView> {
+    match layout {
+        BufferLayout::Interleaved => {
+            debug_assert_eq!(byte_offset % PADDING, 0, "unaligned buffer view");
+            let view = root.push(buffer::View {
+                buffer,
+                byte_length: USize64::from(vertices.len() * encoding.stride()),
+                byte_offset: (byte_offset > 0).then(|| USize64::from(byte_offset)),
+                byte_stride: Some(buffer::Stride(encoding.stride())),
+                extensions: Default::default(),
+                extras: Default::default(),
+                name: None,
+                target: Some(Valid(buffer::Target::ArrayBuffer)),
+            });
+
+            let mut offset = 0;
+            encoding
+                .attributes
+                .iter()
+                .map(|&attribute| {
+                    let attribute_view = AttributeView {
+                        view,
+                        offset,
+                        stride: encoding.stride(),
+                    };
+                    offset += encoding.element_size(attribute);
+                    attribute_view
+                })
+                .collect()
+        }
+        BufferLayout::Separate => {
+            let mut byte_offset = byte_offset;
+            encoding
+                .attributes
+                .iter()
+                .map(|&attribute| {
+                    let element_size = encoding.element_size(attribute);
+                    let byte_length = vertices.len() * element_size;
+                    debug_assert_eq!(byte_offset % PADDING, 0, "unaligned buffer view");
+                    // Elements that are padded, like the quantized ones, aren't tightly packed.
+                    let packed = encoding.accessor_type(attribute).multiplicity()
+                        * encoding.component_type(attribute).size();
+                    let view = root.push(buffer::View {
+                        buffer,
+                        byte_length: USize64::from(byte_length),
+                        byte_offset: Some(USize64::from(byte_offset)),
+                        byte_stride: (element_size != packed)
+                            .then_some(buffer::Stride(element_size)),
+                        extensions: Default::default(),
+                        extras: Default::default(),
+                        name: None,
+                        target: Some(Valid(buffer::Target::ArrayBuffer)),
+                    });
+                    byte_offset +=
+                        padded_view_length(vertices.len(), encoding.element_size(attribute));
+
+                    AttributeView {
+                        view,
+                        offset: 0,
+                        stride: encoding.element_size(attribute),
+                    }
+                })
+                .collect()
         }
     }
+}
+
+/// Creates the BIN buffer holding the vertex attributes according to the layout, followed by the indices
+fn create_buffer(
+    vertices: &[Vertex],
+    encoding: &VertexEncoding,
+    options: &VoxelifyOptions,
+) -> Vec<u8> {
+    let mut buffer = create_vertex_buffer(vertices, encoding, options.layout);
+
+    if has_line_indices(options) {
+        // The indices of every primitive start from the first vertex of its accessors.
+        buffer.extend(
+            primitive_ranges(vertices, options)
+                .into_iter()
+                .flat_map(|(range, _)| generate_line_indices(range.len()))
+                .flat_map(u32::to_le_bytes),
+        );
+        pad_to_4(&mut buffer);
+    }
+
+    buffer
+}
 
-    match pos.y {
-        0 => faces.push(Face::Forward),
-        y if y == image_height - 1 => faces.push(Face::Back),
-        _ => {
-            // Check on the y-axis for adjacent pixels that are empty.
-            if is_empty_pixel(image.get_pixel(pos.x, pos.y + 1)) {
-                faces.push(Face::Back);
+/// Pads the buffer with zeros so the next buffer view starts on a four byte boundary
+#[inline]
+fn pad_to_4(buffer: &mut Vec<u8>) {
+    buffer.resize(align4(buffer.len()), 0);
+}
+
+fn create_vertex_buffer(
+    vertices: &[Vertex],
+    encoding: &VertexEncoding,
+    layout: BufferLayout,
+) -> Vec<u8> {
+    let mut buffer = Vec::with_capacity(calculate_vertex_buffer_length(
+        vertices.len(),
+        encoding,
+        layout,
+    ));
+
+    match layout {
+        BufferLayout::Interleaved => {
+            for vertex in vertices {
+                for &attribute in &encoding.attributes {
+                    encoding.write(attribute, vertex, &mut buffer);
+                }
             }
-            if is_empty_pixel(image.get_pixel(pos.x, pos.y - 1)) {
-                faces.push(Face::Forward);
+            pad_to_4(&mut buffer);
+        }
+        BufferLayout::Separate => {
+            for &attribute in &encoding.attributes {
+                for vertex in vertices {
+                    encoding.write(attribute, vertex, &mut buffer);
+                }
+                pad_to_4(&mut buffer);
             }
         }
     }
 
-    faces
+    buffer
 }
 
-fn create_accessors(
+/// Only uses a material when the options or the glow change it from the default glTF material, or it's named
+/// An existing material with the same properties and name is shared instead of pushing a duplicate, so the meshes
+/// added to the same root share their materials
+fn find_or_create_material(
     root: &mut gltf::json::Root,
-    buffer_view: &Index<buffer::View>,
+    options: &VoxelifyOptions,
     vertices: &[Vertex],
-) -> VoxelAccessors {
-    let (min, max) = bounding_coords(vertices);
+    emissive: Option<[f32; 3]>,
+    texture: Option<Index<json::Texture>>,
+) -> Option<json::Index<json::Material>> {
+    if !options.double_sided
+        && !options.vertex_alpha
+        && emissive.is_none()
+        && texture.is_none()
+        && options.material_name.is_none()
+        && !options.fallback_color
+        && !options.color_target.unlit()
+        && has_vertex_colors(options)
+    {
+        return None;
+    }
 
-    let positions = root.push(json::Accessor {
-        buffer_view: Some(*buffer_view),
-        byte_offset: Some(USize64(0)),
-        count: USize64::from(vertices.len()),
-        component_type: Valid(json::accessor::GenericComponentType(
-            json::accessor::ComponentType::F32,
-        )),
-        extensions: Default::default(),
-        extras: Default::default(),
-        type_: Valid(json::accessor::Type::Vec3),
-        min: Some(json::Value::from(Vec::from(min))),
-        max: Some(json::Value::from(Vec::from(max))),
-        name: None,
-        normalized: false,
-        sparse: None,
-    });
+    // The factor would tint the texture.
+    let base_color_factor = if let Some(color) = options
+        .monochrome
+        .filter(|_| texture.is_none() && !has_vertex_colors(options))
+    {
+        let [r, g, b] = options
+            .color_target
+            .convert(color.0.map(|channel| channel as f32 / RGB_MAX_VALUE));
+        [r, g, b, 1.0]
+    } else if options.fallback_color && texture.is_none() && !vertices.is_empty() {
+        let mut sum = [0.0; 3];
+        for vertex in vertices {
+            for (channel, value) in vertex.color.into_iter().enumerate() {
+                sum[channel] += value;
+            }
+        }
+        let [r, g, b] = options
+            .color_target
+            .convert(sum.map(|channel| channel / vertices.len() as f32));
+        [r, g, b, 1.0]
+    } else {
+        [1.0; 4]
+    };
 
-    let normals = root.push(json::Accessor {
-        buffer_view: Some(*buffer_view),
-        byte_offset: Some(USize64::from(3 * std::mem::size_of::<f32>())),
-        count: USize64::from(vertices.len()),
-        component_type: Valid(json::accessor::GenericComponentType(
-            json::accessor::ComponentType::F32,
-        )),
-        extensions: Default::default(),
-        extras: Default::default(),
-        type_: Valid(json::accessor::Type::Vec3),
-        min: Some(json::Value::from(Vec::from([-1.0, -1.0, -1.0]))),
-        max: Some(json::Value::from(Vec::from([1.0, 1.0, 1.0]))),
-        name: None,
-        normalized: false,
-        sparse: None,
+    let alpha_mode = Valid(if options.vertex_alpha {
+        json::material::AlphaMode::Blend
+    } else {
+        json::material::AlphaMode::Opaque
     });
 
-    let colors = root.push(json::Accessor {
-        buffer_view: Some(*buffer_view),
-        byte_offset: Some(USize64::from(6 * std::mem::size_of::<f32>())),
-        count: USize64::from(vertices.len()),
-        component_type: Valid(json::accessor::GenericComponentType(
-            json::accessor::ComponentType::F32,
-        )),
-        extensions: Default::default(),
-        extras: Default::default(),
-        type_: Valid(json::accessor::Type::Vec3),
-        min: None,
-        max: None,
-        name: None,
-        normalized: false,
-        sparse: None,
-    });
+    // The factor can't go above one, a stronger glow needs the extension.
+    let strength = options.emissive_strength.max(0.0);
+    let emissive = emissive.map(|color| options.color_target.convert(color));
+    let (emissive_factor, emissive_strength) = match emissive {
+        Some(color) if strength > 1.0 => (
+            color,
+            Some(json::extensions::material::EmissiveStrength {
+                emissive_strength: json::extensions::material::EmissiveStrengthFactor(strength),
+            }),
+        ),
+        Some(color) => (color.map(|component| component * strength), None),
+        None => ([0.0; 3], None),
+    };
+    let unlit = options
+        .color_target
+        .unlit()
+        .then_some(json::extensions::material::Unlit {});
+    let extensions = (emissive_strength.is_some() || unlit.is_some()).then_some(
+        json::extensions::material::Material {
+            emissive_strength,
+            unlit,
+        },
+    );
+
+    let material = json::Material {
+        name: options.material_name.clone(),
+        alpha_mode,
+        double_sided: options.double_sided,
+        pbr_metallic_roughness: json::material::PbrMetallicRoughness {
+            base_color_factor: json::material::PbrBaseColorFactor(base_color_factor),
+            base_color_texture: texture.map(|index| json::texture::Info {
+                index,
+                tex_coord: 0,
+                extensions: Default::default(),
+                extras: Default::default(),
+            }),
+            ..Default::default()
+        },
+        emissive_factor: json::material::EmissiveFactor(emissive_factor),
+        extensions,
+        ..Default::default()
+    };
+
+    // Materials have no `PartialEq`, their JSON is compared instead.
+    let serialized = serde_json::to_value(&material).ok();
+    let existing = root
+        .materials
+        .iter()
+        .position(|existing| serde_json::to_value(existing).ok() == serialized);
+    if let Some(index) = existing {
+        return Some(Index::new(index as u32));
+    }
 
-    VoxelAccessors {
-        positions,
-        normals,
-        colors,
+    Some(root.push(material))
+}
+
+/// Declares the extensions the root actually uses, a required extension that isn't used still makes viewers
+/// without it refuse the file
+/// The declarations of the extensions voxelify doesn't write are left to the caller and kept.
+fn declare_extensions(root: &mut gltf::json::Root) {
+    let quantized = root
+        .meshes
+        .iter()
+        .flat_map(|mesh| &mesh.primitives)
+        .flat_map(|primitive| {
+            [
+                json::mesh::Semantic::Positions,
+                json::mesh::Semantic::Normals,
+            ]
+            .into_iter()
+            .filter_map(|semantic| primitive.attributes.get(&Valid(semantic)))
+        })
+        .filter_map(|accessor| root.get(*accessor))
+        .any(|accessor| {
+            !matches!(
+                accessor.component_type,
+                Valid(json::accessor::GenericComponentType(
+                    json::accessor::ComponentType::F32
+                ))
+            )
+        });
+    let materials = || {
+        root.materials
+            .iter()
+            .filter_map(|material| material.extensions.as_ref())
+    };
+    let emissive_strength = materials().any(|extensions| extensions.emissive_strength.is_some());
+    let unlit = materials().any(|extensions| extensions.unlit.is_some());
+
+    let ours = [
+        MESH_QUANTIZATION_EXTENSION,
+        EMISSIVE_STRENGTH_EXTENSION,
+        UNLIT_EXTENSION,
+    ];
+    root.extensions_used
+        .retain(|extension| !ours.contains(&extension.as_str()));
+    root.extensions_required
+        .retain(|extension| !ours.contains(&extension.as_str()));
+
+    // Quantized positions and normals can't be read without the extension, while viewers that lack the material extensions
+    // still show the colors and the glow.
+    if quantized {
+        root.extensions_used
+            .push(MESH_QUANTIZATION_EXTENSION.to_string());
+        root.extensions_required
+            .push(MESH_QUANTIZATION_EXTENSION.to_string());
+    }
+    if emissive_strength {
+        root.extensions_used
+            .push(EMISSIVE_STRENGTH_EXTENSION.to_string());
     }
+    if unlit {
+        root.extensions_used.push(UNLIT_EXTENSION.to_string());
+    }
+}
+
+/// Creates one accessor per attribute of the encoding, with the bounds of the data stored in the buffer
+/// `first` is the index of the first of the vertices in the views.
+fn create_accessors(
+    root: &mut gltf::json::Root,
+    views: &[AttributeView],
+    first: usize,
+    vertices: &[Vertex],
+    encoding: &VertexEncoding,
+) -> Vec<(Attribute, Index<json::Accessor>)> {
+    encoding
+        .attributes
+        .iter()
+        .zip(views)
+        .map(|(&attribute, view)| {
+            let (min, max) = match attribute {
+                Attribute::Position => {
+                    let (min, max) = bounding_coords(vertices);
+                    match encoding.quantization {
+                        Some(quantization) => (
+                            json::Value::from(Vec::from(quantization.quantize(min))),
+                            json::Value::from(Vec::from(quantization.quantize(max))),
+                        ),
+                        None => json_bounds((min, max)),
+                    }
+                }
+                // The bounds of normalized integers are the integers, not the values they stand for.
+                Attribute::Normal if encoding.quantized_normals => {
+                    let (min, max) = component_bounds(vertices, |vertex| {
+                        encoding.quantize_normal(vertex).map(f32::from)
+                    });
+                    (
+                        json::Value::from(min.map(|component| component as i8).to_vec()),
+                        json::Value::from(max.map(|component| component as i8).to_vec()),
+                    )
+                }
+                Attribute::Normal => {
+                    json_bounds(component_bounds(vertices, |vertex| vertex.normal))
+                }
+                Attribute::Color if encoding.color_alpha => {
+                    json_bounds(component_bounds(vertices, |vertex| encoding.rgba(vertex)))
+                }
+                Attribute::Color => {
+                    json_bounds(component_bounds(vertices, |vertex| encoding.rgb(vertex)))
+                }
+                Attribute::TexCoord => json_bounds(component_bounds(vertices, |vertex| vertex.uv)),
+                Attribute::Tangent => {
+                    json_bounds(component_bounds(vertices, |vertex| vertex.tangent))
+                }
+            };
+
+            let accessor = root.push(json::Accessor {
+                buffer_view: Some(view.view),
+                byte_offset: Some(USize64::from(view.offset + first * view.stride)),
+                count: USize64::from(vertices.len()),
+                component_type: Valid(json::accessor::GenericComponentType(
+                    encoding.component_type(attribute),
+                )),
+                extensions: Default::default(),
+                extras: Default::default(),
+                type_: Valid(encoding.accessor_type(attribute)),
+                min: Some(min),
+                max: Some(max),
+                name: None,
+                normalized: encoding.normalized(attribute),
+                sparse: None,
+            });
+
+            (attribute, accessor)
+        })
+        .collect()
+}
+
+fn json_bounds<const N: usize>((min, max): ([f32; N], [f32; N])) -> (json::Value, json::Value) {
+    (
+        json::Value::from(Vec::from(min)),
+        json::Value::from(Vec::from(max)),
+    )
 }
 
 #[inline]
@@ -294,100 +3817,170 @@ fn is_empty_pixel(pixel: image::Rgba<u8>) -> bool {
     pixel.0[ALPHA_CHANNEL_INDEX] == 0
 }
 
-#[rustfmt::skip]
-/// Creates the two triangles for a face of a voxel
-fn create_pixel_verticies_face(
-    pos: [f32; 2],
-    color: image::Rgba<u8>,
-    height: f32,
-    face: &Face,
-) -> Vec<Vertex> {
-    let color = [
-        color.0[0] as f32 / RGB_MAX_VALUE,
-        color.0[1] as f32 / RGB_MAX_VALUE,
-        color.0[2] as f32 / RGB_MAX_VALUE,
-    ];
+/// Grayscale images have no alpha channel, so they're treated as masks where black is empty
+fn is_empty_pixel_at(image: &image::DynamicImage, x: u32, y: u32) -> bool {
+    match image {
+        image::DynamicImage::ImageLuma8(luma) => luma.get_pixel(x, y).0[0] == 0,
+        image::DynamicImage::ImageLuma16(luma) => luma.get_pixel(x, y).0[0] == 0,
+        _ => is_empty_pixel(image.get_pixel(x, y)),
+    }
+}
 
-    match face {
-        // Top face (z = 1)
-        Face::Up => {
-            vec![
-                Vertex { position: [pos[0], pos[1], height], normal: [0.0, 0.0, 1.0], color },
-                Vertex { position: [pos[0] + 1.0, pos[1], height], normal: [0.0, 0.0, 1.0], color },
-                Vertex { position: [pos[0], pos[1] + 1.0, height], normal: [0.0, 0.0, 1.0], color },
-
-                Vertex { position: [pos[0], pos[1] + 1.0, height], normal: [0.0, 0.0, 1.0], color },
-                Vertex { position: [pos[0] + 1.0, pos[1], height], normal: [0.0, 0.0, 1.0], color },
-                Vertex { position: [pos[0] + 1.0, pos[1] + 1.0, height], normal: [0.0, 0.0, 1.0], color },
-            ]
-        }
-        // Bottom face (z = 0)
-        Face::Down => {
-            vec![
-                Vertex { position: [pos[0], pos[1], 0.0], normal: [0.0, 0.0, -1.0], color },
-                Vertex { position: [pos[0], pos[1] + 1.0, 0.0], normal: [0.0, 0.0, -1.0], color },
-                Vertex { position: [pos[0] + 1.0, pos[1], 0.0], normal: [0.0, 0.0, -1.0], color },
+/// Returns true if the pixel isn't empty, pixels outside of the image are filled only with sealed borders
+fn is_filled(image: &image::DynamicImage, x: i64, y: i64, border: BorderMode) -> bool {
+    let (image_width, image_height) = image.dimensions();
 
-                Vertex { position: [pos[0], pos[1] + 1.0, 0.0], normal: [0.0, 0.0, -1.0], color },
-                Vertex { position: [pos[0] + 1.0, pos[1] + 1.0, 0.0], normal: [0.0, 0.0, -1.0], color },
-                Vertex { position: [pos[0] + 1.0, pos[1], 0.0], normal: [0.0, 0.0, -1.0], color },
-            ]
-        }
-        Face::Forward => {
-            vec![
-                Vertex { position: [pos[0], pos[1], 0.0], normal: [0.0, -1.0, 0.0], color },
-                Vertex { position: [pos[0] + 1.0, pos[1], 0.0], normal: [0.0, -1.0, 0.0], color },
-                Vertex { position: [pos[0], pos[1], height], normal: [0.0, -1.0, 0.0], color },
+    if !(0..image_width as i64).contains(&x) || !(0..image_height as i64).contains(&y) {
+        return border == BorderMode::SealedFaces;
+    }
 
-                Vertex { position: [pos[0], pos[1], height], normal: [0.0, -1.0, 0.0], color },
-                Vertex { position: [pos[0] + 1.0, pos[1], 0.0], normal: [0.0, -1.0, 0.0], color },
-                Vertex { position: [pos[0] + 1.0, pos[1], height], normal: [0.0, -1.0, 0.0], color },
-            ]
-        }
-        Face::Back => {
-            vec![
-                Vertex { position: [pos[0], pos[1] + 1.0, 0.0], normal: [0.0, 1.0, 0.0], color },
-                Vertex { position: [pos[0], pos[1] + 1.0, height], normal: [0.0, 1.0, 0.0], color },
-                Vertex { position: [pos[0] + 1.0, pos[1] + 1.0, 0.0], normal: [0.0, 1.0, 0.0], color },
+    !is_empty_pixel_at(image, x as u32, y as u32)
+}
 
-                Vertex { position: [pos[0] + 1.0, pos[1] + 1.0, 0.0], normal: [0.0, 1.0, 0.0], color },
-                Vertex { position: [pos[0], pos[1] + 1.0, height], normal: [0.0, 1.0, 0.0], color },
-                Vertex { position: [pos[0] + 1.0, pos[1] + 1.0, height], normal: [0.0, 1.0, 0.0], color },
-            ]
-        }
-        Face::Left => {
-            vec![
-                Vertex { position: [pos[0], pos[1], 0.0], normal: [-1.0, 0.0, 0.0], color },
-                Vertex { position: [pos[0], pos[1], height], normal: [-1.0, 0.0, 0.0], color },
-                Vertex { position: [pos[0], pos[1] + 1.0, 0.0], normal: [-1.0, 0.0, 0.0], color },
+/// Converts a pixel into the normalized RGB color of a vertex
+#[inline]
+fn pixel_color(pixel: image::Rgba<u8>) -> [f32; 3] {
+    [
+        pixel.0[0] as f32 / RGB_MAX_VALUE,
+        pixel.0[1] as f32 / RGB_MAX_VALUE,
+        pixel.0[2] as f32 / RGB_MAX_VALUE,
+    ]
+}
 
-                Vertex { position: [pos[0], pos[1] + 1.0, 0.0], normal: [-1.0, 0.0, 0.0], color },
-                Vertex { position: [pos[0], pos[1], height], normal: [-1.0, 0.0, 0.0], color },
-                Vertex { position: [pos[0], pos[1] + 1.0, height], normal: [-1.0, 0.0, 0.0], color },
-            ]
-        }
-        Face::Right => {
-            vec![
-                Vertex { position: [pos[0] + 1.0, pos[1], 0.0], normal: [1.0, 0.0, 0.0], color },
-                Vertex { position: [pos[0] + 1.0, pos[1] + 1.0, 0.0], normal: [1.0, 0.0, 0.0], color },
-                Vertex { position: [pos[0] + 1.0, pos[1], height], normal: [1.0, 0.0, 0.0], color },
+/// Relative luminance of the sRGB values of a pixel with the Rec. 709 weights, from 0 to 255
+#[inline]
+fn pixel_luminance(pixel: image::Rgba<u8>) -> f32 {
+    0.2126 * pixel.0[0] as f32 + 0.7152 * pixel.0[1] as f32 + 0.0722 * pixel.0[2] as f32
+}
 
-                Vertex { position: [pos[0] + 1.0, pos[1] + 1.0, 0.0], normal: [1.0, 0.0, 0.0], color },
-                Vertex { position: [pos[0] + 1.0, pos[1] + 1.0, height], normal: [1.0, 0.0, 0.0], color },
-                Vertex { position: [pos[0] + 1.0, pos[1], height], normal: [1.0, 0.0, 0.0], color },
-            ]
-        }
+/// Creates a box spanning the bounds of the vertices on X and Y, from the floor down by the thickness of the base
+/// The top of the box is kept under the voxels, so it's still closed on its own.
+fn create_base(vertices: &[Vertex], base: Base, floor: f32) -> Vec<Vertex> {
+    if vertices.is_empty() || base.thickness <= 0.0 {
+        return Vec::new();
+    }
+
+    let (min, max) = bounding_coords(vertices);
+    let color = base.color.0.map(|channel| channel as f32 / RGB_MAX_VALUE);
+
+    create_box(
+        [min[0], min[1], floor - base.thickness],
+        [max[0], max[1], floor],
+        color,
+    )
+}
+
+/// Creates the six faces of an axis aligned box
+fn create_box(min: [f32; 3], max: [f32; 3], color: [f32; 3]) -> Vec<Vertex> {
+    let (bottom, top) = (min[2], max[2]);
+    let corner = |x: usize, y: usize, z: f32| [[min[0], max[0]][x], [min[1], max[1]][y], z];
+
+    let mut vertices = Vec::with_capacity(6 * VERTICES_PER_FACE);
+    for (z, normal) in [(top, [0.0, 0.0, 1.0]), (bottom, [0.0, 0.0, -1.0])] {
+        vertices.extend(create_quad(
+            [
+                corner(0, 0, z),
+                corner(1, 0, z),
+                corner(0, 1, z),
+                corner(1, 1, z),
+            ],
+            normal,
+            color,
+        ));
+    }
+    for (x, normal) in [(0, [-1.0, 0.0, 0.0]), (1, [1.0, 0.0, 0.0])] {
+        vertices.extend(create_quad(
+            [
+                corner(x, 0, bottom),
+                corner(x, 1, bottom),
+                corner(x, 0, top),
+                corner(x, 1, top),
+            ],
+            normal,
+            color,
+        ));
+    }
+    for (y, normal) in [(0, [0.0, -1.0, 0.0]), (1, [0.0, 1.0, 0.0])] {
+        vertices.extend(create_quad(
+            [
+                corner(0, y, bottom),
+                corner(1, y, bottom),
+                corner(0, y, top),
+                corner(1, y, top),
+            ],
+            normal,
+            color,
+        ));
+    }
+
+    vertices
+}
+
+/// Creates the two triangles for an arbitrary quad, where a and d are opposite corners
+/// The triangles are wound counter-clockwise when looking against the normal
+fn create_quad(corners: [[f32; 3]; 4], normal: [f32; 3], color: [f32; 3]) -> [Vertex; 6] {
+    let [a, mut b, mut c, d] = corners;
+
+    let ab = Vector3::from(b) - Vector3::from(a);
+    let ac = Vector3::from(c) - Vector3::from(a);
+    if ab.cross(&ac).dot(&Vector3::from(normal)) < 0.0 {
+        std::mem::swap(&mut b, &mut c);
     }
+
+    [a, b, c, c, b, d].map(|position| Vertex {
+        position,
+        normal,
+        color,
+        alpha: 1.0,
+        uv: [0.0; 2],
+        tangent: [0.0; 4],
+    })
+}
+
+/// Creates the six faces of a voxel, including the ones hidden by its neighbors
+fn create_pixel_verticies(pos: [f32; 2], color: [f32; 3], floor: f32, ceiling: f32) -> Vec<Vertex> {
+    FACES
+        .iter()
+        .flat_map(|face| create_pixel_verticies_face(pos, color, floor, ceiling, face))
+        .collect()
+}
+
+/// Creates the two triangles for a face of a voxel
+/// For the quad corners a, b, c, d the triangles are always (a, b, c) and (c, b, d)
+fn create_pixel_verticies_face(
+    pos: [f32; 2],
+    color: [f32; 3],
+    floor: f32,
+    ceiling: f32,
+    face: &Face,
+) -> Vec<Vertex> {
+    let [x, y] = pos;
+    let geometry = face.geometry();
+
+    // The height picks the floor or the ceiling instead of scaling, so tops land exactly on the ceiling.
+    let corners = geometry
+        .corners
+        .map(|[dx, dy, dz]| [x + dx, y + dy, if dz > 0.0 { ceiling } else { floor }]);
+
+    create_quad(corners, geometry.normal, color).to_vec()
 }
 
 /// Calculate bounding coordinates of a list of vertices, used for the clipping distance of the model
 fn bounding_coords(points: &[Vertex]) -> ([f32; 3], [f32; 3]) {
-    let mut min = [f32::MAX, f32::MAX, f32::MAX];
-    let mut max = [f32::MIN, f32::MIN, f32::MIN];
+    component_bounds(points, |vertex| vertex.position)
+}
+
+/// Per component minimum and maximum of the attribute picked by `selector`
+fn component_bounds<const N: usize>(
+    vertices: &[Vertex],
+    selector: impl Fn(&Vertex) -> [f32; N],
+) -> ([f32; N], [f32; N]) {
+    let mut min = [f32::MAX; N];
+    let mut max = [f32::MIN; N];
 
-    for point in points {
-        let p = point.position;
-        for i in 0..3 {
+    for vertex in vertices {
+        let p = selector(vertex);
+        for i in 0..N {
             min[i] = f32::min(min[i], p[i]);
             max[i] = f32::max(max[i], p[i]);
         }
@@ -396,12 +3989,59 @@ fn bounding_coords(points: &[Vertex]) -> ([f32; 3], [f32; 3]) {
     (min, max)
 }
 
+/// Rounds up to the next multiple of four bytes, glTF aligns chunks, buffer views and vertex elements to it
+/// Saturates instead of wrapping, so an oversized length still fails the `u32` check of the GLB header
+#[inline]
+pub(crate) fn align4(length: usize) -> usize {
+    length.saturating_add(PADDING - 1) & !(PADDING - 1)
+}
+
+/// Length of a buffer view of `count` elements once padded to four bytes
+/// The arithmetic saturates instead of wrapping, so an oversized buffer fails the `u32` check of the GLB header
+#[inline]
+fn padded_view_length(count: usize, element_size: usize) -> usize {
+    align4(count.saturating_mul(element_size))
+}
+
+/// Exact size of everything `create_buffer` writes for a mesh, vertex attributes and indices included
+fn calculate_buffer_length(
+    vertex_count: usize,
+    encoding: &VertexEncoding,
+    options: &VoxelifyOptions,
+) -> usize {
+    calculate_vertex_buffer_length(vertex_count, encoding, options.layout)
+        .saturating_add(calculate_index_buffer_length(vertex_count, options))
+}
+
+fn calculate_vertex_buffer_length(
+    vertex_count: usize,
+    encoding: &VertexEncoding,
+    layout: BufferLayout,
+) -> usize {
+    match layout {
+        BufferLayout::Interleaved => padded_view_length(vertex_count, encoding.stride()),
+        BufferLayout::Separate => encoding
+            .attributes
+            .iter()
+            .map(|&attribute| padded_view_length(vertex_count, encoding.element_size(attribute)))
+            .fold(0, usize::saturating_add),
+    }
+}
+
+/// The wireframe is drawn with indices into the faces, points have no faces to outline
 #[inline]
-fn align_to_multiple_of_four(number: &mut usize) {
-    *number = (*number + 3) & !3;
+fn has_line_indices(options: &VoxelifyOptions) -> bool {
+    options.wireframe && !options.points
 }
 
 #[inline]
-fn calculate_buffer_length(vertices: &[Vertex]) -> usize {
-    std::mem::size_of_val(vertices)
+fn calculate_index_buffer_length(vertex_count: usize, options: &VoxelifyOptions) -> usize {
+    if has_line_indices(options) {
+        padded_view_length(
+            vertex_count / VERTICES_PER_FACE * LINE_INDICES_PER_FACE,
+            std::mem::size_of::<u32>(),
+        )
+    } else {
+        0
+    }
 }
@@ -1,64 +1,1778 @@
-use clap::{Parser, ValueEnum};
-use image::io::Reader as ImageReader;
-use std::path::PathBuf;
-use voxelify::{create_glb, create_gltf_root, image_to_vertices};
+use clap::parser::ValueSource;
+use clap::{ArgMatches, Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use gltf::json;
+use image::imageops::FilterType;
+use image::{GenericImageView, ImageFormat, Rgb};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use voxelify::{
+    append_to_glb, bake_texture, bin_path, bounding_box_mesh, check_watertight, collision_voxels,
+    compose_channels, create_glb_multi, create_gltf_root_multi, downscale_image,
+    fit_triangle_budget, glb_to_vec, gltf_to_json, image_to_vertices_with_depth_and_options,
+    image_to_vertices_with_metadata, image_to_vertices_with_options, merge_meshes,
+    remove_degenerate_triangles, render_thumbnail, split_components, split_into_tiles, tile_image,
+    upscale_image, validate, write_raw_buffer, Axis, Background, Base, BorderMode, BufferLayout,
+    ColorAdjustment, ColorHeights, ColorJitter, ColorMapping, ColorTarget, DecodeLimits,
+    DecodeOptions, Handedness, HeightNoise, ImageInfo, LabelColors, MeshEntry, ModelScale,
+    NodeTransform, PixelOrigin, Provenance, Vertex, VoxelMetadata, VoxelifyOptions,
+};
 
 #[derive(Debug, Default, PartialEq, Clone, ValueEnum)]
 enum Format {
     #[default]
     Glb,
     Gltf,
+    /// Only the interleaved vertex attributes, with their layout in a `.json` file next to them
+    Raw,
+    /// USDZ package for AR Quick Look
+    #[cfg(feature = "usdz")]
+    Usdz,
+}
+
+impl Format {
+    fn extension(&self) -> &'static str {
+        match self {
+            Format::Glb => "glb",
+            Format::Gltf => "gltf",
+            // Kept apart from the BIN of the GLTF output.
+            Format::Raw => "raw.bin",
+            #[cfg(feature = "usdz")]
+            Format::Usdz => "usdz",
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Clone, ValueEnum)]
+enum Primitive {
+    #[default]
+    Triangles,
+    /// A single colored point per voxel, much smaller and faster for huge images
+    Points,
+    /// A single smooth surface through the tops of the voxels, for heightmaps
+    Terrain,
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+enum Collision {
+    /// The box bounding the model
+    Box,
+    /// Coarse voxels following the silhouette, see `--collision-factor`
+    Voxels,
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+enum MirrorAxis {
+    X,
+    Y,
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+enum ExtrudeAxis {
+    X,
+    Y,
+    Z,
+}
+
+/// Clockwise rotation of the image, in degrees
+#[derive(Debug, Clone, ValueEnum)]
+enum Rotation {
+    #[value(name = "90")]
+    Quarter,
+    #[value(name = "180")]
+    Half,
+    #[value(name = "270")]
+    ThreeQuarters,
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+enum Border {
+    /// The voxels on the edge of the image get their outer faces
+    Open,
+    /// No faces are created on the edge of the image, for tiles that are stitched together
+    Sealed,
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+enum Origin {
+    /// The voxel of a pixel starts at its coordinates
+    Corner,
+    /// The voxel of a pixel is centered on its coordinates
+    Center,
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+enum Target {
+    /// Linear colors as required by glTF
+    GltfLinear,
+    /// The colors of the image as they are, for viewers that don't convert the vertex colors
+    RawSrgb,
+    /// Linear colors for glTFast
+    Unity,
+    /// Linear colors with an unlit material, so the colors of the image are shown exactly
+    Threejs,
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+enum Hand {
+    /// The coordinate system of glTF
+    Right,
+    /// Reflects the model across the X axis, for engines like Unity
+    Left,
+}
+
+#[derive(Debug, Copy, Clone, ValueEnum)]
+enum InputFormat {
+    Png,
+    Jpeg,
+    Gif,
+    Webp,
+    Bmp,
+    Ico,
+    Tiff,
+    Tga,
+    Dds,
+    Hdr,
+    Exr,
+    Pnm,
+    Qoi,
+    Farbfeld,
+}
+
+impl InputFormat {
+    fn image_format(self) -> ImageFormat {
+        match self {
+            InputFormat::Png => ImageFormat::Png,
+            InputFormat::Jpeg => ImageFormat::Jpeg,
+            InputFormat::Gif => ImageFormat::Gif,
+            InputFormat::Webp => ImageFormat::WebP,
+            InputFormat::Bmp => ImageFormat::Bmp,
+            InputFormat::Ico => ImageFormat::Ico,
+            InputFormat::Tiff => ImageFormat::Tiff,
+            InputFormat::Tga => ImageFormat::Tga,
+            InputFormat::Dds => ImageFormat::Dds,
+            InputFormat::Hdr => ImageFormat::Hdr,
+            InputFormat::Exr => ImageFormat::OpenExr,
+            InputFormat::Pnm => ImageFormat::Pnm,
+            InputFormat::Qoi => ImageFormat::Qoi,
+            InputFormat::Farbfeld => ImageFormat::Farbfeld,
+        }
+    }
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+enum Filter {
+    /// Keeps the pixels crisp, for pixel art
+    Nearest,
+    Triangle,
+    CatmullRom,
+    Gaussian,
+    /// Sharpest of the blending filters, for photos
+    Lanczos3,
+}
+
+impl Filter {
+    fn filter_type(&self) -> FilterType {
+        match self {
+            Filter::Nearest => FilterType::Nearest,
+            Filter::Triangle => FilterType::Triangle,
+            Filter::CatmullRom => FilterType::CatmullRom,
+            Filter::Gaussian => FilterType::Gaussian,
+            Filter::Lanczos3 => FilterType::Lanczos3,
+        }
+    }
 }
 
 #[derive(Debug, Parser)]
-struct Args {
-    #[arg(short, long)]
-    input: PathBuf,
-    #[arg(short, long)]
-    format: Format,
-    #[arg(short, long)]
-    output: PathBuf,
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Create the model of the image
+    Convert(Box<ConvertArgs>),
+    /// Print the size, the alpha and the colors of the image
+    Info(InputArgs),
+    /// Print the projected size of the model instead of creating it
+    Estimate(Box<EstimateArgs>),
+}
+
+/// Where the image comes from and how it's oriented and resized before meshing
+#[derive(Debug, Args)]
+struct InputArgs {
+    #[arg(
+        short,
+        long,
+        required_unless_present = "channel_r",
+        conflicts_with = "channel_r"
+    )]
+    input: Option<PathBuf>,
+    /// Grayscale image giving the red of every voxel, instead of the input, along with `--channel-g` and `--channel-b`
+    #[arg(long, requires_all = ["channel_g", "channel_b"])]
+    channel_r: Option<PathBuf>,
+    /// Grayscale image of the same size as `--channel-r` giving the green of every voxel
+    #[arg(long, requires = "channel_r")]
+    channel_g: Option<PathBuf>,
+    /// Grayscale image of the same size as `--channel-r` giving the blue of every voxel
+    #[arg(long, requires = "channel_r")]
+    channel_b: Option<PathBuf>,
+    /// Decode the input as this format instead of guessing it from its content and extension
+    #[arg(long)]
+    input_format: Option<InputFormat>,
+    /// Convert the colors of the input from its embedded ICC profile to sRGB, so they match what editors show
+    #[cfg(feature = "icc")]
+    #[arg(long)]
+    apply_icc: bool,
+    /// Refuse the images with more pixels than this instead of decoding them
+    #[arg(long)]
+    max_pixels: Option<u64>,
+    /// Size in pixels of the longer side of an SVG input, by default the size the SVG declares
+    #[cfg(feature = "svg")]
+    #[arg(long)]
+    raster_size: Option<u32>,
+    /// Swap the X and Y of the image, before the flips
+    #[arg(long)]
+    transpose: bool,
     #[arg(short, long)]
     vertical_flip: bool,
-    #[arg(short, long)]
+    #[arg(short = 'H', long)]
     horizontal_flip: bool,
-    #[arg(short, long, default_value_t = 2.0)]
-    z_height: f32,
+    /// Rotate the image clockwise by this many degrees, after the transpose and the flips
+    #[arg(long)]
+    rotate: Option<Rotation>,
+    /// Enlarge the image by this integer factor before meshing, so thin features are several voxels thick
+    #[arg(long)]
+    upscale: Option<u32>,
+    /// Filter used wherever the image or the depth map is resized
+    /// The levels of detail always average the filled pixels of the image, the filter only resizes their depth map.
+    #[arg(long, value_enum, default_value_t = Filter::Nearest)]
+    filter: Filter,
+    /// Repeat the image on a grid of copies before meshing, as `COLUMNSxROWS`
+    #[arg(long, value_parser = parse_size)]
+    tile: Option<(u32, u32)>,
+}
+
+/// How the image is meshed, shared by the commands that mesh it
+#[derive(Debug, Args)]
+struct MeshArgs {
+    /// JSON file with the meshing options, the flags given on the command line take precedence over it
+    #[arg(long)]
+    config: Option<PathBuf>,
+    /// Grayscale image of the same size as the input giving the height of every voxel, white is `--z-ceiling`
+    #[arg(long, visible_alias = "channel-height", conflicts_with = "bevel")]
+    depth_map: Option<PathBuf>,
+    /// Height of the voxels, same as `--z-ceiling` with the floor at zero
+    #[arg(short, long, conflicts_with_all = ["z_floor", "z_ceiling"])]
+    z_height: Option<f32>,
+    /// Z coordinate of the bottom of the voxels
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    z_floor: f32,
+    /// Z coordinate of the top of the voxels
+    #[arg(long, default_value_t = 2.0, allow_negative_numbers = true)]
+    z_ceiling: f32,
+    /// Store each vertex attribute in its own buffer view instead of interleaving them
+    #[arg(long)]
+    separate_views: bool,
+    /// Record the source file name, its dimensions and the options used in the glTF extras
+    #[arg(long)]
+    provenance: bool,
+    /// Copyright notice stored in the glTF asset
+    #[arg(long)]
+    copyright: Option<String>,
+    /// Oldest glTF version the model needs, for viewers that refuse assets without one
+    #[arg(long, value_parser = ["2.0"])]
+    min_version: Option<String>,
+    /// Export the edges of the faces as lines, useful for inspecting the culling
+    #[arg(long)]
+    wireframe: bool,
+    /// What the voxels are drawn as
+    #[arg(long, value_enum, default_value_t = Primitive::Triangles, conflicts_with_all = ["wireframe", "watertight"])]
+    mode: Primitive,
+    /// Store positions as 16-bit integers with KHR_mesh_quantization to shrink the file
+    #[arg(long)]
+    quantize: bool,
+    /// Store normals as 8-bit integers with KHR_mesh_quantization, a quarter of their size as floats
+    #[arg(long)]
+    quantize_normals: bool,
+    /// Scale the whole model by this factor, one voxel is this many units wide
+    #[arg(long, conflicts_with = "fit_size")]
+    scale: Option<f32>,
+    /// Scale the whole model so its longest side is this many units long
+    #[arg(long)]
+    fit_size: Option<f32>,
+    /// Slant the exposed top edges of the voxels by this fraction of a voxel
+    #[arg(long, default_value_t = 0.0)]
+    bevel: f32,
+    /// How the outside of the image is treated when culling the side faces
+    #[arg(long, value_enum, default_value_t = Border::Open)]
+    border: Border,
+    /// Where the coordinates of a pixel are on its voxel
+    #[arg(long, value_enum, default_value_t = Origin::Corner)]
+    pixel_origin: Origin,
+    /// Handedness of the coordinate system the model is made for
+    #[arg(long, value_enum, default_value_t = Hand::Right)]
+    handedness: Hand,
+    /// Axis the voxels are extruded along, the image is rotated so its X and Y go to the next axes in turn
+    #[arg(long, value_enum, default_value_t = ExtrudeAxis::Z)]
+    extrude_axis: ExtrudeAxis,
+    /// Cut the outer corners of diagonal staircases along the silhouette for smoother edges
+    #[arg(long, conflicts_with = "bevel")]
+    smooth_edges: bool,
+    /// Give every voxel all six faces, even the ones against its neighbors, for imports that split the mesh per block
+    #[arg(long, conflicts_with_all = ["bevel", "smooth_edges", "seal_bottom", "watertight"])]
+    no_cull: bool,
+    /// Round the corners of the voxels by this fraction of half a voxel, from 0 to 1, every face then takes 50
+    /// triangles instead of 2
+    #[arg(long, conflicts_with_all = ["bevel", "smooth_edges", "no_cull", "depth_map"])]
+    rounding: Option<f32>,
+    /// Make the voxels as tall as the alpha of their pixel, so soft edges are shorter
+    #[arg(long, conflicts_with_all = ["bevel", "depth_map"])]
+    alpha_height: bool,
+    /// Height of the voxels of a color, as `RRGGBB=HEIGHT`, can be repeated for a stepped chart
+    /// The voxels of the other colors are as tall as `--z-ceiling`.
+    #[arg(long, value_parser = parse_color_height, conflicts_with_all = ["bevel", "depth_map", "alpha_height"])]
+    color_height: Vec<(Rgb<u8>, f32)>,
+    /// Largest per channel difference from a color of `--color-height` that still gets its height
+    #[arg(long, default_value_t = 0, requires = "color_height")]
+    color_height_tolerance: u8,
+    /// Merge the bottom faces into one watertight base under every connected region, for 3D printing
+    #[arg(long)]
+    seal_bottom: bool,
+    /// Duplicate the model reflected across this axis, for building symmetric models from half an image
+    #[arg(long)]
+    mirror: Option<MirrorAxis>,
+    /// Fill the holes of dithered pixel art, closing the gaps up to twice this many pixels wide
+    #[arg(long)]
+    fill_holes: Option<u32>,
+    /// Only extrude a border of this many pixels around the shape, leaving it hollow
+    #[arg(long)]
+    outline: Option<u32>,
+    /// Wind the triangles clockwise instead of counter-clockwise
+    #[arg(long)]
+    flip_winding: bool,
+    /// Point the normals into the model
+    #[arg(long)]
+    flip_normals: bool,
+    /// Check that every edge is shared by exactly two triangles before writing, for 3D printing
+    /// Implies `--seal-bottom`, the error lists the edges that aren't, like where shapes touch on a single corner
+    #[arg(long, conflicts_with = "wireframe")]
+    watertight: bool,
+    /// Added to the color channels of every voxel, between -1 and 1
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    brightness: f32,
+    /// Multiplies the distance of the color channels from middle gray
+    #[arg(long, default_value_t = 1.0)]
+    contrast: f32,
+    /// Gamma correction of the colors, values above 1 brighten the midtones
+    #[arg(long, default_value_t = 1.0)]
+    gamma: f32,
+    /// Largest random change of every color channel of a voxel, from 0 to 1
+    #[arg(long, default_value_t = 0.0)]
+    jitter_strength: f32,
+    /// Seed of the color jitter, the same seed always gives the same colors
+    #[arg(long, default_value_t = 0, requires = "jitter_strength")]
+    jitter_seed: u64,
+    /// Largest height added to the top of the voxels by smooth random noise, for terrain-like surfaces
+    #[arg(long, conflicts_with = "bevel")]
+    height_noise: Option<f32>,
+    /// Seed of the height noise, the same seed always gives the same heights
+    #[arg(long, default_value_t = 0, requires = "height_noise")]
+    noise_seed: u64,
+    /// Width of the bumps of the height noise, in voxels
+    #[arg(long, default_value_t = 8.0, requires = "height_noise")]
+    noise_scale: f32,
+    /// Snap the heights of the depth map, the color heights, the alpha heights or the noise to this many layers
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    z_layers: Option<u32>,
+    /// Add a solid slab this thick under the whole model, for display stands
+    #[arg(long)]
+    base_thickness: Option<f32>,
+    /// Color of the slab under the model, as `white`, `black` or `RRGGBB`
+    #[arg(long, value_parser = parse_color, default_value = "808080", requires = "base_thickness")]
+    base_color: Rgb<u8>,
+    /// Cull the pixels of this color, as `white`, `black` or `RRGGBB`
+    #[arg(long, value_parser = parse_color)]
+    background: Option<Rgb<u8>>,
+    /// Largest per channel difference from the background color that is still culled
+    #[arg(long, default_value_t = 0, requires = "background")]
+    bg_tolerance: u8,
+    /// Add texture coordinates projected from the image
+    #[arg(long)]
+    uvs: bool,
+    /// Add tangents for normal-mapped materials, implies `--uvs`
+    #[arg(long)]
+    tangents: bool,
+    /// Bake the colors into a texture embedded in the model, every face samples the pixel of its voxel
+    #[arg(long, conflicts_with_all = ["uvs", "tangents", "seal_bottom", "watertight", "base_thickness", "mode"])]
+    embed_texture: bool,
+    /// Sample the embedded texture at the nearest texel, for pixel art that stays crisp up close
+    #[arg(long, requires = "embed_texture")]
+    no_texture_filter: bool,
+    /// Give every voxel the same color, as `white`, `black` or `RRGGBB`, white when it's left out
+    /// The color goes into the material instead of the vertices, for exports that only care about the shape.
+    #[arg(long, value_parser = parse_color, num_args = 0..=1, default_missing_value = "white")]
+    monochrome: Option<Rgb<u8>>,
+    /// Keep the alpha of the pixels in the vertex colors for translucent voxels
+    #[arg(long)]
+    alpha: bool,
+    /// Render the faces from both sides, useful with hollow or open shapes
+    #[arg(long)]
+    double_sided: bool,
+    /// Name of the material, shared by all the meshes of the model
+    #[arg(long)]
+    material_name: Option<String>,
+    /// Give the material the average color of the model, for viewers that ignore vertex colors
+    /// Viewers that use them multiply them by this color and show the model darker.
+    #[arg(long)]
+    fallback_color: bool,
+    /// Viewer the vertex colors are written for
+    #[arg(long, value_enum, default_value_t = Target::RawSrgb)]
+    color_target: Target,
+    /// Make the voxels at least this bright glow with their own color, from 0 to 255
+    #[arg(long)]
+    emissive_threshold: Option<u8>,
+    /// Intensity of the glow of the emissive voxels
+    #[arg(long, default_value_t = 1.0, requires = "emissive_threshold")]
+    emissive_strength: f32,
+    /// Cull the pixels darker than this luminance, from 0 to 255
+    #[arg(long, default_value_t = 0)]
+    min_brightness: u8,
+    /// Stretch the colors of the filled pixels to the full range of every channel, for faded or scanned images
+    #[arg(long)]
+    normalize_colors: bool,
+    /// Downscale the image by the smallest factor that keeps the estimated triangles within this budget, the voxels
+    /// are scaled up to cover the same area. The levels of detail start from that resolution. `convert` fails when
+    /// the meshed model and its collision shape are still over the budget, `estimate` only projects the factor.
+    #[arg(long)]
+    max_triangles: Option<usize>,
+    /// Replace a color before meshing, as `RRGGBB=RRGGBB` or `RRGGBB=empty` to cull it, can be repeated
+    #[arg(long, value_parser = parse_color_mapping)]
+    map_color: Vec<(Rgb<u8>, ColorMapping)>,
+    /// File with one `RRGGBB=RRGGBB` or `RRGGBB=empty` mapping per line, lines starting with `#` are skipped
+    #[arg(long)]
+    color_map_file: Option<PathBuf>,
+    /// Snap every color to the closest color of a palette, `pico-8`, `gameboy` or a file with one `RRGGBB` per line
+    /// like the `.hex` palettes of Lospec, lines starting with `#` are skipped
+    #[arg(long)]
+    palette: Option<String>,
+    /// Give every distinct pixel value its own vivid color, for segmentation masks whose values are class indices
+    #[arg(long)]
+    label_colors: bool,
+    /// Seed of the label colors, the same seed always gives the same colors
+    #[arg(long, default_value_t = 0, requires = "label_colors")]
+    label_seed: u64,
+    /// Mesh on at most this many threads, 0 uses all the cores, ignored without the `parallel` feature
+    #[arg(long, default_value_t = 0)]
+    threads: usize,
+}
+
+#[derive(Debug, Args)]
+struct ConvertArgs {
+    #[command(flatten)]
+    input: InputArgs,
+    #[command(flatten)]
+    mesh: MeshArgs,
+    /// Format of the written model, can be repeated to write several from the same meshes
+    /// With several formats the files are named after the output with the extension of their format.
+    /// GLTF writes the JSON with the BIN next to it, named after the output with a `.bin` extension.
+    #[arg(short, long, required = true)]
+    format: Vec<Format>,
+    /// Path of the written model, `-` writes it to stdout
+    /// A directory, or a path ending with a separator, gets the model named after the input with the extension of
+    /// the first format.
     #[arg(short, long)]
+    output: PathBuf,
+    /// Overwrite the files that already exist, by default nothing is written over
+    #[arg(long)]
+    force: bool,
+    /// Indent the JSON of `-f gltf` for reading and diffing, by default it's compact
+    #[arg(long)]
+    pretty: bool,
+    /// Write the meshing options of this run as JSON, to repeat it with `--config`
+    #[arg(long)]
+    save_config: Option<PathBuf>,
+    /// URI of the BIN referenced by a GLTF output, by default the output with a `.bin` extension
+    /// A relative URI is written from the directory of the GLTF and can't leave it, an absolute one is only referenced.
+    #[arg(short, long, value_parser = parse_uri, conflicts_with = "lods")]
     uri: Option<String>,
+    /// Check the generated glTF for problems before writing it
+    #[arg(long)]
+    validate: bool,
+    /// Remove the triangles with two corners at the same position, which some validators and physics engines reject
+    #[arg(long, conflicts_with_all = ["wireframe", "emissive_threshold", "mode"])]
+    remove_degenerate: bool,
+    /// Also write the grid position, the color and the height of every voxel to this JSON file
+    /// With levels of detail or `--max-triangles` the positions are the ones of the finest level that's written.
+    #[arg(long, conflicts_with_all = ["sprites", "split_components"])]
+    metadata: Option<PathBuf>,
+    /// Also render an isometric preview of the model to this PNG
+    #[arg(long, conflicts_with = "mode")]
+    thumbnail: Option<PathBuf>,
+    /// Width and height of the preview in pixels
+    #[arg(long, default_value_t = 256, requires = "thumbnail")]
+    thumbnail_size: u32,
+    /// Don't print the summary once the model is written
+    #[arg(short, long, conflicts_with = "json")]
+    quiet: bool,
+    /// Print the summary as JSON, to stdout unless the model is written there
+    #[arg(long)]
+    json: bool,
+    /// Split the image into sprites of this size, as `WIDTHxHEIGHT`, each packed as its own node
+    #[arg(long, value_parser = parse_size, conflicts_with_all = ["embed_texture", "max_triangles"])]
+    sprites: Option<(u32, u32)>,
+    /// Space between the sprites laid out on the grid, in voxels
+    #[arg(long, default_value_t = 1.0, requires = "sprites")]
+    sprite_gap: f32,
+    /// Also write this many coarser levels of detail, each at half the resolution of the previous one
+    /// The files get a `_lod` suffix with their level, `model_lod0.glb` being the full resolution
+    #[arg(long, default_value_t = 0, conflicts_with_all = ["sprites", "split_components"])]
+    lods: u32,
+    /// Also add a cheap collision shape for game engines, as a node named `collision`
+    #[arg(long, value_enum, conflicts_with_all = ["sprites", "mode"])]
+    collision: Option<Collision>,
+    /// Width in pixels of the voxels of the `voxels` collision shape
+    #[arg(long, default_value_t = 4, requires = "collision")]
+    collision_factor: u32,
+    /// Split the model into square tiles with at most this many vertices each, written as files of their own
+    /// The files get a suffix with the column and the row of their tile, `model_0_0.glb` being the first.
+    #[arg(long, conflicts_with_all = ["sprites", "split_components", "collision"])]
+    max_vertices_per_file: Option<usize>,
+    /// Add the meshes as new nodes to the scene of this voxelify GLB, the glTF outputs keep what's already in it
+    /// It may be the output itself, which is then overwritten without `--force` so it grows with every run.
+    #[arg(long, conflicts_with_all = ["lods", "max_vertices_per_file"])]
+    append: Option<PathBuf>,
+    /// Pack every connected shape of the image as its own node
+    #[arg(long, conflicts_with_all = ["sprites", "embed_texture", "max_triangles"])]
+    split_components: bool,
+    /// Drop the connected shapes with fewer voxels than this, like stray pixels
+    #[arg(long, default_value_t = 1, requires = "split_components")]
+    min_component_size: usize,
+}
+
+#[derive(Debug, Args)]
+struct EstimateArgs {
+    #[command(flatten)]
+    input: InputArgs,
+    #[command(flatten)]
+    mesh: MeshArgs,
 }
 
 fn main() {
-    let args = Args::parse();
+    let matches = Cli::command().get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|error| error.exit());
+    // The flags of the subcommand tell which options were given on the command line.
+    let (_, matches) = matches.subcommand().expect("the subcommand is required");
+
+    #[cfg(feature = "logging")]
+    logger::init();
+
+    match &cli.command {
+        Command::Convert(args) => convert(args, matches),
+        Command::Info(input) => {
+            let info = ImageInfo::inspect(&load_input(input));
+            println!("size: {}x{}", info.width, info.height);
+            println!("alpha channel: {}", info.has_alpha_channel);
+            println!("contains alpha: {}", info.contains_alpha);
+            println!("grayscale: {}", info.grayscale);
+            println!("distinct colors: {}", info.distinct_colors);
+        }
+        Command::Estimate(args) => {
+            let img = load_input(&args.input);
+            let options = mesh_options(&args.input, &args.mesh, &img, matches);
+            #[cfg(feature = "parallel")]
+            build_thread_pool(&options);
+            let factor = triangle_budget(&args.mesh, &img, &options);
+            let estimate = voxelify::estimate(&downscaled(&img, factor), &options);
+            println!("voxels: {}", estimate.voxels);
+            println!("faces: {}", estimate.faces);
+            println!("triangles: {}", estimate.triangles);
+            println!("vertices: {}", estimate.vertices);
+            println!("bytes: {}", estimate.bytes);
+        }
+    }
+}
+
+/// Meshes the image and writes the model with its levels of detail
+fn convert(args: &ConvertArgs, matches: &ArgMatches) {
+    let start = std::time::Instant::now();
+    let img = load_input(&args.input);
+    // The depth map is transformed along with the image so they stay registered, its gray levels are heights
+    // rather than colors so they're never converted.
+    let depth = args.mesh.depth_map.as_ref().map(|path| {
+        load_oriented(
+            &args.input,
+            path,
+            DecodeOptions {
+                limits: decode_limits(&args.input),
+                ..Default::default()
+            },
+        )
+    });
+
+    let options = mesh_options(&args.input, &args.mesh, &img, matches);
+    #[cfg(feature = "parallel")]
+    build_thread_pool(&options);
+    // The flags conflict already, a configuration file can still turn these on.
+    if args.remove_degenerate && (options.wireframe || options.emissive_threshold.is_some()) {
+        Cli::command()
+            .error(
+                clap::error::ErrorKind::ArgumentConflict,
+                "the degenerate triangles can't be removed from a wireframe or from glowing faces",
+            )
+            .exit();
+    }
+    if let Some(path) = &args.save_config {
+        check_overwrite(path, args.force);
+        let config =
+            serde_json::to_string_pretty(&options).or_exit("Failed to serialize the options");
+        std::fs::write(path, config).or_exit("Failed to write the config file");
+    }
+
+    let budget = meshed_triangle_budget(args, &img, depth.as_ref(), &options);
+    let output = output_path(
+        args.output.clone(),
+        input_path(&args.input),
+        &args.format[0],
+    );
+    if output.as_os_str() == "-" {
+        let error = if args.lods > 0 {
+            Some("the levels of detail can't be written to stdout")
+        } else if args.max_vertices_per_file.is_some() {
+            Some("the tiles can't be written to stdout")
+        } else if args.format.iter().any(|format| *format != Format::Glb) {
+            Some("only GLB can be written to stdout")
+        } else {
+            None
+        };
+        if let Some(error) = error {
+            Cli::command()
+                .error(clap::error::ErrorKind::ArgumentConflict, error)
+                .exit();
+        }
+    }
+
+    for level in 0..=args.lods {
+        // Every level halves the resolution of the previous one, its voxels are scaled up to cover the same area.
+        let factor = budget << level;
+        let img = downscaled(&img, factor);
+        let depth = depth
+            .as_ref()
+            .map(|depth| downscaled_depth(depth, &img, factor, &args.input));
+        let output = if args.lods > 0 {
+            lod_path(&output, level)
+        } else {
+            output.clone()
+        };
+
+        export(
+            args,
+            &img,
+            depth.as_deref(),
+            Level {
+                factor,
+                preview: level == 0,
+            },
+            &options,
+            &output,
+            start,
+        );
+    }
+}
+
+/// Caps the global rayon thread pool to the threads of the options, once for the whole run
+#[cfg(feature = "parallel")]
+fn build_thread_pool(options: &VoxelifyOptions) {
+    if options.threads > 0 {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(options.threads)
+            .build_global()
+            .or_exit(&format!(
+                "Failed to create a pool of {} threads",
+                options.threads
+            ));
+    }
+}
+
+fn decode_limits(input: &InputArgs) -> DecodeLimits {
+    DecodeLimits {
+        max_pixels: input.max_pixels,
+        ..Default::default()
+    }
+}
+
+/// Decodes the input, or composes it from the channels, then orients and resizes it
+fn load_input(input: &InputArgs) -> image::DynamicImage {
+    let limits = decode_limits(input);
+
+    // The channels are gray levels like the depth map, their profiles aren't applied either.
+    match (&input.channel_r, &input.channel_g, &input.channel_b) {
+        (Some(red), Some(green), Some(blue)) => {
+            let [red, green, blue] = [red, green, blue].map(|path| {
+                load_oriented(
+                    input,
+                    path,
+                    DecodeOptions {
+                        limits,
+                        ..Default::default()
+                    },
+                )
+            });
+            compose_channels(&red, &green, &blue).or_exit("Failed to compose the channels")
+        }
+        _ => load_oriented(
+            input,
+            input_path(input),
+            DecodeOptions {
+                limits,
+                #[cfg(feature = "icc")]
+                apply_icc: input.apply_icc,
+                format: input.input_format.map(InputFormat::image_format),
+            },
+        ),
+    }
+}
+
+/// Decodes an image and applies the orientation, the upscaling and the tiling of the input to it
+fn load_oriented(input: &InputArgs, path: &Path, options: DecodeOptions) -> image::DynamicImage {
+    let file_path = path
+        .to_str()
+        .ok_or("the path isn't valid UTF-8")
+        .or_exit(&format!("Failed to load `{}`", path.display()));
+    let mut img = load_image(input, file_path, options);
+
+    // The orientation is applied in a fixed order, transpose then the flips then the rotation.
+    if input.transpose {
+        // Rotating clockwise takes (x, y) to (height - 1 - y, x), the flip makes it (y, x).
+        img = img.rotate90().fliph();
+    }
+    if input.vertical_flip {
+        img = img.flipv();
+    }
+    if input.horizontal_flip {
+        img = img.fliph();
+    }
+    img = match input.rotate {
+        Some(Rotation::Quarter) => img.rotate90(),
+        Some(Rotation::Half) => img.rotate180(),
+        Some(Rotation::ThreeQuarters) => img.rotate270(),
+        None => img,
+    };
+    if let Some(factor) = input.upscale {
+        img = upscale_image(&img, factor, input.filter.filter_type());
+    }
+    if let Some((columns, rows)) = input.tile {
+        img = tile_image(&img, columns, rows);
+    }
+
+    img
+}
+
+/// Options given by the flags, on top of the configuration file when there's one
+fn mesh_options(
+    input: &InputArgs,
+    mesh: &MeshArgs,
+    img: &image::DynamicImage,
+    matches: &ArgMatches,
+) -> VoxelifyOptions {
+    let mut color_map: HashMap<_, _> = mesh.map_color.iter().copied().collect();
+    if let Some(path) = &mesh.color_map_file {
+        let content = std::fs::read_to_string(path).or_exit("Failed to read the color map file");
+        for line in content.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (from, to) = parse_color_mapping(line).or_exit("Invalid color map file");
+            color_map.insert(from, to);
+        }
+    }
+
+    let flags = VoxelifyOptions {
+        z_floor: mesh.z_floor,
+        z_ceiling: mesh.z_height.unwrap_or(mesh.z_ceiling),
+        bevel: mesh.bevel,
+        border: match mesh.border {
+            Border::Open => BorderMode::OpenFaces,
+            Border::Sealed => BorderMode::SealedFaces,
+        },
+        pixel_origin: match mesh.pixel_origin {
+            Origin::Corner => PixelOrigin::Corner,
+            Origin::Center => PixelOrigin::Center,
+        },
+        handedness: match mesh.handedness {
+            Hand::Right => Handedness::Right,
+            Hand::Left => Handedness::Left,
+        },
+        smooth_edges: mesh.smooth_edges,
+        full_cubes: mesh.no_cull,
+        rounding: mesh.rounding.unwrap_or_default(),
+        alpha_height: mesh.alpha_height,
+        color_heights: (!mesh.color_height.is_empty()).then(|| ColorHeights {
+            heights: mesh.color_height.clone(),
+            tolerance: mesh.color_height_tolerance,
+        }),
+        seal_bottom: mesh.seal_bottom || mesh.watertight,
+        extrude_axis: match mesh.extrude_axis {
+            ExtrudeAxis::X => Axis::X,
+            ExtrudeAxis::Y => Axis::Y,
+            ExtrudeAxis::Z => Axis::Z,
+        },
+        mirror: mesh.mirror.as_ref().map(|axis| match axis {
+            MirrorAxis::X => Axis::X,
+            MirrorAxis::Y => Axis::Y,
+        }),
+        fill_holes: mesh.fill_holes,
+        outline: mesh.outline,
+        flip_winding: mesh.flip_winding,
+        flip_normals: mesh.flip_normals,
+        color_adjustment: ColorAdjustment {
+            brightness: mesh.brightness,
+            contrast: mesh.contrast,
+            gamma: mesh.gamma,
+        },
+        color_jitter: (mesh.jitter_strength > 0.0).then_some(ColorJitter {
+            seed: mesh.jitter_seed,
+            strength: mesh.jitter_strength,
+        }),
+        height_noise: mesh.height_noise.map(|amplitude| HeightNoise {
+            seed: mesh.noise_seed,
+            amplitude,
+            scale: mesh.noise_scale,
+        }),
+        z_layers: mesh.z_layers,
+        base: mesh.base_thickness.map(|thickness| Base {
+            thickness,
+            color: mesh.base_color,
+        }),
+        background: mesh.background.map(|color| Background {
+            color,
+            tolerance: mesh.bg_tolerance,
+        }),
+        min_brightness: mesh.min_brightness,
+        normalize_colors: mesh.normalize_colors,
+        label_colors: mesh.label_colors.then_some(LabelColors {
+            seed: mesh.label_seed,
+        }),
+        color_map,
+        palette: mesh
+            .palette
+            .as_deref()
+            .map(load_palette)
+            .unwrap_or_default(),
+        layout: if mesh.separate_views {
+            BufferLayout::Separate
+        } else {
+            BufferLayout::Interleaved
+        },
+        wireframe: mesh.wireframe,
+        points: mesh.mode == Primitive::Points,
+        terrain: mesh.mode == Primitive::Terrain,
+        monochrome: mesh.monochrome,
+        vertex_alpha: mesh.alpha,
+        double_sided: mesh.double_sided,
+        material_name: mesh.material_name.clone(),
+        fallback_color: mesh.fallback_color,
+        color_target: match mesh.color_target {
+            Target::GltfLinear => ColorTarget::GltfLinear,
+            Target::RawSrgb => ColorTarget::RawSrgb,
+            Target::Unity => ColorTarget::Unity,
+            Target::Threejs => ColorTarget::ThreeJs,
+        },
+        emissive_threshold: mesh.emissive_threshold,
+        emissive_strength: mesh.emissive_strength,
+        texture_coordinates: mesh.uvs,
+        tangents: mesh.tangents,
+        embed_texture: mesh.embed_texture,
+        nearest_sampling: mesh.no_texture_filter,
+        quantize_positions: mesh.quantize,
+        quantize_normals: mesh.quantize_normals,
+        scale: mesh
+            .scale
+            .map(ModelScale::Factor)
+            .or(mesh.fit_size.map(ModelScale::Fit)),
+        provenance: mesh.provenance.then(|| {
+            let (width, height) = img.dimensions();
+            Provenance {
+                file_name: input_path(input)
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned()),
+                width,
+                height,
+            }
+        }),
+        copyright: mesh.copyright.clone(),
+        min_version: mesh.min_version.clone(),
+        threads: mesh.threads,
+    };
+    match &mesh.config {
+        Some(path) => {
+            let config = std::fs::read_to_string(path).or_exit("Failed to read the config file");
+            let config = serde_json::from_str(&config).or_exit("Invalid config file");
+            merge_config(config, flags, matches)
+        }
+        None => flags,
+    }
+}
+
+/// Factor the image is downscaled by to keep within `--max-triangles`, one without a budget
+fn triangle_budget(mesh: &MeshArgs, img: &image::DynamicImage, options: &VoxelifyOptions) -> u32 {
+    mesh.max_triangles.map_or(1, |max_triangles| {
+        fit_triangle_budget(img, options, max_triangles)
+    })
+}
+
+/// Factor the image is downscaled by to keep the written model within `--max-triangles`, one without a budget
+/// The estimate leaves out the depth steps, the bevels, the base, the sealed bottom and the collision shape, so the
+/// model is meshed at the factor it gives and stops there when it's still over the budget.
+fn meshed_triangle_budget(
+    args: &ConvertArgs,
+    img: &image::DynamicImage,
+    depth: Option<&image::DynamicImage>,
+    options: &VoxelifyOptions,
+) -> u32 {
+    let factor = triangle_budget(&args.mesh, img, options);
+    let Some(max_triangles) = args.mesh.max_triangles else {
+        return factor;
+    };
+
+    let img = downscaled(img, factor);
+    let depth = depth.map(|depth| downscaled_depth(depth, &img, factor, &args.input));
+    let vertices = voxelify(&img, depth.as_deref(), options);
+    let collision = match args.collision {
+        Some(Collision::Box) => bounding_box_mesh(&vertices).len(),
+        Some(Collision::Voxels) => collision_voxels(&img, args.collision_factor, options).len(),
+        None => 0,
+    };
+    let model = if options.points { 0 } else { vertices.len() };
+    let triangles = (model + collision) / 3;
+    if triangles > max_triangles {
+        Cli::command()
+            .error(
+                clap::error::ErrorKind::ValueValidation,
+                format!(
+                    "the model has {triangles} triangles downscaled {factor} times, over --max-triangles {max_triangles}"
+                ),
+            )
+            .exit();
+    }
+
+    factor
+}
+
+fn downscaled(img: &image::DynamicImage, factor: u32) -> Cow<'_, image::DynamicImage> {
+    if factor == 1 {
+        Cow::Borrowed(img)
+    } else {
+        Cow::Owned(downscale_image(img, factor))
+    }
+}
+
+/// Depth map resized to the downscaled image, so they stay registered
+fn downscaled_depth<'a>(
+    depth: &'a image::DynamicImage,
+    img: &image::DynamicImage,
+    factor: u32,
+    input: &InputArgs,
+) -> Cow<'a, image::DynamicImage> {
+    if factor == 1 {
+        Cow::Borrowed(depth)
+    } else {
+        Cow::Owned(depth.resize_exact(img.width(), img.height(), input.filter.filter_type()))
+    }
+}
+
+/// Resolution of one of the written models
+#[derive(Copy, Clone, Debug)]
+struct Level {
+    /// Pixels of the input covered by every voxel, in both directions
+    factor: u32,
+    /// The levels of detail would all look the same, only the finest gets a preview
+    preview: bool,
+}
+
+/// Meshes the image and writes the model, then prints the summary unless it's quiet
+fn export(
+    args: &ConvertArgs,
+    img: &image::DynamicImage,
+    depth: Option<&image::DynamicImage>,
+    level: Level,
+    options: &VoxelifyOptions,
+    output: &Path,
+    start: std::time::Instant,
+) {
+    let factor = level.factor;
+    let mut meshes = match args.sprites {
+        Some(size) => sprite_meshes(img, depth, size, args.sprite_gap, options),
+        None if args.split_components => {
+            component_meshes(img, depth, args.min_component_size, options)
+        }
+        None => {
+            let vertices = match args.metadata.as_ref().filter(|_| level.preview) {
+                Some(path) => {
+                    let (vertices, metadata) = image_to_vertices_with_metadata(img, depth, options)
+                        .or_exit("Invalid depth map");
+                    check_overwrite(path, args.force);
+                    let json: Vec<_> = metadata.iter().map(VoxelMetadata::to_json).collect();
+                    std::fs::write(path, serde_json::Value::Array(json).to_string())
+                        .or_exit("Failed to write the metadata");
+                    vertices
+                }
+                None => voxelify(img, depth, options),
+            };
+            vec![(None, [0.0; 3], vertices)]
+        }
+    };
+    let degenerate = args.remove_degenerate.then(|| {
+        meshes
+            .iter_mut()
+            .map(|(_, _, vertices)| remove_degenerate_triangles(vertices))
+            .sum::<usize>()
+    });
+    if args.mesh.watertight {
+        for (_, _, vertices) in &meshes {
+            check_watertight(vertices).or_exit("The --watertight check failed");
+        }
+    }
+    let texture = options
+        .embed_texture
+        .then(|| bake_texture(img, options).or_exit("Failed to encode the texture"));
+    let entries: Vec<_> = meshes
+        .iter()
+        .map(|(name, translation, vertices)| MeshEntry {
+            vertices,
+            name: name.clone(),
+            transform: NodeTransform {
+                // The voxels are wider along the axes of the image, which the extrusion rotates.
+                scale: options
+                    .extrude_axis
+                    .extrude([factor as f32, factor as f32, 1.0]),
+                ..NodeTransform::from_translation(*translation)
+            },
+            texture: texture.as_deref(),
+        })
+        .collect();
+
+    if let Some(path) = args.thumbnail.as_ref().filter(|_| level.preview) {
+        check_overwrite(path, args.force);
+        render_thumbnail(&merged_model(&entries), args.thumbnail_size)
+            .save(path)
+            .or_exit("Failed to write the thumbnail");
+    }
+
+    let tiles = args.max_vertices_per_file.map(|max_vertices| {
+        split_into_tiles(entries[0].vertices, options, max_vertices)
+            .or_exit("Failed to split the model into tiles")
+    });
+    // Every tile is written as a model of its own, named after its place on the grid.
+    let files: Vec<(PathBuf, Vec<MeshEntry>)> = match &tiles {
+        Some(tiles) => tiles
+            .iter()
+            .map(|tile| {
+                let entry = MeshEntry {
+                    vertices: &tile.vertices,
+                    ..entries[0].clone()
+                };
+                (tile_path(output, tile.column, tile.row), vec![entry])
+            })
+            .collect(),
+        None => vec![(output.to_path_buf(), entries)],
+    };
+
+    for (index, (output, entries)) in files.iter().enumerate() {
+        // The collision shape only goes into the glTF, next to the meshes it stands in for.
+        let collision = args.collision.as_ref().map(|collision| match collision {
+            Collision::Box => (bounding_box_mesh(&merged_model(entries)), 1),
+            Collision::Voxels => (
+                collision_voxels(img, args.collision_factor, options),
+                factor,
+            ),
+        });
+        let mut gltf_entries = entries.clone();
+        if let Some((vertices, factor)) = &collision {
+            gltf_entries.push(MeshEntry {
+                vertices,
+                name: Some("collision".to_string()),
+                transform: NodeTransform {
+                    scale: options
+                        .extrude_axis
+                        .extrude([*factor as f32, *factor as f32, 1.0]),
+                    ..Default::default()
+                },
+                texture: None,
+            });
+        }
 
-    let img = {
-        let mut img = load_image(args.input.as_path().to_str().unwrap());
+        // A GLB holds its own BIN, the URI only goes into the GLTF output.
+        let (root, glb) = match &args.append {
+            Some(path) => {
+                let bytes = std::fs::read(path).or_exit("Failed to read the GLB to append to");
+                let existing = gltf::binary::Glb::from_slice(&bytes).or_exit("Invalid GLB");
+                append_to_glb(&existing, &gltf_entries, options)
+                    .or_exit("Failed to append to the GLB")
+            }
+            None => {
+                let root = create_gltf_root_multi(&gltf_entries, None, options);
+                let glb = create_glb_multi(&root, &gltf_entries, options)
+                    .or_exit("Failed to write the GLB");
+                (root, glb)
+            }
+        };
+        if args.validate {
+            validate(&root).or_exit("The --validate check failed");
+        }
+
+        let mut formats = Vec::new();
+        for format in &args.format {
+            if !formats.contains(&format) {
+                formats.push(format);
+            }
+        }
+        let to_stdout = output.as_os_str() == "-";
+
+        // The formats only differ in how the same root and BIN are written.
+        for format in &formats {
+            let output = if formats.len() > 1 {
+                output.with_extension(format.extension())
+            } else {
+                output.to_path_buf()
+            };
 
-        if args.vertical_flip {
-            img = img.flipv();
+            let bytes = match format {
+                Format::Glb => {
+                    let bytes = glb_to_vec(&glb).or_exit("Failed to encode the GLB");
+                    if to_stdout {
+                        let mut stdout = std::io::stdout().lock();
+                        stdout
+                            .write_all(&bytes)
+                            .or_exit("Failed to write to stdout");
+                        stdout.flush().or_exit("Failed to write to stdout");
+                    } else {
+                        // The GLB that's appended to was read already, growing it in place is the point.
+                        if args.append.as_ref() != Some(&output) {
+                            check_overwrite(&output, args.force);
+                        }
+                        std::fs::write(&output, &bytes).or_exit(&write_error(&output));
+                    }
+                    bytes.len()
+                }
+                Format::Gltf => write_gltf(
+                    &root,
+                    glb.bin.as_deref().unwrap_or_default(),
+                    &output,
+                    args.uri.as_deref(),
+                    args.pretty,
+                    args.force,
+                ),
+                Format::Raw => write_raw(entries, &output, options, args.force),
+                #[cfg(feature = "usdz")]
+                Format::Usdz => write_usdz(entries, &output, options, args.force),
+            };
+
+            if args.quiet {
+                continue;
+            }
+            let vertices = entries.iter().map(|entry| entry.vertices.len()).sum();
+            let (width, height) = img.dimensions();
+            let summary = Summary {
+                width,
+                height,
+                vertices,
+                triangles: if options.points || options.wireframe {
+                    0
+                } else {
+                    vertices / 3
+                },
+                // Every face is outlined by the four lines of its quad.
+                lines: (options.wireframe && !options.points).then_some(vertices / 6 * 4),
+                degenerate: degenerate.filter(|_| index == 0),
+                bytes,
+                elapsed: start.elapsed(),
+            };
+
+            // Stdout only gets the summary when the model isn't written there.
+            if args.json && !to_stdout {
+                println!("{}", summary.to_json());
+            } else if args.json {
+                eprintln!("{}", summary.to_json());
+            } else {
+                eprintln!("{summary}");
+            }
         }
-        if args.horizontal_flip {
-            img = img.fliph();
+    }
+}
+
+/// Image the model is named after, the red channel stands for the input when the channels are given
+fn input_path(input: &InputArgs) -> &PathBuf {
+    input
+        .input
+        .as_ref()
+        .or(input.channel_r.as_ref())
+        .expect("the input or the channels are required")
+}
+
+/// Turns a failure of the conversion into an error of the command line, rather than a panic with a backtrace
+trait OrExit<T> {
+    fn or_exit(self, context: &str) -> T;
+}
+
+impl<T, E: Into<Box<dyn std::error::Error>>> OrExit<T> for Result<T, E> {
+    fn or_exit(self, context: &str) -> T {
+        self.unwrap_or_else(|error| {
+            // The messages of `VoxelifyError` leave out their source, like why the file couldn't be read.
+            let error = error.into();
+            let mut message = format!("{context}: {error}");
+            let mut source = error.source();
+            while let Some(cause) = source {
+                message.push_str(&format!(": {cause}"));
+                source = cause.source();
+            }
+            Cli::command()
+                .error(clap::error::ErrorKind::Io, message)
+                .exit()
+        })
+    }
+}
+
+/// Context of a failed write, naming the file
+fn write_error(path: &Path) -> String {
+    format!("Failed to write `{}`", path.display())
+}
+
+/// Stops before anything is written when the file exists, unless it's forced
+/// Both files of the GLTF and the raw outputs are checked before writing the first, so they're written together.
+fn check_overwrite(path: &Path, force: bool) {
+    if !force && path.exists() {
+        Cli::command()
+            .error(
+                clap::error::ErrorKind::ValueValidation,
+                format!(
+                    "`{}` already exists, pass --force to overwrite it",
+                    path.display()
+                ),
+            )
+            .exit();
+    }
+}
+
+/// Writes the vertices of all the meshes placed by their transforms, then their layout next to them
+fn write_raw(
+    entries: &[MeshEntry],
+    output: &Path,
+    options: &VoxelifyOptions,
+    force: bool,
+) -> usize {
+    // `model.raw.bin` gets its layout in `model.raw.json`.
+    let layout_path = output.with_extension("json");
+    check_overwrite(output, force);
+    check_overwrite(&layout_path, force);
+
+    let model = merged_model(entries);
+    let mut bytes = Vec::new();
+    let layout =
+        write_raw_buffer(&mut bytes, &model, options).or_exit("Failed to encode the raw buffer");
+    std::fs::write(output, &bytes).or_exit(&write_error(output));
+
+    let json = layout.to_json();
+    std::fs::write(&layout_path, &json).or_exit(&write_error(&layout_path));
+    bytes.len() + json.len()
+}
+
+/// Writes the merged meshes as a USDZ package, returns its size
+#[cfg(feature = "usdz")]
+fn write_usdz(
+    entries: &[MeshEntry],
+    output: &Path,
+    options: &VoxelifyOptions,
+    force: bool,
+) -> usize {
+    check_overwrite(output, force);
+    let mut bytes = Vec::new();
+    voxelify::write_usdz(&mut bytes, &merged_model(entries), options)
+        .or_exit("Failed to encode the USDZ");
+    std::fs::write(output, &bytes).or_exit(&write_error(output));
+
+    bytes.len()
+}
+
+/// Vertices of all the meshes placed by their transforms, as a single mesh
+fn merged_model(entries: &[MeshEntry]) -> Vec<Vertex> {
+    merge_meshes(
+        &entries
+            .iter()
+            .map(|entry| (entry.vertices.to_vec(), entry.transform))
+            .collect::<Vec<_>>(),
+    )
+}
+
+/// Writes the root as JSON with the buffer pointing to the URI, returns the size of the written files
+/// The BIN is written where the URI points when it's relative, see `bin_path`.
+fn write_gltf(
+    root: &json::Root,
+    bin: &[u8],
+    output: &Path,
+    uri: Option<&str>,
+    pretty: bool,
+    force: bool,
+) -> usize {
+    let uri = uri.map_or_else(
+        || {
+            output
+                .with_extension("bin")
+                .file_name()
+                .map_or_else(String::new, |name| name.to_string_lossy().into_owned())
+        },
+        str::to_string,
+    );
+
+    let mut root = root.clone();
+    if let Some(buffer) = root.buffers.first_mut() {
+        buffer.uri = Some(uri.clone());
+    }
+
+    let bin_path = bin_path(output, &uri);
+    check_overwrite(output, force);
+    if let Some(path) = &bin_path {
+        check_overwrite(path, force);
+    }
+
+    let json = gltf_to_json(&root, pretty).or_exit("Failed to encode the GLTF");
+    std::fs::write(output, &json).or_exit(&write_error(output));
+
+    match bin_path {
+        Some(path) => {
+            if let Some(directory) = path.parent() {
+                std::fs::create_dir_all(directory).or_exit(&write_error(directory));
+            }
+            std::fs::write(&path, bin).or_exit(&write_error(&path));
+            json.len() + bin.len()
         }
+        None => json.len(),
+    }
+}
+
+/// Path of the model to write, the directories are named after the input and created when they're missing
+fn output_path(output: PathBuf, input: &Path, format: &Format) -> PathBuf {
+    if output.as_os_str() == "-" {
+        return output;
+    }
 
-        img
+    let directory = output.is_dir()
+        || output
+            .to_string_lossy()
+            .chars()
+            .last()
+            .is_some_and(std::path::is_separator);
+    let output = if directory {
+        let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+        output.join(format!("{stem}.{}", format.extension()))
+    } else {
+        output
     };
 
-    let vertices = image_to_vertices(&img, args.z_height);
-    let root = create_gltf_root(&vertices, args.uri);
-    let glb = create_glb(&root, &vertices).unwrap();
+    if let Some(parent) = output
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        std::fs::create_dir_all(parent).or_exit("Failed to create the output directory");
+    }
+
+    output
+}
+
+/// Path of a tile of the model, `model.glb` becomes `model_2_1.glb` for the third column of the second row
+fn tile_path(output: &Path, column: u32, row: u32) -> PathBuf {
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    let mut name = format!("{stem}_{column}_{row}");
+    if let Some(extension) = output.extension() {
+        name = format!("{name}.{}", extension.to_string_lossy());
+    }
+
+    output.with_file_name(name)
+}
+
+/// Path of a level of detail, `model.glb` becomes `model_lod1.glb`
+fn lod_path(output: &Path, level: u32) -> PathBuf {
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    let mut name = format!("{stem}_lod{level}");
+    if let Some(extension) = output.extension() {
+        name = format!("{name}.{}", extension.to_string_lossy());
+    }
+
+    output.with_file_name(name)
+}
+
+/// What was written, printed once the model is saved
+struct Summary {
+    width: u32,
+    height: u32,
+    vertices: usize,
+    triangles: usize,
+    /// Lines drawn instead of the triangles, with `--wireframe`
+    lines: Option<usize>,
+    /// Triangles removed for being degenerate, when they're removed
+    degenerate: Option<usize>,
+    /// Size of the written file
+    bytes: usize,
+    elapsed: std::time::Duration,
+}
+
+impl Summary {
+    fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "width": self.width,
+            "height": self.height,
+            "vertices": self.vertices,
+            "triangles": self.triangles,
+            "lines": self.lines,
+            "degenerate_triangles_removed": self.degenerate,
+            "bytes": self.bytes,
+            "elapsed_ms": self.elapsed.as_secs_f64() * 1000.0,
+        })
+    }
+}
 
-    match args.format {
-        Format::Glb => {
-            let writer = std::fs::File::create(args.output).expect("I/O error");
-            glb.to_writer(writer).expect("glTF binary output error");
+impl std::fmt::Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}x{} image, {} vertices, ",
+            self.width, self.height, self.vertices
+        )?;
+        match self.lines {
+            Some(lines) => write!(f, "{lines} lines")?,
+            None => write!(f, "{} triangles", self.triangles)?,
         }
-        Format::Gltf => {
-            panic!("GLTF output is not implemented yet");
+        write!(f, ", {} bytes in {:.2?}", self.bytes, self.elapsed)?;
+        if let Some(degenerate) = self.degenerate {
+            write!(f, ", {degenerate} degenerate triangles removed")?;
         }
+        Ok(())
     }
 }
 
-fn load_image(file_path: &str) -> image::DynamicImage {
-    ImageReader::open(file_path).unwrap().decode().unwrap()
+/// Converts the image to vertices, with the heights from the depth map if there's one
+fn voxelify(
+    img: &image::DynamicImage,
+    depth: Option<&image::DynamicImage>,
+    options: &VoxelifyOptions,
+) -> Vec<Vertex> {
+    match depth {
+        Some(depth) => image_to_vertices_with_depth_and_options(img, depth, options)
+            .or_exit("Invalid depth map"),
+        None => image_to_vertices_with_options(img, options),
+    }
+}
+
+/// Voxelifies every non-empty sprite of a sheet, keeping the sprites on their grid with a gap between them
+fn sprite_meshes(
+    img: &image::DynamicImage,
+    depth: Option<&image::DynamicImage>,
+    (width, height): (u32, u32),
+    gap: f32,
+    options: &VoxelifyOptions,
+) -> Vec<(Option<String>, [f32; 3], Vec<Vertex>)> {
+    let (image_width, image_height) = img.dimensions();
+    let mut meshes = Vec::new();
+
+    for row in 0..image_height.div_ceil(height) {
+        for column in 0..image_width.div_ceil(width) {
+            let crop = |img: &image::DynamicImage| {
+                img.crop_imm(column * width, row * height, width, height)
+            };
+            let vertices = voxelify(&crop(img), depth.map(crop).as_ref(), options);
+            if vertices.is_empty() {
+                continue;
+            }
+
+            let translation = options.extrude_axis.extrude([
+                column as f32 * (width as f32 + gap),
+                row as f32 * (height as f32 + gap),
+                0.0,
+            ]);
+            meshes.push((
+                Some(format!("sprite_{column}_{row}")),
+                translation,
+                vertices,
+            ));
+        }
+    }
+
+    meshes
+}
+
+/// Voxelifies every connected shape of the image on its own, keeping the shapes where they are in the image
+fn component_meshes(
+    img: &image::DynamicImage,
+    depth: Option<&image::DynamicImage>,
+    min_size: usize,
+    options: &VoxelifyOptions,
+) -> Vec<(Option<String>, [f32; 3], Vec<Vertex>)> {
+    split_components(img, options, min_size)
+        .into_iter()
+        .enumerate()
+        .map(|(index, component)| {
+            let (width, height) = component.image.dimensions();
+            let depth = depth.map(|depth| depth.crop_imm(component.x, component.y, width, height));
+
+            (
+                Some(format!("component_{index}")),
+                [component.x as f32, component.y as f32, 0.0],
+                voxelify(&component.image, depth.as_ref(), options),
+            )
+        })
+        .collect()
+}
+
+/// Parses a size written as `WIDTHxHEIGHT`
+fn parse_size(value: &str) -> Result<(u32, u32), String> {
+    let error = || format!("Invalid size `{value}`, expected WIDTHxHEIGHT");
+    let (width, height) = value.split_once('x').ok_or_else(error)?;
+    let width: u32 = width.parse().map_err(|_| error())?;
+    let height: u32 = height.parse().map_err(|_| error())?;
+
+    if width == 0 || height == 0 {
+        return Err(error());
+    }
+
+    Ok((width, height))
+}
+
+/// Parses the URI of the BIN, a relative one can't go up from the directory of the GLTF
+fn parse_uri(value: &str) -> Result<String, String> {
+    let relative = bin_path(Path::new(""), value).is_some();
+    if relative && value.split(['/', '\\']).any(|segment| segment == "..") {
+        return Err(format!(
+            "Invalid URI `{value}`, a relative URI can't contain `..`"
+        ));
+    }
+
+    Ok(value.to_string())
+}
+
+/// Parses a color written as `RRGGBB`, with an optional leading `#`, or as `white` or `black`
+fn parse_color(value: &str) -> Result<Rgb<u8>, String> {
+    match value {
+        "white" => return Ok(Rgb([255, 255, 255])),
+        "black" => return Ok(Rgb([0, 0, 0])),
+        _ => {}
+    }
+
+    let hex = value.trim_start_matches('#');
+    let channel = |index: usize| {
+        hex.get(index..index + 2)
+            .and_then(|channel| u8::from_str_radix(channel, 16).ok())
+            .ok_or_else(|| format!("Invalid color `{value}`, expected RRGGBB"))
+    };
+
+    if hex.len() != 6 {
+        return Err(format!("Invalid color `{value}`, expected RRGGBB"));
+    }
+
+    Ok(Rgb([channel(0)?, channel(2)?, channel(4)?]))
+}
+
+fn parse_color_mapping(value: &str) -> Result<(Rgb<u8>, ColorMapping), String> {
+    let (from, to) = value
+        .split_once('=')
+        .ok_or_else(|| format!("Invalid color mapping `{value}`, expected FROM=TO"))?;
+
+    let to = match to.trim() {
+        "empty" => ColorMapping::Empty,
+        color => ColorMapping::Color(parse_color(color)?),
+    };
+
+    Ok((parse_color(from.trim())?, to))
+}
+
+/// Replaces the options of the configuration file that are set by the flags given on the command line
+/// The colors mapped on the command line are added to the color map of the file.
+fn merge_config(
+    config: VoxelifyOptions,
+    flags: VoxelifyOptions,
+    matches: &ArgMatches,
+) -> VoxelifyOptions {
+    let given = |ids: &[&str]| {
+        ids.iter()
+            .any(|id| matches.value_source(id) == Some(ValueSource::CommandLine))
+    };
+    let mut options = config;
+
+    macro_rules! take {
+        ($($($field:ident).+: [$($id:literal),+]),* $(,)?) => {
+            $(if given(&[$($id),+]) {
+                options.$($field).+ = flags.$($field).+;
+            })*
+        };
+    }
+
+    take! {
+        z_floor: ["z_floor"],
+        z_ceiling: ["z_ceiling", "z_height"],
+        bevel: ["bevel"],
+        border: ["border"],
+        pixel_origin: ["pixel_origin"],
+        handedness: ["handedness"],
+        extrude_axis: ["extrude_axis"],
+        smooth_edges: ["smooth_edges"],
+        full_cubes: ["no_cull"],
+        rounding: ["rounding"],
+        alpha_height: ["alpha_height"],
+        color_heights: ["color_height", "color_height_tolerance"],
+        seal_bottom: ["seal_bottom", "watertight"],
+        base: ["base_thickness", "base_color"],
+        mirror: ["mirror"],
+        fill_holes: ["fill_holes"],
+        outline: ["outline"],
+        flip_winding: ["flip_winding"],
+        flip_normals: ["flip_normals"],
+        color_adjustment.brightness: ["brightness"],
+        color_adjustment.contrast: ["contrast"],
+        color_adjustment.gamma: ["gamma"],
+        color_jitter: ["jitter_strength", "jitter_seed"],
+        height_noise: ["height_noise", "noise_seed", "noise_scale"],
+        z_layers: ["z_layers"],
+        background: ["background", "bg_tolerance"],
+        min_brightness: ["min_brightness"],
+        normalize_colors: ["normalize_colors"],
+        label_colors: ["label_colors", "label_seed"],
+        palette: ["palette"],
+        layout: ["separate_views"],
+        wireframe: ["wireframe"],
+        points: ["mode"],
+        terrain: ["mode"],
+        monochrome: ["monochrome"],
+        vertex_alpha: ["alpha"],
+        double_sided: ["double_sided"],
+        material_name: ["material_name"],
+        fallback_color: ["fallback_color"],
+        color_target: ["color_target"],
+        emissive_threshold: ["emissive_threshold"],
+        emissive_strength: ["emissive_strength"],
+        texture_coordinates: ["uvs"],
+        tangents: ["tangents"],
+        embed_texture: ["embed_texture"],
+        nearest_sampling: ["no_texture_filter"],
+        quantize_positions: ["quantize"],
+        quantize_normals: ["quantize_normals"],
+        scale: ["scale", "fit_size"],
+        copyright: ["copyright"],
+        min_version: ["min_version"],
+        threads: ["threads"],
+    }
+
+    options.color_map.extend(flags.color_map);
+    options.provenance = flags.provenance;
+    options
+}
+
+/// Colors of a preset palette, or of the palette file at this path
+fn load_palette(value: &str) -> Vec<Rgb<u8>> {
+    let hex: &[&str] = match value {
+        "pico-8" => &[
+            "000000", "1d2b53", "7e2553", "008751", "ab5236", "5f574f", "c2c3c7", "fff1e8",
+            "ff004d", "ffa300", "ffec27", "00e436", "29adff", "83769c", "ff77a8", "ffccaa",
+        ],
+        "gameboy" => &["0f380f", "306230", "8bac0f", "9bbc0f"],
+        path => {
+            let content = std::fs::read_to_string(path).or_exit("Failed to read the palette file");
+            return content
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(|line| parse_color(line).or_exit("Invalid palette file"))
+                .collect();
+        }
+    };
+
+    hex.iter()
+        .map(|color| parse_color(color).expect("the presets are valid"))
+        .collect()
+}
+
+fn parse_color_height(value: &str) -> Result<(Rgb<u8>, f32), String> {
+    let (color, height) = value
+        .split_once('=')
+        .ok_or_else(|| format!("Invalid color height `{value}`, expected RRGGBB=HEIGHT"))?;
+
+    let height = height
+        .trim()
+        .parse()
+        .map_err(|_| format!("Invalid height `{height}` in `{value}`"))?;
+
+    Ok((parse_color(color.trim())?, height))
+}
+
+// Only SVG input uses the raster size of the input.
+#[cfg_attr(not(feature = "svg"), allow(unused_variables))]
+fn load_image(input: &InputArgs, file_path: &str, options: DecodeOptions) -> image::DynamicImage {
+    #[cfg(feature = "remote")]
+    if voxelify::is_remote_url(file_path) {
+        // A wrong URL is a mistake on the command line rather than a crash, like a wrong path.
+        return voxelify::fetch_image(file_path)
+            .and_then(|bytes| voxelify::image_from_bytes_with_options(&bytes, options))
+            .or_exit(&format!("Failed to load `{file_path}`"));
+    }
+
+    let extension = Path::new(file_path)
+        .extension()
+        .map(|extension| extension.to_ascii_lowercase());
+    if extension.is_some_and(|extension| extension == "svg" || extension == "svgz") {
+        #[cfg(feature = "svg")]
+        {
+            return std::fs::read(file_path)
+                .map_err(voxelify::VoxelifyError::from)
+                .and_then(|bytes| {
+                    voxelify::rasterize_svg(&bytes, input.raster_size, options.limits)
+                })
+                .or_exit(&format!("Failed to load `{file_path}`"));
+        }
+        #[cfg(not(feature = "svg"))]
+        Cli::command()
+            .error(
+                clap::error::ErrorKind::InvalidValue,
+                "SVG input needs the `svg` feature, or rasterize it to a PNG first",
+            )
+            .exit();
+    }
+
+    voxelify::load_image_path_with_options(file_path, options)
+        .or_exit(&format!("Failed to load `{file_path}`"))
+}
+
+/// Minimal logger writing to stderr, so stdout stays free for the output
+#[cfg(feature = "logging")]
+mod logger {
+    use log::{LevelFilter, Log, Metadata, Record};
+
+    struct StderrLogger;
+
+    static LOGGER: StderrLogger = StderrLogger;
+
+    impl Log for StderrLogger {
+        fn enabled(&self, metadata: &Metadata) -> bool {
+            metadata.level() <= log::max_level()
+        }
+
+        fn log(&self, record: &Record) {
+            if self.enabled(record.metadata()) {
+                eprintln!("[{} {}] {}", record.level(), record.target(), record.args());
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    /// Installs the logger with the level taken from `RUST_LOG`, defaults to warnings only
+    pub fn init() {
+        let level = std::env::var("RUST_LOG")
+            .ok()
+            .and_then(|level| level.parse().ok())
+            .unwrap_or(LevelFilter::Warn);
+
+        if log::set_logger(&LOGGER).is_ok() {
+            log::set_max_level(level);
+        }
+    }
 }
@@ -0,0 +1,211 @@
+use image::GenericImageView;
+use std::collections::VecDeque;
+
+use crate::is_empty_pixel_at;
+
+/// Row-major grid telling which pixels of an image produce a voxel
+#[derive(Clone, Debug)]
+pub(crate) struct Mask {
+    pub width: u32,
+    pub height: u32,
+    pub filled: Vec<bool>,
+}
+
+impl Mask {
+    pub fn from_image(image: &image::DynamicImage) -> Self {
+        let (width, height) = image.dimensions();
+        let filled = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| !is_empty_pixel_at(image, x, y))
+            .collect();
+
+        Self {
+            width,
+            height,
+            filled,
+        }
+    }
+
+    #[inline]
+    pub fn index(&self, x: u32, y: u32) -> usize {
+        (y * self.width + x) as usize
+    }
+
+    /// Returns the in-bounds 4-connected neighbors of a pixel
+    pub fn neighbors(&self, x: u32, y: u32) -> impl Iterator<Item = (u32, u32)> {
+        let (width, height) = (self.width as i64, self.height as i64);
+        [(-1, 0), (1, 0), (0, -1), (0, 1)]
+            .into_iter()
+            .map(move |(dx, dy)| (x as i64 + dx, y as i64 + dy))
+            .filter(move |(nx, ny)| (0..width).contains(nx) && (0..height).contains(ny))
+            .map(|(nx, ny)| (nx as u32, ny as u32))
+    }
+
+    /// Counts the faces of the voxels when every face touching an empty pixel or the border is kept
+    pub fn visible_face_count(&self) -> usize {
+        let mut faces = 0;
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if !self.filled[self.index(x, y)] {
+                    continue;
+                }
+
+                let filled_neighbors = self
+                    .neighbors(x, y)
+                    .filter(|(nx, ny)| self.filled[self.index(*nx, *ny)])
+                    .count();
+
+                // The top and bottom faces are always visible.
+                faces += 2 + 4 - filled_neighbors;
+            }
+        }
+
+        faces
+    }
+
+    /// Groups the filled pixels into 4-connected components, in the order of their first pixel in the rows
+    pub fn components(&self) -> Vec<Vec<(u32, u32)>> {
+        let mut visited = vec![false; self.filled.len()];
+        let mut components = Vec::new();
+        let mut queue = VecDeque::new();
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let index = self.index(x, y);
+                if !self.filled[index] || visited[index] {
+                    continue;
+                }
+                visited[index] = true;
+                queue.push_back((x, y));
+
+                let mut pixels = Vec::new();
+                while let Some((x, y)) = queue.pop_front() {
+                    pixels.push((x, y));
+                    for (nx, ny) in self.neighbors(x, y) {
+                        let index = self.index(nx, ny);
+                        if self.filled[index] && !visited[index] {
+                            visited[index] = true;
+                            queue.push_back((nx, ny));
+                        }
+                    }
+                }
+                components.push(pixels);
+            }
+        }
+
+        components
+    }
+
+    /// Distance in 4-connected steps from every filled pixel to the closest empty pixel
+    /// Anything outside of the image counts as empty, so border pixels are at a distance of one
+    pub fn distance_to_empty(&self) -> Vec<u32> {
+        let mut distances = vec![u32::MAX; self.filled.len()];
+        let mut queue = VecDeque::new();
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let index = self.index(x, y);
+                let on_border = x == 0 || y == 0 || x == self.width - 1 || y == self.height - 1;
+
+                if !self.filled[index] {
+                    distances[index] = 0;
+                    queue.push_back((x, y));
+                } else if on_border {
+                    distances[index] = 1;
+                    queue.push_back((x, y));
+                }
+            }
+        }
+
+        while let Some((x, y)) = queue.pop_front() {
+            let distance = distances[self.index(x, y)];
+            for (nx, ny) in self.neighbors(x, y) {
+                let index = self.index(nx, ny);
+                if distances[index] > distance + 1 {
+                    distances[index] = distance + 1;
+                    queue.push_back((nx, ny));
+                }
+            }
+        }
+
+        distances
+    }
+
+    /// Fills the gaps that a dilation by `radius` 4-connected steps closes, then erodes the shape back by as much
+    /// Also returns the index of the closest filled pixel of every pixel, for the colors of the ones it fills.
+    /// The border of the image doesn't erode the shape, only the pixels that stayed empty do.
+    pub fn close(&self, radius: u32) -> (Self, Vec<usize>) {
+        let (distances, sources) = self.distance_to(|index| self.filled[index]);
+        let dilated = Self {
+            filled: distances
+                .iter()
+                .map(|&distance| distance <= radius)
+                .collect(),
+            ..self.clone()
+        };
+        let (outside, _) = dilated.distance_to(|index| !dilated.filled[index]);
+
+        let closed = Self {
+            filled: self
+                .filled
+                .iter()
+                .zip(outside)
+                .map(|(&filled, distance)| filled || distance > radius)
+                .collect(),
+            ..self.clone()
+        };
+
+        (closed, sources)
+    }
+
+    /// Distance in 4-connected steps from every pixel to the closest source, with the index of that source
+    /// The pixels are at `u32::MAX` when there's no source.
+    fn distance_to(&self, is_source: impl Fn(usize) -> bool) -> (Vec<u32>, Vec<usize>) {
+        let mut distances = vec![u32::MAX; self.filled.len()];
+        let mut sources = vec![usize::MAX; self.filled.len()];
+        let mut queue = VecDeque::new();
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let index = self.index(x, y);
+                if is_source(index) {
+                    distances[index] = 0;
+                    sources[index] = index;
+                    queue.push_back((x, y));
+                }
+            }
+        }
+
+        while let Some((x, y)) = queue.pop_front() {
+            let index = self.index(x, y);
+            let (distance, source) = (distances[index], sources[index]);
+            for (nx, ny) in self.neighbors(x, y) {
+                let neighbor = self.index(nx, ny);
+                if distances[neighbor] > distance + 1 {
+                    distances[neighbor] = distance + 1;
+                    sources[neighbor] = source;
+                    queue.push_back((nx, ny));
+                }
+            }
+        }
+
+        (distances, sources)
+    }
+
+    /// Creates an RGBA copy of the image where every pixel outside of the mask is transparent
+    pub fn apply(&self, image: &image::DynamicImage) -> image::DynamicImage {
+        let mut output = image.to_rgba8();
+
+        for (x, y, pixel) in output.enumerate_pixels_mut() {
+            if !self.filled[self.index(x, y)] {
+                pixel.0 = [0, 0, 0, 0];
+            } else if pixel.0[3] == 0 {
+                // Masks can fill pixels that were empty, those become opaque.
+                pixel.0[3] = u8::MAX;
+            }
+        }
+
+        image::DynamicImage::ImageRgba8(output)
+    }
+}
@@ -0,0 +1,942 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Layout of the vertex attributes inside the BIN buffer
+///
+/// ```
+/// let image = image::DynamicImage::ImageRgba8(image::RgbaImage::from_fn(3, 2, |x, y| {
+///     image::Rgba([x as u8 * 100, y as u8 * 200, 50, 255])
+/// }));
+///
+/// let read = |layout| -> Result<_, voxelify::VoxelifyError> {
+///     let options = voxelify::VoxelifyOptions { layout, ..Default::default() };
+///     let vertices = voxelify::image_to_vertices_with_options(&image, &options);
+///     let root = voxelify::create_gltf_root(&vertices, None, &options);
+///     let document = voxelify::create_gltf_document(&root, &vertices, &options)?;
+///     let views: Vec<_> = document.views().map(|view| view.stride()).collect();
+///
+///     let primitive = document.meshes().next().unwrap().primitives().next().unwrap();
+///     let reader = primitive.reader(|_| document.blob.as_deref());
+///     let positions: Vec<_> = reader.read_positions().unwrap().collect();
+///     let normals: Vec<_> = reader.read_normals().unwrap().collect();
+///     let colors: Vec<_> = reader.read_colors(0).unwrap().into_rgb_f32().collect();
+///     Ok((views, (positions, normals, colors)))
+/// };
+/// let (interleaved_views, interleaved) = read(voxelify::BufferLayout::Interleaved)?;
+/// let (separate_views, separate) = read(voxelify::BufferLayout::Separate)?;
+///
+/// assert_eq!(interleaved_views, [Some(36)]);
+/// // One tightly packed view per attribute
+/// assert_eq!(separate_views, [None, None, None]);
+/// assert_eq!(separate, interleaved);
+/// # Ok::<(), voxelify::VoxelifyError>(())
+/// ```
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BufferLayout {
+    /// A single buffer view where the attributes of each vertex are stored next to each other
+    #[default]
+    Interleaved,
+    /// One tightly packed buffer view per attribute, for loaders that don't support strided data
+    Separate,
+}
+
+/// How the neighbors outside of the image are treated when culling the side faces
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BorderMode {
+    /// Outside of the image is empty, so the voxels on the border get their outer faces
+    #[default]
+    OpenFaces,
+    /// Outside of the image is filled, for tiles that are stitched together without seams
+    SealedFaces,
+}
+
+/// Where a pixel sits relative to its voxel in the model space
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PixelOrigin {
+    /// The voxel of the pixel (x, y) spans from (x, y) to (x + 1, y + 1)
+    #[default]
+    Corner,
+    /// The voxel of the pixel (x, y) is centered on (x, y), for engines that put their grid on the voxel centers
+    Center,
+}
+
+/// Viewer the colors are written for, as viewers don't all read `COLOR_0` the same way
+/// The colors of the image are sRGB, while glTF stores linear colors that the viewer converts back.
+///
+/// ```
+/// use voxelify::ColorTarget;
+///
+/// let close = |a: [f32; 3], b: f32| a.iter().all(|channel| (channel - b).abs() < 1e-3);
+/// let gray = [0.5; 3];
+///
+/// assert!(close(ColorTarget::GltfLinear.convert(gray), 0.214));
+/// assert!(close(ColorTarget::RawSrgb.convert(gray), 0.5));
+/// assert!(close(ColorTarget::Unity.convert(gray), 0.214));
+/// assert!(close(ColorTarget::ThreeJs.convert(gray), 0.214));
+/// assert!(ColorTarget::ThreeJs.unlit() && !ColorTarget::Unity.unlit());
+/// ```
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ColorTarget {
+    /// Linear colors as required by glTF
+    GltfLinear,
+    /// The sRGB values of the image, for viewers that show `COLOR_0` without converting it
+    #[default]
+    RawSrgb,
+    /// Linear colors, glTFast converts them to the color space of the project
+    Unity,
+    /// Linear colors with an unlit material, so three.js shows the exact colors of the image
+    ThreeJs,
+}
+
+impl ColorTarget {
+    /// Color written for an sRGB color of the image
+    pub fn convert(self, color: [f32; 3]) -> [f32; 3] {
+        match self {
+            ColorTarget::RawSrgb => color,
+            _ => color.map(|channel| {
+                if channel <= 0.04045 {
+                    channel / 12.92
+                } else {
+                    ((channel + 0.055) / 1.055).powf(2.4)
+                }
+            }),
+        }
+    }
+
+    /// Whether the material is unlit with `KHR_materials_unlit`
+    pub fn unlit(self) -> bool {
+        self == ColorTarget::ThreeJs
+    }
+}
+
+/// Coordinate system the model is made for
+/// The left-handed model is reflected across the X axis, with its winding reversed so the faces still point out.
+///
+/// ```
+/// use voxelify::{image_to_vertices_with_options, Handedness, VoxelifyOptions};
+///
+/// let image = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+///     2,
+///     2,
+///     image::Rgba([255, 0, 0, 255]),
+/// ));
+/// let vertices = image_to_vertices_with_options(
+///     &image,
+///     &VoxelifyOptions {
+///         handedness: Handedness::Left,
+///         ..Default::default()
+///     },
+/// );
+/// let center = [-1.0, 1.0, 1.0];
+///
+/// for triangle in vertices.chunks_exact(3) {
+///     let [a, b, c] = [0, 1, 2].map(|index| triangle[index].position());
+///     let (u, v) = ([0, 1, 2].map(|i| b[i] - a[i]), [0, 1, 2].map(|i| c[i] - a[i]));
+///     let geometric = [
+///         u[1] * v[2] - u[2] * v[1],
+///         u[2] * v[0] - u[0] * v[2],
+///         u[0] * v[1] - u[1] * v[0],
+///     ];
+///     let outward = [0, 1, 2].map(|i| (a[i] + b[i] + c[i]) / 3.0 - center[i]);
+///     let dot = |x: [f32; 3], y: [f32; 3]| (0..3).map(|i| x[i] * y[i]).sum::<f32>();
+///
+///     assert!(dot(geometric, triangle[0].normal()) > 0.0);
+///     assert!(dot(geometric, outward) > 0.0);
+/// }
+/// ```
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Handedness {
+    /// The coordinate system of glTF
+    #[default]
+    Right,
+    /// For engines like Unity, X points the other way
+    Left,
+}
+
+/// An axis of the model space
+/// Extruding along another axis than Z rotates the model so the image X and Y go to the next axes in turn, the
+/// rotation keeps the faces pointing out.
+///
+/// ```
+/// use voxelify::{image_to_vertices_with_options, Axis, VoxelifyOptions};
+///
+/// let image = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+///     2,
+///     2,
+///     image::Rgba([255, 0, 0, 255]),
+/// ));
+///
+/// for axis in [Axis::X, Axis::Y, Axis::Z] {
+///     let options = VoxelifyOptions {
+///         extrude_axis: axis,
+///         ..Default::default()
+///     };
+///     let vertices = image_to_vertices_with_options(&image, &options);
+///     let center = axis.extrude([1.0, 1.0, 1.0]);
+///     let top = vertices.iter().map(|vertex| vertex.position()[axis as usize]).fold(f32::MIN, f32::max);
+///     assert_eq!(top, options.z_ceiling);
+///
+///     for triangle in vertices.chunks_exact(3) {
+///         let [a, b, c] = [0, 1, 2].map(|index| triangle[index].position());
+///         let (u, v) = ([0, 1, 2].map(|i| b[i] - a[i]), [0, 1, 2].map(|i| c[i] - a[i]));
+///         let geometric = [
+///             u[1] * v[2] - u[2] * v[1],
+///             u[2] * v[0] - u[0] * v[2],
+///             u[0] * v[1] - u[1] * v[0],
+///         ];
+///         let outward = [0, 1, 2].map(|i| (a[i] + b[i] + c[i]) / 3.0 - center[i]);
+///         let dot = |x: [f32; 3], y: [f32; 3]| (0..3).map(|i| x[i] * y[i]).sum::<f32>();
+///
+///         assert!(dot(geometric, triangle[0].normal()) > 0.0);
+///         assert!(dot(geometric, outward) > 0.0);
+///     }
+/// }
+/// ```
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Axis {
+    X,
+    Y,
+    #[default]
+    Z,
+}
+
+impl Axis {
+    /// Moves a point of a model extruded along Z to the same point of the model extruded along this axis
+    pub fn extrude(self, point: [f32; 3]) -> [f32; 3] {
+        let [x, y, z] = point;
+        match self {
+            Axis::X => [z, x, y],
+            Axis::Y => [y, z, x],
+            Axis::Z => point,
+        }
+    }
+}
+
+/// Uniform scale of the whole model, applied by a node above the nodes of the meshes
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ModelScale {
+    /// Multiplies every coordinate, one voxel is this many units wide
+    Factor(f32),
+    /// Scales the model so the largest side of its bounding box is this many units long
+    Fit(f32),
+}
+
+/// What a color of the image is replaced with before meshing
+/// Written as `RRGGBB` or `empty` in configuration files.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub enum ColorMapping {
+    Color(image::Rgb<u8>),
+    /// Pixels of this color are treated as empty and culled
+    Empty,
+}
+
+/// Solid slab under the whole model, for display stands
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Base {
+    /// Height of the slab, which goes down from the floor of the voxels
+    pub thickness: f32,
+    #[serde(with = "hex_color")]
+    pub color: image::Rgb<u8>,
+}
+
+/// Color of the pixels to cull as background, on top of the transparent ones
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Background {
+    #[serde(with = "hex_color")]
+    pub color: image::Rgb<u8>,
+    /// Largest difference allowed on every channel for a pixel to still count as background
+    #[serde(default)]
+    pub tolerance: u8,
+}
+
+impl Background {
+    pub fn matches(&self, pixel: image::Rgba<u8>) -> bool {
+        pixel.0[..3]
+            .iter()
+            .zip(self.color.0)
+            .all(|(channel, background)| channel.abs_diff(background) <= self.tolerance)
+    }
+}
+
+/// Heights of the voxels picked by their color, for turning categorical color maps into stepped charts
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ColorHeights {
+    /// Height above the floor of the voxels of every color
+    #[serde(with = "color_heights")]
+    pub heights: Vec<(image::Rgb<u8>, f32)>,
+    /// Largest difference allowed on every channel for a pixel to still match a color
+    #[serde(default)]
+    pub tolerance: u8,
+}
+
+impl ColorHeights {
+    /// Height of the closest color within the tolerance, `None` when no color matches
+    pub fn height(&self, pixel: image::Rgb<u8>) -> Option<f32> {
+        self.heights
+            .iter()
+            .map(|(color, height)| {
+                let distance = pixel
+                    .0
+                    .iter()
+                    .zip(color.0)
+                    .map(|(a, b)| a.abs_diff(b))
+                    .max();
+                (distance.unwrap_or_default(), *height)
+            })
+            .filter(|(distance, _)| *distance <= self.tolerance)
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, height)| height)
+    }
+}
+
+/// Tweaks applied to the colors of the voxels, on the sRGB values read from the image
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ColorAdjustment {
+    /// Added to every channel, between -1 and 1
+    pub brightness: f32,
+    /// Scales the distance of every channel from the middle gray
+    pub contrast: f32,
+    /// Channels are raised to the power of `1 / gamma`, values above 1 brighten the midtones
+    pub gamma: f32,
+}
+
+impl ColorAdjustment {
+    /// Applies the adjustment to a normalized RGB color, the result is clamped to the valid range
+    pub fn apply(&self, color: [f32; 3]) -> [f32; 3] {
+        if *self == Self::default() {
+            return color;
+        }
+
+        color.map(|channel| {
+            let channel = ((channel - 0.5) * self.contrast + 0.5 + self.brightness).clamp(0.0, 1.0);
+            channel.powf(1.0 / self.gamma)
+        })
+    }
+}
+
+impl Default for ColorAdjustment {
+    fn default() -> Self {
+        Self {
+            brightness: 0.0,
+            contrast: 1.0,
+            gamma: 1.0,
+        }
+    }
+}
+
+/// Deterministic random offset added to the color of every voxel, the same pixel always gets the same offset
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ColorJitter {
+    pub seed: u64,
+    /// Largest offset added to or removed from every channel of the normalized color
+    pub strength: f32,
+}
+
+impl ColorJitter {
+    pub fn apply(&self, color: [f32; 3], x: u32, y: u32) -> [f32; 3] {
+        let hash = splitmix64(self.seed ^ splitmix64(((x as u64) << 32) | y as u64));
+
+        std::array::from_fn(|channel| {
+            // Every channel takes its own 21 bits of the hash, mapped to [-1, 1].
+            let bits = (hash >> (channel * 21)) & 0x1F_FFFF;
+            let offset = bits as f32 / 0x1F_FFFF as f32 * 2.0 - 1.0;
+            (color[channel] + offset * self.strength).clamp(0.0, 1.0)
+        })
+    }
+}
+
+/// Seeded value noise added to the top of the voxels, the same pixel always gets the same height
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HeightNoise {
+    pub seed: u64,
+    /// Largest height added to the top of a voxel
+    pub amplitude: f32,
+    /// Width of the bumps in pixels, the noise is smooth between them
+    pub scale: f32,
+}
+
+impl HeightNoise {
+    /// Height added to the voxel of the pixel, from 0 to the amplitude
+    pub fn sample(&self, x: u32, y: u32) -> f32 {
+        let scale = self.scale.max(1.0);
+        let (fx, fy) = ((x as f32 + 0.5) / scale, (y as f32 + 0.5) / scale);
+        let (cx, cy) = (fx.floor() as u64, fy.floor() as u64);
+
+        // Every corner of the lattice gets a random value, the ones in between are blended smoothly.
+        let corner = |dx: u64, dy: u64| {
+            let hash = splitmix64(self.seed ^ splitmix64(((cx + dx) << 32) | (cy + dy)));
+            (hash >> 40) as f32 / (1u64 << 24) as f32
+        };
+        let smooth = |t: f32| t * t * (3.0 - 2.0 * t);
+        let (tx, ty) = (smooth(fx.fract()), smooth(fy.fract()));
+
+        let top = corner(0, 0) + (corner(1, 0) - corner(0, 0)) * tx;
+        let bottom = corner(0, 1) + (corner(1, 1) - corner(0, 1)) * tx;
+        (top + (bottom - top) * ty) * self.amplitude.max(0.0)
+    }
+}
+
+/// Distinct colors for the pixels of label images, where the color of a pixel is the index of its class
+/// The hues of consecutive labels are a golden ratio of a turn apart, so close labels get colors far apart.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LabelColors {
+    /// Rotates the hues, the same seed always gives the same colors
+    pub seed: u64,
+}
+
+impl LabelColors {
+    /// Color of the label, labels are the RGB of the pixel packed as `0xRRGGBB`
+    pub fn color(&self, label: u32) -> image::Rgb<u8> {
+        const GOLDEN_RATIO_CONJUGATE: f64 = 0.618_033_988_749_895;
+
+        let offset = (splitmix64(self.seed) >> 11) as f64 / (1u64 << 53) as f64;
+        let hue = (offset + label as f64 * GOLDEN_RATIO_CONJUGATE).fract() * 6.0;
+        // Every other label is darker, which tells apart the hues that end up close.
+        let (saturation, value) = if label.is_multiple_of(2) {
+            (0.65, 0.95)
+        } else {
+            (0.8, 0.7)
+        };
+
+        let chroma = value * saturation;
+        let second = chroma * (1.0 - (hue % 2.0 - 1.0).abs());
+        let (r, g, b) = match hue as u32 {
+            0 => (chroma, second, 0.0),
+            1 => (second, chroma, 0.0),
+            2 => (0.0, chroma, second),
+            3 => (0.0, second, chroma),
+            4 => (second, 0.0, chroma),
+            _ => (chroma, 0.0, second),
+        };
+
+        image::Rgb(
+            [r, g, b].map(|channel| ((channel + value - chroma) * u8::MAX as f64).round() as u8),
+        )
+    }
+}
+
+fn splitmix64(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// How the images are decoded
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct DecodeOptions {
+    pub limits: DecodeLimits,
+    /// Convert the colors from the ICC profile embedded in the image to sRGB, which costs a pass over the pixels
+    /// Without it the colors are used as they are, which only matches what editors show for sRGB images.
+    #[cfg(feature = "icc")]
+    pub apply_icc: bool,
+    /// Decode the image as this format instead of guessing it, for data with a wrong or missing extension
+    /// Data that isn't in the format fails to decode with an error.
+    pub format: Option<image::ImageFormat>,
+}
+
+/// Bounds on the images that are decoded, for images from sources that can't be trusted
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct DecodeLimits {
+    /// Largest width times height of an image, checked before decoding it
+    pub max_pixels: Option<u64>,
+    /// Largest memory in bytes the decoder may allocate, the `image` default of 512 MiB when it isn't set
+    pub max_alloc: Option<u64>,
+}
+
+/// Describes where a model came from, written into the glTF `extras` of the root
+/// Only the file name is recorded so absolute paths never leak into the output
+#[derive(Clone, Debug, Default)]
+pub struct Provenance {
+    pub file_name: Option<String>,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Options that control how an image is turned into a GLTF object
+/// Unknown keys are refused, so a misspelled option in a configuration file isn't silently ignored.
+///
+/// ```
+/// let options = voxelify::VoxelifyOptions {
+///     bevel: 0.2,
+///     threads: 2,
+///     ..Default::default()
+/// };
+/// let json = serde_json::to_string(&options)?;
+/// let parsed: voxelify::VoxelifyOptions = serde_json::from_str(&json)?;
+/// assert_eq!((parsed.bevel, parsed.threads), (0.2, 2));
+///
+/// assert!(serde_json::from_str::<voxelify::VoxelifyOptions>(r#"{ "bevell": 0.2 }"#).is_err());
+/// # Ok::<(), serde_json::Error>(())
+/// ```
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VoxelifyOptions {
+    /// Z coordinate of the bottom faces of the voxels
+    pub z_floor: f32,
+    /// Z coordinate of the top faces of the voxels
+    pub z_ceiling: f32,
+    /// Size of the slanted edge around the exposed top edges of a voxel, up to just under half a voxel
+    pub bevel: f32,
+    pub border: BorderMode,
+    pub pixel_origin: PixelOrigin,
+    pub handedness: Handedness,
+    /// Axis the voxels are extruded along, which the floor and the ceiling are measured on
+    pub extrude_axis: Axis,
+    /// Cut the outer corners of staircases along the silhouette at 45°, ignored when the voxels are beveled
+    pub smooth_edges: bool,
+    /// Give every voxel all six faces instead of culling the ones against its neighbors, for voxel physics
+    /// imports that split the mesh per block. Bevels, smooth edges and the sealed bottom are ignored.
+    pub full_cubes: bool,
+    /// Round the corners of the voxels by this fraction of half a voxel, from 0 to 1, with smooth normals
+    /// Every visible face becomes a grid with many more triangles. The edges against the neighbors stay square so
+    /// they join. Bevels, smooth edges and the sealed bottom are ignored, the voxels keep their own bottom, and so is
+    /// the rounding itself with a depth map.
+    pub rounding: f32,
+    /// Replace the bottom faces by a single watertight bottom under every connected region, for 3D printing
+    pub seal_bottom: bool,
+    /// Add a slab spanning the bounds of the model under its floor
+    pub base: Option<Base>,
+    /// Duplicate the model reflected across the far side of its bounds on this axis
+    pub mirror: Option<Axis>,
+    /// Fill the gaps between the pixels up to twice this radius wide, so dithered areas become solid
+    pub fill_holes: Option<u32>,
+    /// Only extrude the pixels within this many pixels from the edge of the shape
+    pub outline: Option<u32>,
+    /// Wind the triangles clockwise, for engines that expect it
+    ///
+    /// ```
+    /// let image = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(2, 1, image::Rgba([255, 0, 0, 255])));
+    /// // How the triangles face compared to their normals, counter-clockwise towards the normal is positive
+    /// let facing = |options: voxelify::VoxelifyOptions| -> Vec<f32> {
+    ///     let vertices = voxelify::image_to_vertices_with_options(&image, &options);
+    ///     vertices
+    ///         .chunks(3)
+    ///         .map(|triangle| {
+    ///             let [a, b, c] = [0, 1, 2].map(|index| nalgebra::Vector3::from(triangle[index].position()));
+    ///             (b - a).cross(&(c - a)).dot(&nalgebra::Vector3::from(triangle[0].normal())).signum()
+    ///         })
+    ///         .collect()
+    /// };
+    ///
+    /// let default = facing(voxelify::VoxelifyOptions::default());
+    /// assert!(default.iter().all(|&sign| sign == 1.0));
+    /// let flipped = facing(voxelify::VoxelifyOptions { flip_winding: true, ..Default::default() });
+    /// assert!(flipped.iter().all(|&sign| sign == -1.0));
+    /// let inwards = facing(voxelify::VoxelifyOptions { flip_normals: true, ..Default::default() });
+    /// assert!(inwards.iter().all(|&sign| sign == -1.0));
+    /// let both = voxelify::VoxelifyOptions { flip_winding: true, flip_normals: true, ..Default::default() };
+    /// assert_eq!(facing(both), default);
+    /// ```
+    pub flip_winding: bool,
+    /// Point the normals into the model
+    pub flip_normals: bool,
+    pub color_adjustment: ColorAdjustment,
+    /// Vary the color of every voxel a little for a less flat look
+    pub color_jitter: Option<ColorJitter>,
+    /// Make the voxels as tall as the alpha of their pixel, opaque ones reach the ceiling and aren't beveled
+    /// Ignored when the heights come from a depth map.
+    pub alpha_height: bool,
+    /// Make the voxels as tall as the height of their color, the others keep the height between the floor and ceiling
+    pub color_heights: Option<ColorHeights>,
+    /// Vary the height of the top of every voxel for a less flat look, the voxels aren't beveled with it
+    pub height_noise: Option<HeightNoise>,
+    /// Snap the heights of voxels that aren't all as tall to this many layers between the floor and the ceiling
+    /// Heights are rounded to the closest layer, the ones that round to zero are flat.
+    pub z_layers: Option<u32>,
+    /// Cull the pixels close to this color, by default only transparent pixels are empty
+    pub background: Option<Background>,
+    /// Cull the pixels whose luminance is below this value, from 0 to 255
+    pub min_brightness: u8,
+    /// Stretch the colors of the filled pixels to the full range of every channel before meshing, for faded images
+    /// The empty and culled pixels don't count towards the darkest and brightest colors.
+    pub normalize_colors: bool,
+    /// Replace the colors with distinct ones, for label images whose colors are class indices rather than colors
+    /// The pixels are culled before, so a background label can be culled as the background color.
+    pub label_colors: Option<LabelColors>,
+    /// Colors to substitute before meshing, colors that aren't in the map are kept as they are
+    #[serde(with = "color_map")]
+    pub color_map: HashMap<image::Rgb<u8>, ColorMapping>,
+    /// Snap the color of every pixel to the closest of these colors after the color map, none keeps the colors
+    #[serde(with = "palette")]
+    pub palette: Vec<image::Rgb<u8>>,
+    pub layout: BufferLayout,
+    /// Draw the outline of every face as lines instead of filled triangles
+    pub wireframe: bool,
+    /// Emit every voxel as a single colored point instead of its faces, for quick previews of huge images
+    /// The points sit on the center of the top of their voxel and only store positions and colors, the options
+    /// that shape the faces are ignored.
+    pub points: bool,
+    /// Emit a single surface through the tops of the voxels instead of their faces, for heightmaps
+    /// Every pixel is a vertex shared by the cells around it, with a normal following the slope, and the empty
+    /// pixels make holes. The options that shape the faces are ignored, and so is this with `points`.
+    pub terrain: bool,
+    /// Give every voxel this color instead of the color of its pixel, the pixels are only read to cull them
+    /// The color goes into the material, so `COLOR_0` is left out unless it holds the alpha of `vertex_alpha`.
+    #[serde(with = "optional_hex_color")]
+    pub monochrome: Option<image::Rgb<u8>>,
+    /// Store the alpha of the pixels in `COLOR_0` and blend the material, fully transparent pixels are still culled
+    pub vertex_alpha: bool,
+    /// Render the faces from both sides, for hollow or open shapes whose inside can be seen
+    pub double_sided: bool,
+    /// Name of the material, setting it always gives the meshes a material
+    pub material_name: Option<String>,
+    /// Set the base color of the material to the average color of the mesh, for viewers that ignore `COLOR_0`
+    /// glTF multiplies the vertex colors by the base color, so viewers that do use them show darker colors.
+    pub fallback_color: bool,
+    pub color_target: ColorTarget,
+    /// Make the voxels whose luminance is at least this value glow with their own color, from 0 to 255
+    pub emissive_threshold: Option<u8>,
+    /// Intensity of the glow, values above 1 use `KHR_materials_emissive_strength`
+    pub emissive_strength: f32,
+    /// Add `TEXCOORD_0` projected from the image, one voxel covers one pixel of the image
+    pub texture_coordinates: bool,
+    /// Add a `TANGENT` for normal-mapped materials, this implies `texture_coordinates`
+    ///
+    /// ```
+    /// let image = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(2, 2, image::Rgba([255, 0, 0, 255])));
+    /// let options = voxelify::VoxelifyOptions {
+    ///     tangents: true,
+    ///     bevel: 0.2,
+    ///     ..Default::default()
+    /// };
+    /// let vertices = voxelify::image_to_vertices_with_options(&image, &options);
+    /// let root = voxelify::create_gltf_root(&vertices, None, &options);
+    /// let document = voxelify::create_gltf_document(&root, &vertices, &options)?;
+    ///
+    /// let primitive = document.meshes().next().unwrap().primitives().next().unwrap();
+    /// let reader = primitive.reader(|_| document.blob.as_deref());
+    /// let tangents: Vec<_> = reader.read_tangents().unwrap().collect();
+    /// assert_eq!(tangents.len(), vertices.len());
+    /// for (tangent, normal) in tangents.iter().zip(reader.read_normals().unwrap()) {
+    ///     let dot: f32 = (0..3).map(|axis| tangent[axis] * normal[axis]).sum();
+    ///     assert!(dot.abs() < 1e-5);
+    ///     assert!(tangent[3] == 1.0 || tangent[3] == -1.0);
+    /// }
+    /// # Ok::<(), voxelify::VoxelifyError>(())
+    /// ```
+    pub tangents: bool,
+    /// Color the faces with the texture of their mesh entry instead of `COLOR_0`, see `bake_texture`
+    /// Every face samples the center of the pixel of its voxel, which replaces the projected texture coordinates
+    /// and the tangents. The sealed bottom and the base span several pixels and are left out.
+    pub embed_texture: bool,
+    /// Sample the embedded texture at the nearest texel, without the filtering and mipmaps viewers use by default
+    pub nearest_sampling: bool,
+    /// Store positions as `u16` using `KHR_mesh_quantization`, the node transform restores their scale
+    /// The positions are padded to four bytes, which a separate view declares with its stride.
+    ///
+    /// ```
+    /// let image = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(3, 2, image::Rgba([255, 0, 0, 255])));
+    /// let options = voxelify::VoxelifyOptions {
+    ///     quantize_positions: true,
+    ///     layout: voxelify::BufferLayout::Separate,
+    ///     z_ceiling: 2.5,
+    ///     ..Default::default()
+    /// };
+    /// let vertices = voxelify::image_to_vertices_with_options(&image, &options);
+    /// let root = voxelify::create_gltf_root(&vertices, None, &options);
+    /// let document = voxelify::create_gltf_document(&root, &vertices, &options)?;
+    ///
+    /// // Decoded like a loader would, with the stride of the view or tightly packed without one
+    /// let node = document.nodes().find(|node| node.mesh().is_some()).unwrap();
+    /// let (translation, _, scale) = node.transform().decomposed();
+    /// let primitive = node.mesh().unwrap().primitives().next().unwrap();
+    /// let accessor = primitive.get(&gltf::Semantic::Positions).unwrap();
+    /// let view = accessor.view().unwrap();
+    /// let stride = view.stride().unwrap_or(3 * 2);
+    /// let bin = document.blob.as_deref().unwrap();
+    /// for (index, vertex) in vertices.iter().enumerate() {
+    ///     let start = view.offset() + accessor.offset() + index * stride;
+    ///     for axis in 0..3 {
+    ///         let bytes = [bin[start + axis * 2], bin[start + axis * 2 + 1]];
+    ///         let position = u16::from_le_bytes(bytes) as f32 * scale[axis] + translation[axis];
+    ///         assert!((position - vertex.position()[axis]).abs() < 1e-3);
+    ///     }
+    /// }
+    /// # Ok::<(), voxelify::VoxelifyError>(())
+    /// ```
+    pub quantize_positions: bool,
+    /// Store normals as normalized `i8` using `KHR_mesh_quantization`, exact for the faces along the axes
+    ///
+    /// ```
+    /// let image = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(2, 2, image::Rgba([255, 0, 0, 255])));
+    /// for layout in [voxelify::BufferLayout::Interleaved, voxelify::BufferLayout::Separate] {
+    ///     let options = voxelify::VoxelifyOptions {
+    ///         quantize_normals: true,
+    ///         layout,
+    ///         ..Default::default()
+    ///     };
+    ///     let vertices = voxelify::image_to_vertices_with_options(&image, &options);
+    ///     let root = voxelify::create_gltf_root(&vertices, None, &options);
+    ///     let document = voxelify::create_gltf_document(&root, &vertices, &options)?;
+    ///
+    ///     let primitive = document.meshes().next().unwrap().primitives().next().unwrap();
+    ///     let accessor = primitive.get(&gltf::Semantic::Normals).unwrap();
+    ///     assert_eq!(accessor.data_type(), gltf::accessor::DataType::I8);
+    ///     assert!(accessor.normalized());
+    ///
+    ///     // Decoded like a loader would, the faces along the axes round-trip exactly.
+    ///     let view = accessor.view().unwrap();
+    ///     let stride = view.stride().unwrap_or(3);
+    ///     let bin = document.blob.as_deref().unwrap();
+    ///     for (index, vertex) in vertices.iter().enumerate() {
+    ///         let start = view.offset() + accessor.offset() + index * stride;
+    ///         let normal = bin[start..start + 3]
+    ///             .iter()
+    ///             .map(|&byte| (byte as i8 as f32 / 127.0).max(-1.0));
+    ///         assert!(normal.eq(vertex.normal()));
+    ///     }
+    /// }
+    /// # Ok::<(), voxelify::VoxelifyError>(())
+    /// ```
+    pub quantize_normals: bool,
+    pub scale: Option<ModelScale>,
+    /// Opt-in source information stored in the `extras` of the root, it describes the input so it isn't configured
+    #[serde(skip)]
+    pub provenance: Option<Provenance>,
+    /// Copyright of the model, stored in the asset of the root
+    pub copyright: Option<String>,
+    /// Oldest glTF version the model needs, stored in the asset for viewers that refuse assets without it
+    pub min_version: Option<String>,
+    /// Threads meshing the tiles of large images, 0 uses all the cores
+    /// The library meshes on the current rayon thread pool, so callers cap it by meshing inside their own
+    /// `ThreadPool::install`. The CLI builds its global pool with these threads once for the run.
+    /// Ignored without the `parallel` feature, it's still kept so the configuration files work on every build.
+    pub threads: usize,
+}
+
+impl Default for VoxelifyOptions {
+    fn default() -> Self {
+        Self {
+            z_floor: 0.0,
+            z_ceiling: 2.0,
+            bevel: 0.0,
+            border: BorderMode::OpenFaces,
+            pixel_origin: PixelOrigin::Corner,
+            handedness: Handedness::Right,
+            extrude_axis: Axis::Z,
+            smooth_edges: false,
+            full_cubes: false,
+            rounding: 0.0,
+            seal_bottom: false,
+            base: None,
+            mirror: None,
+            fill_holes: None,
+            outline: None,
+            flip_winding: false,
+            flip_normals: false,
+            color_adjustment: Default::default(),
+            color_jitter: None,
+            alpha_height: false,
+            color_heights: None,
+            height_noise: None,
+            z_layers: None,
+            background: None,
+            min_brightness: 0,
+            normalize_colors: false,
+            label_colors: None,
+            color_map: HashMap::new(),
+            palette: Vec::new(),
+            layout: Default::default(),
+            wireframe: false,
+            points: false,
+            terrain: false,
+            monochrome: None,
+            vertex_alpha: false,
+            double_sided: false,
+            material_name: None,
+            fallback_color: false,
+            color_target: ColorTarget::RawSrgb,
+            emissive_threshold: None,
+            emissive_strength: 1.0,
+            texture_coordinates: false,
+            tangents: false,
+            embed_texture: false,
+            nearest_sampling: false,
+            quantize_positions: false,
+            quantize_normals: false,
+            scale: None,
+            provenance: None,
+            copyright: None,
+            min_version: None,
+            threads: 0,
+        }
+    }
+}
+
+impl From<ColorMapping> for String {
+    fn from(mapping: ColorMapping) -> Self {
+        match mapping {
+            ColorMapping::Color(color) => hex_color::format(color),
+            ColorMapping::Empty => "empty".to_string(),
+        }
+    }
+}
+
+impl TryFrom<String> for ColorMapping {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            "empty" => Ok(ColorMapping::Empty),
+            color => hex_color::parse(color).map(ColorMapping::Color),
+        }
+    }
+}
+
+/// Colors are written as `RRGGBB` in configuration files, like on the command line
+pub(crate) mod hex_color {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn format(color: image::Rgb<u8>) -> String {
+        let [r, g, b] = color.0;
+        format!("{r:02x}{g:02x}{b:02x}")
+    }
+
+    pub fn parse(value: &str) -> Result<image::Rgb<u8>, String> {
+        let hex = value.trim_start_matches('#');
+        let channel = |index: usize| {
+            hex.get(index..index + 2)
+                .and_then(|channel| u8::from_str_radix(channel, 16).ok())
+        };
+
+        match (hex.len(), channel(0), channel(2), channel(4)) {
+            (6, Some(r), Some(g), Some(b)) => Ok(image::Rgb([r, g, b])),
+            _ => Err(format!("Invalid color `{value}`, expected RRGGBB")),
+        }
+    }
+
+    pub fn serialize<S: Serializer>(
+        color: &image::Rgb<u8>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format(*color))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<image::Rgb<u8>, D::Error> {
+        parse(&String::deserialize(deserializer)?).map_err(D::Error::custom)
+    }
+}
+
+/// A missing color is written as `null`
+mod optional_hex_color {
+    use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::hex_color;
+
+    pub fn serialize<S: Serializer>(
+        color: &Option<image::Rgb<u8>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        color.map(hex_color::format).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<image::Rgb<u8>>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|color| hex_color::parse(&color).map_err(D::Error::custom))
+            .transpose()
+    }
+}
+
+/// The color map is an object keyed by the colors it replaces, sorted so the same map is always written the same way
+mod color_map {
+    use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::{BTreeMap, HashMap};
+
+    use super::{hex_color, ColorMapping};
+
+    pub fn serialize<S: Serializer>(
+        map: &HashMap<image::Rgb<u8>, ColorMapping>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        map.iter()
+            .map(|(color, mapping)| (hex_color::format(*color), *mapping))
+            .collect::<BTreeMap<_, _>>()
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<HashMap<image::Rgb<u8>, ColorMapping>, D::Error> {
+        HashMap::<String, ColorMapping>::deserialize(deserializer)?
+            .into_iter()
+            .map(|(color, mapping)| {
+                Ok((hex_color::parse(&color).map_err(D::Error::custom)?, mapping))
+            })
+            .collect()
+    }
+}
+
+/// The palette is a list of colors, the order breaks the ties between colors as close
+mod palette {
+    use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::hex_color;
+
+    pub fn serialize<S: Serializer>(
+        palette: &[image::Rgb<u8>],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        palette
+            .iter()
+            .map(|color| hex_color::format(*color))
+            .collect::<Vec<_>>()
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<image::Rgb<u8>>, D::Error> {
+        Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|color| hex_color::parse(color).map_err(D::Error::custom))
+            .collect()
+    }
+}
+
+/// The heights are a list of `[color, height]` pairs, the order breaks the ties between colors as close
+mod color_heights {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    struct Entry(#[serde(with = "super::hex_color")] image::Rgb<u8>, f32);
+
+    pub fn serialize<S: Serializer>(
+        heights: &[(image::Rgb<u8>, f32)],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        heights
+            .iter()
+            .map(|&(color, height)| Entry(color, height))
+            .collect::<Vec<_>>()
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<(image::Rgb<u8>, f32)>, D::Error> {
+        Ok(Vec::<Entry>::deserialize(deserializer)?
+            .into_iter()
+            .map(|Entry(color, height)| (color, height))
+            .collect())
+    }
+}
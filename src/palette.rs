@@ -0,0 +1,171 @@
+use std::io::{Read, Seek};
+
+use crate::{DecodeLimits, VoxelifyError};
+
+/// Allocation limit of the `image` decoders, used when the limits don't set one
+const DEFAULT_MAX_ALLOC: u64 = 512 * 1024 * 1024;
+
+/// Pixels of an indexed-color PNG kept as indices into its palette
+/// Grouping the voxels by index is exact, unlike grouping the colors they were resolved to.
+/// The loaders of the crate decode indexed PNGs through it, so the pixels get exactly the colors of the palette.
+///
+/// ```
+/// let mut png = Vec::new();
+/// let mut encoder = png::Encoder::new(&mut png, 4, 1);
+/// encoder.set_color(png::ColorType::Indexed);
+/// encoder.set_depth(png::BitDepth::Eight);
+/// encoder.set_palette(vec![0, 0, 0, 255, 0, 0, 0, 0, 255]);
+/// encoder.set_trns(vec![0]);
+/// let mut writer = encoder.write_header()?;
+/// writer.write_image_data(&[0, 1, 2, 1])?;
+/// writer.finish()?;
+///
+/// let indexed = voxelify::IndexedImage::from_png(std::io::Cursor::new(&png))?.unwrap();
+/// assert_eq!(indexed.indices, [0, 1, 2, 1]);
+/// assert_eq!(indexed.layers().len(), 3);
+///
+/// let image = voxelify::image_from_bytes(&png)?;
+/// assert_eq!(image.to_rgba8(), indexed.to_image().to_rgba8());
+/// assert_eq!(image.to_rgba8().get_pixel(2, 0), &image::Rgba([0, 0, 255, 255]));
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct IndexedImage {
+    pub width: u32,
+    pub height: u32,
+    /// Entries of the `PLTE` chunk, with the alpha from the `tRNS` chunk
+    pub palette: Vec<image::Rgba<u8>>,
+    /// Row-major palette index of every pixel
+    pub indices: Vec<u8>,
+    /// Embedded `iCCP` profile the colors of the palette are in
+    pub icc_profile: Option<Vec<u8>>,
+}
+
+impl IndexedImage {
+    /// Reads a PNG without resolving its palette, returns `None` when the PNG isn't indexed
+    pub fn from_png<R: Read + Seek>(reader: R) -> Result<Option<Self>, VoxelifyError> {
+        Self::from_png_with_limits(reader, DecodeLimits::default())
+    }
+
+    /// Same as `from_png`, PNGs over the limits are refused with an error before their pixels are read
+    pub fn from_png_with_limits<R: Read + Seek>(
+        reader: R,
+        limits: DecodeLimits,
+    ) -> Result<Option<Self>, VoxelifyError> {
+        let max_alloc = limits.max_alloc.unwrap_or(DEFAULT_MAX_ALLOC);
+        let decoder_limits = png::Limits {
+            bytes: usize::try_from(max_alloc).unwrap_or(usize::MAX),
+        };
+        // The default transformations keep the indices instead of expanding them to colors.
+        let mut reader =
+            png::Decoder::new_with_limits(std::io::BufReader::new(reader), decoder_limits)
+                .read_info()?;
+
+        let info = reader.info();
+        if info.color_type != png::ColorType::Indexed {
+            return Ok(None);
+        }
+        let (width, height) = (info.width, info.height);
+        if let Some(max_pixels) = limits.max_pixels {
+            if width as u64 * height as u64 > max_pixels {
+                return Err(VoxelifyError::TooManyPixels {
+                    width,
+                    height,
+                    max_pixels,
+                });
+            }
+        }
+        // The rows, the indices and the resolved image are all alive at once.
+        let mut allocation = image::Limits::default();
+        allocation.max_alloc = Some(max_alloc);
+        allocation.reserve(width as u64 * height as u64 * (1 + 4))?;
+        let icc_profile = info.icc_profile.as_deref().map(<[u8]>::to_vec);
+        let bit_depth = info.bit_depth as usize;
+
+        let rgb = info.palette.as_deref().unwrap_or_default();
+        let alpha = info.trns.as_deref().unwrap_or_default();
+        let palette = rgb
+            .chunks_exact(3)
+            .enumerate()
+            .map(|(index, rgb)| {
+                image::Rgba([
+                    rgb[0],
+                    rgb[1],
+                    rgb[2],
+                    *alpha.get(index).unwrap_or(&u8::MAX),
+                ])
+            })
+            .collect();
+
+        let mut buffer = vec![0; reader.output_buffer_size().unwrap_or_default()];
+        let frame = reader.next_frame(&mut buffer)?;
+
+        // Indices below 8 bits are packed into the bytes of a row from the most significant bit.
+        let mut indices = Vec::with_capacity((width * height) as usize);
+        for row in buffer.chunks(frame.line_size).take(height as usize) {
+            indices.extend((0..width as usize).map(|x| {
+                let bit = x * bit_depth;
+                let shift = 8 - bit_depth - bit % 8;
+                (row[bit / 8] >> shift) & ((1u16 << bit_depth) - 1) as u8
+            }));
+        }
+
+        Ok(Some(Self {
+            width,
+            height,
+            palette,
+            indices,
+            icc_profile,
+        }))
+    }
+
+    /// Color of a palette entry, indices past the end of the palette are transparent
+    pub fn color(&self, index: u8) -> image::Rgba<u8> {
+        self.palette
+            .get(index as usize)
+            .copied()
+            .unwrap_or(image::Rgba([0, 0, 0, 0]))
+    }
+
+    /// Resolves the palette into an image, every pixel gets the exact color of its entry
+    /// Like the `image` decoders, palettes without transparency give an RGB image and the others an RGBA one.
+    pub fn to_image(&self) -> image::DynamicImage {
+        let image = image::RgbaImage::from_fn(self.width, self.height, |x, y| {
+            self.color(self.indices[(y * self.width + x) as usize])
+        });
+
+        let transparent = self
+            .indices
+            .iter()
+            .any(|&index| self.color(index)[3] < u8::MAX);
+        if transparent {
+            image::DynamicImage::ImageRgba8(image)
+        } else {
+            image::DynamicImage::ImageRgb8(image::DynamicImage::ImageRgba8(image).to_rgb8())
+        }
+    }
+
+    /// Splits the image into one image per palette entry in use, in the order of the palette
+    /// Every layer only keeps the pixels of its entry, the others are transparent.
+    pub fn layers(&self) -> Vec<(u8, image::DynamicImage)> {
+        let mut used = [false; 256];
+        for &index in &self.indices {
+            used[index as usize] = true;
+        }
+
+        (0..=u8::MAX)
+            .filter(|&index| used[index as usize])
+            .map(|index| {
+                let layer = image::RgbaImage::from_fn(self.width, self.height, |x, y| {
+                    if self.indices[(y * self.width + x) as usize] == index {
+                        self.color(index)
+                    } else {
+                        image::Rgba([0, 0, 0, 0])
+                    }
+                });
+
+                (index, image::DynamicImage::ImageRgba8(layer))
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,49 @@
+use std::time::Duration;
+
+use crate::VoxelifyError;
+
+const HTTP_SCHEME: &str = "http://";
+const HTTPS_SCHEME: &str = "https://";
+const TIMEOUT: Duration = Duration::from_secs(30);
+/// Largest image that's downloaded, the decode limits only apply once it's in memory
+const MAX_DOWNLOAD_BYTES: u64 = 256 * 1024 * 1024;
+
+/// Returns true if the input looks like an HTTP(S) URL rather than a file path
+pub fn is_remote_url(input: &str) -> bool {
+    input.starts_with(HTTP_SCHEME) || input.starts_with(HTTPS_SCHEME)
+}
+
+/// Downloads the raw bytes of an image from an HTTP or HTTPS URL, following redirects
+/// The bytes can be decoded with `image_from_bytes`. Responses that aren't successful, timeouts and bodies over
+/// 256 MiB are errors.
+pub fn fetch_image(url: &str) -> Result<Vec<u8>, VoxelifyError> {
+    if !is_remote_url(url) {
+        return Err(remote_error(format!("Not an HTTP URL: {url}")));
+    }
+
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .timeout_global(Some(TIMEOUT))
+        // The roots of the system, which also trust the proxies that intercept the connections.
+        .tls_config(
+            ureq::tls::TlsConfig::builder()
+                .root_certs(ureq::tls::RootCerts::PlatformVerifier)
+                .build(),
+        )
+        .build()
+        .into();
+    let mut response = agent
+        .get(url)
+        .call()
+        .map_err(|err| remote_error(format!("{url}: {err}")))?;
+
+    response
+        .body_mut()
+        .with_config()
+        .limit(MAX_DOWNLOAD_BYTES)
+        .read_to_vec()
+        .map_err(|err| remote_error(format!("Failed to read the response of {url}: {err}")))
+}
+
+fn remote_error(message: impl Into<String>) -> VoxelifyError {
+    VoxelifyError::RemoteError(message.into())
+}
@@ -0,0 +1,284 @@
+use nalgebra::{Vector2, Vector3};
+
+use crate::{is_filled, BorderMode, Vertex};
+
+/// Steps of the grid across every rounded edge, on each of the two faces that meet there
+const SEGMENTS: usize = 2;
+
+/// Axis the face is fixed on, its side, and the axes of its grid in the order that winds it outwards
+const FACES: [(usize, usize, usize, usize); 6] = [
+    (2, 1, 0, 1),
+    (2, 0, 1, 0),
+    (0, 0, 2, 1),
+    (0, 1, 1, 2),
+    (1, 0, 0, 2),
+    (1, 1, 2, 0),
+];
+
+/// Voxel with rounded corners, made of a grid per visible face that is pushed onto a rounded box
+/// Only the edges between two visible faces are rounded, the sides towards a hidden face stay square so the
+/// neighbors join. Every grid has the same steps whatever the neighbors, so the faces of neighbors share their
+/// vertices.
+pub(crate) struct RoundedVoxel {
+    min: [f32; 3],
+    max: [f32; 3],
+    /// Whether the low and the high side of every axis is visible
+    exposed: [[bool; 2]; 3],
+    radius: f32,
+}
+
+impl RoundedVoxel {
+    /// The radius is the rounding times half of the shortest side of the voxel
+    pub fn new(
+        image: &image::DynamicImage,
+        pos: Vector2<u32>,
+        floor: f32,
+        ceiling: f32,
+        rounding: f32,
+        border: BorderMode,
+    ) -> Self {
+        let (x, y) = (pos.x as i64, pos.y as i64);
+        let exposed = |dx: i64, dy: i64| !is_filled(image, x + dx, y + dy, border);
+
+        Self {
+            min: [pos.x as f32, pos.y as f32, floor],
+            max: [pos.x as f32 + 1.0, pos.y as f32 + 1.0, ceiling],
+            exposed: [
+                [exposed(-1, 0), exposed(1, 0)],
+                [exposed(0, -1), exposed(0, 1)],
+                [true, true],
+            ],
+            radius: rounding.clamp(0.0, 1.0) * 0.5 * (ceiling - floor).clamp(0.0, 1.0),
+        }
+    }
+
+    /// Quads of the visible faces, which `vertices` turns into six vertices each
+    /// The strips filling the inner corners are left out.
+    pub fn quads(&self) -> usize {
+        self.faces()
+            .map(|(_, _, u, v)| (self.steps(u).len() - 1) * (self.steps(v).len() - 1))
+            .sum()
+    }
+
+    /// Creates the faces of the voxel, and the strips filling the gaps left at the corners it shares with rounded
+    /// neighbors
+    pub fn vertices(
+        &self,
+        image: &image::DynamicImage,
+        pos: Vector2<u32>,
+        border: BorderMode,
+        color: [f32; 3],
+    ) -> Vec<Vertex> {
+        let mut vertices = Vec::with_capacity(self.quads() * 6);
+
+        for (axis, side, u, v) in self.faces() {
+            let (us, vs) = (self.steps(u), self.steps(v));
+            let corner = |i: usize, j: usize| {
+                let mut point = [0.0; 3];
+                point[axis] = self.side(axis, side);
+                point[u] = us[i];
+                point[v] = vs[j];
+                self.round(point, axis, side)
+            };
+
+            for j in 0..vs.len() - 1 {
+                for i in 0..us.len() - 1 {
+                    let corners = [
+                        corner(i, j),
+                        corner(i + 1, j),
+                        corner(i, j + 1),
+                        corner(i + 1, j + 1),
+                    ];
+                    vertices.extend(corners_to_vertices(corners, color));
+                }
+            }
+        }
+
+        // Like the bevels, a neighbor whose side is visible where this voxel's isn't rounds away from the
+        // square corner of this voxel.
+        let (x, y) = (pos.x as i64, pos.y as i64);
+        let filled = |dx: i64, dy: i64| is_filled(image, x + dx, y + dy, border);
+        for (dx, dy) in [(-1, -1), (1, -1), (-1, 1), (1, 1)] {
+            if !(filled(dx, 0) && !filled(dx, dy) && filled(0, dy)) {
+                continue;
+            }
+
+            let neighbor = |dx: i64, dy: i64| RoundedVoxel {
+                exposed: [
+                    [!filled(dx - 1, dy), !filled(dx + 1, dy)],
+                    [!filled(dx, dy - 1), !filled(dx, dy + 1)],
+                    [true, true],
+                ],
+                min: [
+                    self.min[0] + dx as f32,
+                    self.min[1] + dy as f32,
+                    self.min[2],
+                ],
+                max: [
+                    self.max[0] + dx as f32,
+                    self.max[1] + dy as f32,
+                    self.max[2],
+                ],
+                radius: self.radius,
+            };
+            let sides = [(dx > 0) as usize, (dy > 0) as usize];
+
+            for floor in [false, true] {
+                vertices.extend(self.corner_strip(&neighbor(dx, 0), 0, sides, floor, color));
+                vertices.extend(self.corner_strip(&neighbor(0, dy), 1, sides, floor, color));
+            }
+        }
+
+        vertices
+    }
+
+    /// Fills the gap between the square corner of this voxel and the rounded edge of its neighbor along the axis
+    /// The gap lies on the side the voxels share, between the edges along the other axis on the top or the floor.
+    fn corner_strip(
+        &self,
+        neighbor: &RoundedVoxel,
+        axis: usize,
+        sides: [usize; 2],
+        floor: bool,
+        color: [f32; 3],
+    ) -> Vec<Vertex> {
+        let other = 1 - axis;
+        let z_side = (!floor) as usize;
+        let edge = self.side(other, sides[other]);
+        let height = self.side(2, z_side);
+
+        let mut band = self.steps(other);
+        if sides[other] == 0 {
+            band.truncate(SEGMENTS + 1);
+            band.reverse();
+        } else {
+            band.drain(..band.len() - SEGMENTS - 1);
+        }
+        let mut heights = self.steps(2);
+        if z_side == 0 {
+            heights.truncate(SEGMENTS + 1);
+        } else {
+            heights.drain(..heights.len() - SEGMENTS - 1);
+            heights.reverse();
+        }
+
+        // The square corner goes along the face of this voxel and then down or up its side, while the rounded
+        // edge follows the same points on the faces of the neighbor.
+        let point = |along: f32, z: f32| {
+            let mut point = [0.0; 3];
+            point[axis] = self.side(axis, sides[axis]);
+            point[other] = along;
+            point[2] = z;
+            point
+        };
+        let square: Vec<[f32; 3]> = band
+            .iter()
+            .map(|&along| point(along, height))
+            .chain(heights.iter().skip(1).map(|&z| point(edge, z)))
+            .collect();
+        let rounded: Vec<[f32; 3]> = band
+            .iter()
+            .map(|&along| neighbor.round(point(along, height), 2, z_side).0)
+            .chain(
+                heights
+                    .iter()
+                    .skip(1)
+                    .map(|&z| neighbor.round(point(edge, z), other, sides[other]).0),
+            )
+            .collect();
+
+        let mut normal = [0.0; 3];
+        normal[axis] = if sides[axis] == 0 { -1.0 } else { 1.0 };
+
+        let mut vertices = Vec::with_capacity((square.len() - 1) * 6);
+        for index in 0..square.len() - 1 {
+            let mut corners = [
+                square[index],
+                square[index + 1],
+                rounded[index],
+                rounded[index + 1],
+            ];
+
+            // One of the triangles at the ends of the strip is degenerate, the other one gives the winding.
+            let [a, b, c, d] = corners.map(Vector3::from);
+            let winding = (b - a).cross(&(c - a)) + (b - c).cross(&(d - c));
+            if winding.dot(&Vector3::from(normal)) < 0.0 {
+                corners.swap(1, 2);
+            }
+
+            vertices.extend(corners_to_vertices(
+                corners.map(|corner| (corner, normal)),
+                color,
+            ));
+        }
+
+        vertices
+    }
+
+    fn faces(&self) -> impl Iterator<Item = (usize, usize, usize, usize)> + '_ {
+        FACES
+            .into_iter()
+            .filter(|&(axis, side, _, _)| self.exposed[axis][side])
+    }
+
+    fn side(&self, axis: usize, side: usize) -> f32 {
+        if side == 0 {
+            self.min[axis]
+        } else {
+            self.max[axis]
+        }
+    }
+
+    /// Coordinates of the grid along the axis, denser at both ends where the edges may be rounded
+    fn steps(&self, axis: usize) -> Vec<f32> {
+        let (min, max) = (self.min[axis], self.max[axis]);
+        let step = |index: usize| self.radius * index as f32 / SEGMENTS as f32;
+
+        let mut steps: Vec<f32> = (0..=SEGMENTS)
+            .map(|index| min + step(index))
+            .chain((0..=SEGMENTS).rev().map(|index| max - step(index)))
+            .collect();
+        // The bands of a voxel only as wide as the diameter meet in the middle.
+        steps.dedup_by(|next, previous| *next <= *previous);
+        steps
+    }
+
+    /// Moves a point of the face onto the rounded box and gives it the normal of the surface there
+    fn round(&self, point: [f32; 3], axis: usize, side: usize) -> ([f32; 3], [f32; 3]) {
+        let inner: [f32; 3] = std::array::from_fn(|axis| {
+            let [low, high] =
+                self.exposed[axis].map(|exposed| if exposed { self.radius } else { 0.0 });
+            // Not `clamp`, whose bounds may cross by a rounding error when the bands meet.
+            point[axis]
+                .max(self.min[axis] + low)
+                .min(self.max[axis] - high)
+        });
+        let offset = Vector3::from(point) - Vector3::from(inner);
+
+        // The flat parts of the faces keep their points exactly, so they weld with the square faces of the neighbors.
+        if offset.iter().filter(|&&component| component != 0.0).count() <= 1 {
+            let mut normal = [0.0; 3];
+            normal[axis] = if side == 0 { -1.0 } else { 1.0 };
+            return (point, normal);
+        }
+
+        let normal = offset.normalize();
+        (
+            (Vector3::from(inner) + normal * self.radius).into(),
+            normal.into(),
+        )
+    }
+}
+
+fn corners_to_vertices(corners: [([f32; 3], [f32; 3]); 4], color: [f32; 3]) -> [Vertex; 6] {
+    let [a, b, c, d] = corners;
+
+    [a, b, c, c, b, d].map(|(position, normal)| Vertex {
+        position,
+        normal,
+        color,
+        alpha: 1.0,
+        uv: [0.0; 2],
+        tangent: [0.0; 4],
+    })
+}
@@ -0,0 +1,186 @@
+use std::collections::VecDeque;
+
+use crate::mask::Mask;
+use crate::{create_quad, Vertex};
+
+/// Run of sealed pixels on a row, from `start` up to `end` excluded
+#[derive(Copy, Clone, Debug)]
+struct Strip {
+    y: u32,
+    start: u32,
+    end: u32,
+}
+
+/// Creates a single bottom under every connected region of the mask, replacing the bottom faces of its voxels
+/// The region is split in strips along its rows. Their edges only get a vertex where a side face or another strip
+/// ends, so the bottom meets the sides without T-junctions and stays watertight. `shade` gives the color and the
+/// alpha of a strip from its first pixel.
+pub(crate) fn create_sealed_bottom(
+    mask: &Mask,
+    floor: f32,
+    shade: impl Fn(u32, u32) -> ([f32; 3], f32),
+) -> Vec<Vertex> {
+    let strips = find_strips(mask);
+
+    // The vertices on every line between two rows, as x coordinates counted in half voxels.
+    // Half voxels leave room for extra vertices between the ones on the grid.
+    let mut lines: Vec<Vec<u32>> = (0..=mask.height).map(|y| line_points(mask, y)).collect();
+
+    // Every strip is made of quads, which needs an even number of triangles. A strip with an odd number gets
+    // an extra vertex on an edge shared with a neighboring strip, which flips both of them. Going from the
+    // leaves of a spanning tree of the strips to its root leaves every strip even, as every region has an
+    // even number of triangles in total.
+    for (index, parent) in spanning_forest(&strips).into_iter().rev() {
+        let Some(parent) = parent else {
+            debug_assert!(triangle_count(&strips[index], &lines).is_multiple_of(2));
+            continue;
+        };
+        if triangle_count(&strips[index], &lines).is_multiple_of(2) {
+            continue;
+        }
+
+        let (strip, other) = (strips[index], strips[parent]);
+        let line = strip.y.max(other.y) as usize;
+        let point = strip.start.max(other.start) * 2 + 1;
+        let position = lines[line].binary_search(&point).unwrap_or_else(|i| i);
+        lines[line].insert(position, point);
+    }
+
+    let mut vertices = Vec::new();
+    for strip in &strips {
+        let (color, alpha) = shade(strip.start, strip.y);
+        let chain = |y: u32| -> Vec<[f32; 3]> {
+            lines[y as usize]
+                .iter()
+                .filter(|&&x| (strip.start * 2..=strip.end * 2).contains(&x))
+                .map(|&x| [x as f32 / 2.0, y as f32, floor])
+                .collect()
+        };
+        let (top, bottom) = (chain(strip.y), chain(strip.y + 1));
+
+        // Zips the two edges of the strip together, every triangle shares an edge with the next one.
+        let (mut i, mut j) = (0, 0);
+        let mut triangles = Vec::new();
+        while i + 1 < top.len() || j + 1 < bottom.len() {
+            let advance_top =
+                j + 1 == bottom.len() || (i + 1 < top.len() && top[i + 1][0] <= bottom[j + 1][0]);
+            if advance_top {
+                triangles.push(([top[i], bottom[j]], top[i + 1]));
+                i += 1;
+            } else {
+                triangles.push(([top[i], bottom[j]], bottom[j + 1]));
+                j += 1;
+            }
+        }
+
+        // Consecutive triangles share the edge between the current vertices of both chains.
+        debug_assert!(triangles.len().is_multiple_of(2));
+        for pair in triangles.chunks_exact(2) {
+            let ([top, bottom], _) = pair[0];
+            let ([b, c], d) = pair[1];
+            // The vertex of the first triangle that isn't on the shared edge is the one left behind.
+            let a = if b == top { bottom } else { top };
+            let mut quad = create_quad([a, b, c, d], [0.0, 0.0, -1.0], color);
+            for vertex in &mut quad {
+                vertex.alpha = alpha;
+            }
+            vertices.extend(quad);
+        }
+    }
+
+    vertices
+}
+
+fn find_strips(mask: &Mask) -> Vec<Strip> {
+    let mut strips = Vec::new();
+
+    for y in 0..mask.height {
+        let mut x = 0;
+        while x < mask.width {
+            if !mask.filled[mask.index(x, y)] {
+                x += 1;
+                continue;
+            }
+
+            let start = x;
+            while x < mask.width && mask.filled[mask.index(x, y)] {
+                x += 1;
+            }
+            strips.push(Strip { y, start, end: x });
+        }
+    }
+
+    strips
+}
+
+/// Points of the line above row `y` where a strip ends or that are on an exposed edge, in half voxels
+/// Points between four sealed pixels aren't needed, the strips on both sides of the line share them.
+fn line_points(mask: &Mask, y: u32) -> Vec<u32> {
+    let filled = |x: i64, y: i64| {
+        (0..mask.width as i64).contains(&x)
+            && (0..mask.height as i64).contains(&y)
+            && mask.filled[mask.index(x as u32, y as u32)]
+    };
+    let y = y as i64;
+
+    (0..=mask.width as i64)
+        .filter(|&x| {
+            let [above_left, above_right] = [filled(x - 1, y - 1), filled(x, y - 1)];
+            let [below_left, below_right] = [filled(x - 1, y), filled(x, y)];
+
+            above_left != above_right
+                || below_left != below_right
+                || above_left != below_left
+                || above_right != below_right
+        })
+        .map(|x| x as u32 * 2)
+        .collect()
+}
+
+/// Triangles needed to zip the two edges of the strip together
+fn triangle_count(strip: &Strip, lines: &[Vec<u32>]) -> usize {
+    let points = |y: u32| {
+        lines[y as usize]
+            .iter()
+            .filter(|&&x| (strip.start * 2..=strip.end * 2).contains(&x))
+            .count()
+    };
+
+    points(strip.y) + points(strip.y + 1) - 2
+}
+
+/// Breadth first order of the strips with the strip each one was reached from, strips touching along a row
+/// edge are neighbors. Every connected region is a separate tree.
+fn spanning_forest(strips: &[Strip]) -> Vec<(usize, Option<usize>)> {
+    let touching =
+        |a: &Strip, b: &Strip| a.y.abs_diff(b.y) == 1 && a.start.max(b.start) < a.end.min(b.end);
+
+    let mut visited = vec![false; strips.len()];
+    let mut order = Vec::with_capacity(strips.len());
+    let mut queue = VecDeque::new();
+
+    for root in 0..strips.len() {
+        if visited[root] {
+            continue;
+        }
+        visited[root] = true;
+        queue.push_back((root, None));
+
+        while let Some((index, parent)) = queue.pop_front() {
+            order.push((index, parent));
+
+            // The strips are sorted by row, so only the strips of the rows around this one need to be checked.
+            let y = strips[index].y;
+            let first = strips.partition_point(|strip| strip.y + 1 < y);
+            let last = strips.partition_point(|strip| strip.y <= y + 1);
+            for neighbor in first..last {
+                if !visited[neighbor] && touching(&strips[index], &strips[neighbor]) {
+                    visited[neighbor] = true;
+                    queue.push_back((neighbor, Some(index)));
+                }
+            }
+        }
+    }
+
+    order
+}
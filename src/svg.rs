@@ -0,0 +1,63 @@
+use crate::{DecodeLimits, VoxelifyError};
+
+/// Rasterizes an SVG so its longer side is `size` pixels, or at the size it declares without one
+/// The areas the SVG leaves transparent become empty pixels, and the text is drawn with the fonts of the system.
+/// The limits are checked on the size of the raster before it's allocated.
+///
+/// ```
+/// use image::GenericImageView;
+///
+/// let svg = br#"<svg xmlns="http://www.w3.org/2000/svg" width="20" height="10">
+///     <rect x="0" y="0" width="10" height="10" fill="red"/>
+/// </svg>"#;
+/// let image = voxelify::rasterize_svg(svg, Some(8), voxelify::DecodeLimits::default())?;
+///
+/// assert_eq!(image.dimensions(), (8, 4));
+/// assert_eq!(image.get_pixel(1, 1), image::Rgba([255, 0, 0, 255]));
+/// assert_eq!(image.get_pixel(6, 1)[3], 0);
+/// # Ok::<(), voxelify::VoxelifyError>(())
+/// ```
+pub fn rasterize_svg(
+    bytes: &[u8],
+    size: Option<u32>,
+    limits: DecodeLimits,
+) -> Result<image::DynamicImage, VoxelifyError> {
+    let mut options = resvg::usvg::Options::default();
+    options.fontdb_mut().load_system_fonts();
+    let tree = resvg::usvg::Tree::from_data(bytes, &options)
+        .map_err(|err| VoxelifyError::SvgError(err.to_string()))?;
+
+    let declared = tree.size();
+    let scale = size.map_or(1.0, |size| {
+        size as f32 / declared.width().max(declared.height())
+    });
+    let width = (declared.width() * scale).round().max(1.0) as u32;
+    let height = (declared.height() * scale).round().max(1.0) as u32;
+
+    if let Some(max_pixels) = limits.max_pixels {
+        if width as u64 * height as u64 > max_pixels {
+            return Err(VoxelifyError::TooManyPixels {
+                width,
+                height,
+                max_pixels,
+            });
+        }
+    }
+    let mut allocation = image::Limits::default();
+    if limits.max_alloc.is_some() {
+        allocation.max_alloc = limits.max_alloc;
+    }
+    allocation.reserve(width as u64 * height as u64 * 4)?;
+
+    let mut pixmap = resvg::tiny_skia::Pixmap::new(width, height)
+        .ok_or_else(|| VoxelifyError::SvgError(format!("Can't rasterize at {width}x{height}")))?;
+    resvg::render(
+        &tree,
+        resvg::tiny_skia::Transform::from_scale(scale, scale),
+        &mut pixmap.as_mut(),
+    );
+
+    let image = image::RgbaImage::from_raw(width, height, pixmap.take_demultiplied())
+        .expect("the pixmap has the size of the image");
+    Ok(image::DynamicImage::ImageRgba8(image))
+}
@@ -0,0 +1,66 @@
+use nalgebra::Vector3;
+
+use crate::Vertex;
+
+/// Creates a single surface through the tops of the pixels, a quad between the centers of every four neighbors
+/// The quads of neighboring cells meet on the same grid vertices, so the surface is connected. A cell is left out
+/// when one of its pixels is empty, which makes a hole around every empty pixel. `top` gives the height of a pixel,
+/// `None` when it's empty, and `shade` its color and alpha. The normals follow the slope towards the neighbors.
+pub(crate) fn create_terrain(
+    width: u32,
+    height: u32,
+    top: impl Fn(u32, u32) -> Option<f32>,
+    shade: impl Fn(u32, u32) -> ([f32; 3], f32),
+) -> Vec<Vertex> {
+    let heights: Vec<Option<f32>> = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| top(x, y))
+        .collect();
+    let height_at = |x: i64, y: i64| {
+        let inside = (0..width as i64).contains(&x) && (0..height as i64).contains(&y);
+        inside
+            .then(|| heights[(y * width as i64 + x) as usize])
+            .flatten()
+    };
+
+    let vertex = |x: u32, y: u32| {
+        let (px, py) = (x as i64, y as i64);
+        let center = height_at(px, py).expect("the cells only have filled pixels");
+        // The slope is the difference across the pixel, a missing neighbor is as high as the pixel itself.
+        let slope = |dx: i64, dy: i64| {
+            let ahead = height_at(px + dx, py + dy).unwrap_or(center);
+            let behind = height_at(px - dx, py - dy).unwrap_or(center);
+            (ahead - behind) / 2.0
+        };
+        let normal = Vector3::new(-slope(1, 0), -slope(0, 1), 1.0).normalize();
+        let (color, alpha) = shade(x, y);
+
+        Vertex {
+            position: [x as f32 + 0.5, y as f32 + 0.5, center],
+            normal: normal.into(),
+            color,
+            alpha,
+            uv: [0.0; 2],
+            tangent: [0.0; 4],
+        }
+    };
+
+    let mut vertices = Vec::new();
+    for y in 0..height.saturating_sub(1) {
+        for x in 0..width.saturating_sub(1) {
+            let corners = [(x, y), (x + 1, y), (x, y + 1), (x + 1, y + 1)];
+            if corners
+                .iter()
+                .any(|&(x, y)| height_at(x as i64, y as i64).is_none())
+            {
+                continue;
+            }
+
+            // Wound like the quads of the top faces, the diagonal goes from the second corner to the third.
+            let [a, b, c, d] = corners.map(|(x, y)| vertex(x, y));
+            vertices.extend([a, b, c, c, b, d]);
+        }
+    }
+
+    vertices
+}
@@ -0,0 +1,113 @@
+use nalgebra::Vector3;
+
+use crate::Vertex;
+
+/// Empty pixels around the model, as a fraction of the size of the thumbnail
+const MARGIN: f32 = 0.05;
+/// Light reaching the faces turned away from the light
+const AMBIENT: f32 = 0.35;
+
+/// Renders the vertices from an isometric camera into a square image, flat shaded with their colors
+/// The camera looks down at the top faces with the right and lower sides of the image facing it, the model is
+/// scaled to fit the image. The background is transparent.
+pub fn render_thumbnail(vertices: &[Vertex], size: u32) -> image::RgbaImage {
+    let mut thumbnail = image::RgbaImage::new(size, size);
+    if vertices.is_empty() || size == 0 {
+        return thumbnail;
+    }
+
+    // The image X goes to the right of the screen, its Y towards the viewer and its height goes up.
+    let eye = Vector3::new(1.0, 1.0, 1.0).normalize();
+    let right = Vector3::new(1.0, -1.0, 0.0).normalize();
+    let down = Vector3::new(1.0, 1.0, -2.0).normalize();
+    let light = Vector3::new(1.0, 0.5, 1.5).normalize();
+
+    let projected: Vec<[f32; 3]> = vertices
+        .iter()
+        .map(|vertex| {
+            let position = Vector3::from(vertex.position);
+            [
+                position.dot(&right),
+                position.dot(&down),
+                position.dot(&eye),
+            ]
+        })
+        .collect();
+
+    let (mut min, mut max) = ([f32::MAX; 2], [f32::MIN; 2]);
+    for point in &projected {
+        for axis in 0..2 {
+            min[axis] = min[axis].min(point[axis]);
+            max[axis] = max[axis].max(point[axis]);
+        }
+    }
+
+    // Both axes share the scale, so the model keeps its proportions and is centered on the other axis.
+    let extent = (max[0] - min[0]).max(max[1] - min[1]).max(f32::EPSILON);
+    let scale = size as f32 * (1.0 - 2.0 * MARGIN) / extent;
+    let center = [(min[0] + max[0]) / 2.0, (min[1] + max[1]) / 2.0];
+    let to_screen = |point: [f32; 3]| {
+        [
+            (point[0] - center[0]) * scale + size as f32 / 2.0,
+            (point[1] - center[1]) * scale + size as f32 / 2.0,
+            point[2],
+        ]
+    };
+
+    let mut depths = vec![f32::MIN; (size * size) as usize];
+
+    for (triangle, corners) in vertices.chunks_exact(3).zip(projected.chunks_exact(3)) {
+        let normal = Vector3::from(triangle[0].normal);
+        if normal.dot(&eye) <= 0.0 {
+            continue;
+        }
+
+        let shade = AMBIENT + (1.0 - AMBIENT) * normal.dot(&light).max(0.0);
+        let [r, g, b] = triangle[0]
+            .color
+            .map(|channel| (channel * shade * u8::MAX as f32).round().clamp(0.0, 255.0) as u8);
+        let color = image::Rgba([r, g, b, (triangle[0].alpha * u8::MAX as f32).round() as u8]);
+
+        let [a, b, c] = [0, 1, 2].map(|index| to_screen(corners[index]));
+        let area = edge(a, b, c);
+        if area.abs() <= f32::EPSILON {
+            continue;
+        }
+
+        let clamp = |value: f32| (value.max(0.0) as u32).min(size - 1);
+        let (x0, x1) = (
+            clamp(a[0].min(b[0]).min(c[0])),
+            clamp(a[0].max(b[0]).max(c[0]).ceil()),
+        );
+        let (y0, y1) = (
+            clamp(a[1].min(b[1]).min(c[1])),
+            clamp(a[1].max(b[1]).max(c[1]).ceil()),
+        );
+
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                // Pixels are sampled on their centers, with weights that don't depend on the winding.
+                let point = [x as f32 + 0.5, y as f32 + 0.5, 0.0];
+                let weights = [edge(b, c, point), edge(c, a, point), edge(a, b, point)]
+                    .map(|weight| weight / area);
+                if weights.iter().any(|&weight| weight < 0.0) {
+                    continue;
+                }
+
+                let depth = weights[0] * a[2] + weights[1] * b[2] + weights[2] * c[2];
+                let index = (y * size + x) as usize;
+                if depth > depths[index] {
+                    depths[index] = depth;
+                    thumbnail.put_pixel(x, y, color);
+                }
+            }
+        }
+    }
+
+    thumbnail
+}
+
+/// Twice the signed area of the triangle on the screen, positive when it turns clockwise
+fn edge(a: [f32; 3], b: [f32; 3], point: [f32; 3]) -> f32 {
+    (b[0] - a[0]) * (point[1] - a[1]) - (b[1] - a[1]) * (point[0] - a[0])
+}
@@ -0,0 +1,179 @@
+use std::fmt::Write as _;
+use std::io::Write;
+
+use crate::{ColorTarget, Vertex, VoxelifyError, VoxelifyOptions};
+
+/// Name of the layer inside the package, the first file of a USDZ is the one that's opened
+const USDA_NAME: &str = "model.usda";
+/// USDZ readers map the files straight from the archive, their data must start on this boundary
+const USDZ_ALIGNMENT: usize = 64;
+/// Header ID of the extra field padding the files of the archive, the one used by the USD tools
+const PADDING_HEADER_ID: u16 = 0x1986;
+/// Date of the files in the archive, 1980-01-01 in MS-DOS format
+const DOS_DATE: u16 = (1 << 5) | 1;
+
+/// Writes the vertices as the text layer of a USD stage, with a single mesh under a `Model` prim
+/// Every three vertices make a triangle, the vertices aren't shared. The colors go into `displayColor`, which USD
+/// stores as linear colors, and the alpha into `displayOpacity` with `vertex_alpha`. With `points` the voxels are
+/// written as a `Points` prim instead, the wireframe isn't supported.
+pub fn create_usda(vertices: &[Vertex], options: &VoxelifyOptions) -> String {
+    let mut usda = String::from(
+        "#usda 1.0\n(\n    defaultPrim = \"Model\"\n    metersPerUnit = 1\n    upAxis = \"Z\"\n)\n\n",
+    );
+    usda.push_str("def Xform \"Model\"\n{\n");
+
+    if options.points {
+        usda.push_str("    def Points \"Voxels\"\n    {\n");
+        usda.push_str("        float[] widths = [1]");
+        write_interpolation(&mut usda, "constant");
+    } else {
+        usda.push_str("    def Mesh \"Voxels\"\n    {\n");
+        write_array(
+            &mut usda,
+            "int[] faceVertexCounts",
+            vertices.len() / 3,
+            |_| "3".to_string(),
+        );
+        usda.push('\n');
+        write_array(
+            &mut usda,
+            "int[] faceVertexIndices",
+            vertices.len(),
+            |index| index.to_string(),
+        );
+        usda.push('\n');
+        write_array(&mut usda, "normal3f[] normals", vertices.len(), |index| {
+            tuple(vertices[index].normal)
+        });
+        write_interpolation(&mut usda, "vertex");
+        usda.push_str("        uniform token subdivisionScheme = \"none\"\n");
+        let _ = writeln!(
+            usda,
+            "        uniform bool doubleSided = {}",
+            options.double_sided
+        );
+    }
+
+    write_array(&mut usda, "point3f[] points", vertices.len(), |index| {
+        tuple(vertices[index].position)
+    });
+    usda.push('\n');
+    write_array(
+        &mut usda,
+        "color3f[] primvars:displayColor",
+        vertices.len(),
+        |index| tuple(ColorTarget::GltfLinear.convert(vertices[index].color)),
+    );
+    write_interpolation(&mut usda, "vertex");
+    if options.vertex_alpha {
+        write_array(
+            &mut usda,
+            "float[] primvars:displayOpacity",
+            vertices.len(),
+            |index| vertices[index].alpha.to_string(),
+        );
+        write_interpolation(&mut usda, "vertex");
+    }
+
+    usda.push_str("    }\n}\n");
+    usda
+}
+
+/// Writes the vertices as a USDZ package for AR Quick Look, an uncompressed ZIP holding the layer of `create_usda`
+pub fn write_usdz<W: Write>(
+    mut writer: W,
+    vertices: &[Vertex],
+    options: &VoxelifyOptions,
+) -> Result<(), VoxelifyError> {
+    let usda = create_usda(vertices, options);
+    let data = usda.as_bytes();
+    let name = USDA_NAME.as_bytes();
+    let crc = crc32fast::hash(data);
+    let size: u32 = data.len().try_into()?;
+
+    // The extra field grows until the data after the local header is aligned.
+    let local_header_length = 30 + name.len();
+    let padding = (USDZ_ALIGNMENT - (local_header_length + 4) % USDZ_ALIGNMENT) % USDZ_ALIGNMENT;
+
+    let mut local = Vec::with_capacity(local_header_length + 4 + padding);
+    local.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+    write_entry_fields(&mut local, crc, size, name.len(), 4 + padding);
+    local.extend_from_slice(name);
+    local.extend_from_slice(&PADDING_HEADER_ID.to_le_bytes());
+    local.extend_from_slice(&(padding as u16).to_le_bytes());
+    local.resize(local.len() + padding, 0);
+
+    let mut central = Vec::new();
+    central.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+    // Made by and needed to extract, version 2.0 of the ZIP format.
+    central.extend_from_slice(&20u16.to_le_bytes());
+    write_entry_fields(&mut central, crc, size, name.len(), 0);
+    // No comment, on the first disk, without attributes and with the local header at the start.
+    central.extend_from_slice(&[0; 14]);
+    central.extend(name);
+
+    let central_offset: u32 = (local.len() + data.len()).try_into()?;
+    let mut end = Vec::with_capacity(22);
+    end.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+    end.extend_from_slice(&[0; 4]);
+    end.extend_from_slice(&1u16.to_le_bytes());
+    end.extend_from_slice(&1u16.to_le_bytes());
+    end.extend_from_slice(&(central.len() as u32).to_le_bytes());
+    end.extend_from_slice(&central_offset.to_le_bytes());
+    end.extend_from_slice(&0u16.to_le_bytes());
+
+    writer.write_all(&local)?;
+    writer.write_all(data)?;
+    writer.write_all(&central)?;
+    writer.write_all(&end)?;
+    Ok(())
+}
+
+/// Fields shared by the local and the central headers of a stored file, from the version needed to the extra length
+fn write_entry_fields(
+    header: &mut Vec<u8>,
+    crc: u32,
+    size: u32,
+    name_length: usize,
+    extra_length: usize,
+) {
+    header.extend_from_slice(&20u16.to_le_bytes());
+    // No flags, stored without compression at midnight.
+    header.extend_from_slice(&[0; 6]);
+    header.extend_from_slice(&DOS_DATE.to_le_bytes());
+    header.extend_from_slice(&crc.to_le_bytes());
+    header.extend_from_slice(&size.to_le_bytes());
+    header.extend_from_slice(&size.to_le_bytes());
+    header.extend_from_slice(&(name_length as u16).to_le_bytes());
+    header.extend_from_slice(&(extra_length as u16).to_le_bytes());
+}
+
+/// Writes an attribute holding an array, the elements are formatted by `element`
+/// The line is left open for the metadata of the attribute.
+fn write_array(
+    usda: &mut String,
+    declaration: &str,
+    length: usize,
+    element: impl Fn(usize) -> String,
+) {
+    let _ = write!(usda, "        {declaration} = [");
+    for index in 0..length {
+        if index > 0 {
+            usda.push_str(", ");
+        }
+        usda.push_str(&element(index));
+    }
+    usda.push(']');
+}
+
+/// Ends the line of an attribute with how its values are spread over the prim
+fn write_interpolation(usda: &mut String, interpolation: &str) {
+    let _ = writeln!(
+        usda,
+        " (\n            interpolation = \"{interpolation}\"\n        )"
+    );
+}
+
+fn tuple(components: [f32; 3]) -> String {
+    format!("({}, {}, {})", components[0], components[1], components[2])
+}
@@ -0,0 +1,45 @@
+use nalgebra::Vector3;
+
+use crate::Vertex;
+
+/// Fills the texture coordinates and tangents with a planar projection along the main axis of each normal
+/// One voxel covers one pixel of the image, side faces are upright with V growing downwards like in the image
+pub(crate) fn generate_texture_coordinates(vertices: &mut [Vertex], width: u32, height: u32) {
+    let (width, height) = (width as f32, height as f32);
+
+    for vertex in vertices {
+        let [x, y, z] = vertex.position;
+        let normal = Vector3::from(vertex.normal);
+
+        // The directions along which U and V grow on this face, the height uses the scale of the paired axis.
+        let (uv, u_direction, v_direction) = match main_axis(vertex.normal) {
+            2 => ([x / width, y / height], Vector3::x(), Vector3::y()),
+            0 => ([y / height, -z / height], Vector3::y(), -Vector3::z()),
+            _ => ([x / width, -z / width], Vector3::x(), -Vector3::z()),
+        };
+
+        let tangent = (u_direction - normal * u_direction.dot(&normal)).normalize();
+        let bitangent = v_direction - normal * v_direction.dot(&normal);
+        let handedness = if normal.cross(&tangent).dot(&bitangent) < 0.0 {
+            -1.0
+        } else {
+            1.0
+        };
+
+        vertex.uv = uv;
+        vertex.tangent = [tangent.x, tangent.y, tangent.z, handedness];
+    }
+}
+
+/// Index of the largest component of the normal, the Z axis wins ties so slanted bevels map like the top
+fn main_axis(normal: [f32; 3]) -> usize {
+    let [x, y, z] = normal.map(f32::abs);
+
+    if z >= x && z >= y {
+        2
+    } else if x >= y {
+        0
+    } else {
+        1
+    }
+}
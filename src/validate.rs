@@ -0,0 +1,198 @@
+use gltf::json::validation::{Checked, Validate};
+use gltf::json::{self, mesh::Mode};
+use std::collections::HashMap;
+
+use crate::{Vertex, VoxelifyError, PADDING};
+
+/// Edge of the mesh that isn't shared by exactly two triangles, see `check_watertight`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct OpenEdge {
+    pub start: [f32; 3],
+    pub end: [f32; 3],
+    /// Triangles using the edge, one on a hole and more than two where the surface touches itself
+    pub triangles: usize,
+}
+
+/// Checks that the mesh is a closed manifold, as slicers require for 3D printing
+/// The vertices are welded by position, then every edge has to be shared by exactly two triangles.
+/// Returns the edges that aren't along with the error.
+///
+/// ```
+/// use voxelify::{check_watertight, image_to_vertices_with_options, VoxelifyOptions};
+///
+/// let options = VoxelifyOptions {
+///     seal_bottom: true,
+///     ..Default::default()
+/// };
+///
+/// let block = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+///     3,
+///     3,
+///     image::Rgba([255, 0, 0, 255]),
+/// ));
+/// assert!(check_watertight(&image_to_vertices_with_options(&block, &options)).is_ok());
+///
+/// // Two voxels touching on a single edge share it between four triangles.
+/// let diagonal = image::DynamicImage::ImageRgba8(image::RgbaImage::from_fn(2, 2, |x, y| {
+///     image::Rgba([255, 0, 0, if x == y { 255 } else { 0 }])
+/// }));
+/// assert!(check_watertight(&image_to_vertices_with_options(&diagonal, &options)).is_err());
+/// ```
+pub fn check_watertight(vertices: &[Vertex]) -> Result<(), VoxelifyError> {
+    // Adding zero turns -0.0 into 0.0, so both weld into the same point.
+    let key = |vertex: &Vertex| vertex.position.map(|component| (component + 0.0).to_bits());
+
+    let mut edges: HashMap<([u32; 3], [u32; 3]), usize> = HashMap::new();
+    for triangle in vertices.chunks_exact(3) {
+        for index in 0..3 {
+            let (start, end) = (key(&triangle[index]), key(&triangle[(index + 1) % 3]));
+            *edges.entry((start.min(end), start.max(end))).or_default() += 1;
+        }
+    }
+
+    let mut open: Vec<_> = edges
+        .into_iter()
+        .filter(|&(_, triangles)| triangles != 2)
+        .collect();
+    if open.is_empty() {
+        return Ok(());
+    }
+    open.sort_unstable();
+
+    Err(VoxelifyError::NotWatertight(
+        open.into_iter()
+            .map(|((start, end), triangles)| OpenEdge {
+                start: start.map(f32::from_bits),
+                end: end.map(f32::from_bits),
+                triangles,
+            })
+            .collect(),
+    ))
+}
+
+/// Checks the generated glTF before it's written
+/// On top of the validation of the `gltf` crate, the accessors and buffer views are checked to fit
+/// inside of their buffers with the alignment required by the spec
+pub fn validate(root: &json::Root) -> Result<(), VoxelifyError> {
+    let mut problems = Vec::new();
+
+    root.validate(root, json::Path::new, &mut |path, error| {
+        // Extensions the `gltf` crate can't read itself are still valid glTF.
+        if error != json::validation::Error::Unsupported {
+            problems.push(format!("{}: {:?}", path(), error))
+        }
+    });
+
+    for (index, view) in root.buffer_views.iter().enumerate() {
+        let offset = view.byte_offset.map_or(0, |offset| offset.0);
+        let end = offset + view.byte_length.0;
+
+        if !offset.is_multiple_of(PADDING as u64) {
+            problems.push(format!("bufferViews[{index}] is not aligned to four bytes"));
+        }
+
+        if let Some(buffer) = root.get(view.buffer) {
+            if end > buffer.byte_length.0 {
+                problems.push(format!(
+                    "bufferViews[{index}] ends at {end}, past the end of its buffer at {}",
+                    buffer.byte_length.0
+                ));
+            }
+        }
+    }
+
+    for (index, accessor) in root.accessors.iter().enumerate() {
+        validate_accessor(root, index, accessor, &mut problems);
+    }
+
+    for (mesh_index, mesh) in root.meshes.iter().enumerate() {
+        for (index, primitive) in mesh.primitives.iter().enumerate() {
+            validate_primitive(root, mesh_index, index, primitive, &mut problems);
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(VoxelifyError::ValidationError(problems))
+    }
+}
+
+fn validate_accessor(
+    root: &json::Root,
+    index: usize,
+    accessor: &json::Accessor,
+    problems: &mut Vec<String>,
+) {
+    let (Checked::Valid(component_type), Checked::Valid(type_)) =
+        (&accessor.component_type, &accessor.type_)
+    else {
+        // Already reported by the validation of the `gltf` crate.
+        return;
+    };
+
+    let Some(view) = accessor.buffer_view.and_then(|view| root.get(view)) else {
+        return;
+    };
+
+    let component_size = component_type.0.size() as u64;
+    let element_size = component_size * type_.multiplicity() as u64;
+    let offset = accessor.byte_offset.map_or(0, |offset| offset.0);
+    let stride = view
+        .byte_stride
+        .map_or(element_size, |stride| stride.0 as u64);
+
+    if !offset.is_multiple_of(component_size) {
+        problems.push(format!(
+            "accessors[{index}] offset is not aligned to its component size"
+        ));
+    }
+
+    if accessor.count.0 > 0 {
+        let end = offset + stride * (accessor.count.0 - 1) + element_size;
+        if end > view.byte_length.0 {
+            problems.push(format!(
+                "accessors[{index}] reads up to byte {end}, past the end of its buffer view at {}",
+                view.byte_length.0
+            ));
+        }
+    }
+}
+
+fn validate_primitive(
+    root: &json::Root,
+    mesh_index: usize,
+    index: usize,
+    primitive: &json::mesh::Primitive,
+    problems: &mut Vec<String>,
+) {
+    let counts: Vec<u64> = primitive
+        .attributes
+        .values()
+        .filter_map(|accessor| root.get(*accessor))
+        .map(|accessor| accessor.count.0)
+        .collect();
+
+    if counts.windows(2).any(|pair| pair[0] != pair[1]) {
+        problems.push(format!(
+            "meshes[{mesh_index}].primitives[{index}] has attributes with different counts"
+        ));
+    }
+
+    let element_count = match primitive.indices.and_then(|indices| root.get(indices)) {
+        Some(indices) => indices.count.0,
+        None => counts.first().copied().unwrap_or(0),
+    };
+
+    let group_size = match primitive.mode {
+        Checked::Valid(Mode::Lines) => 2,
+        Checked::Valid(Mode::Triangles) => 3,
+        _ => 1,
+    };
+
+    if !element_count.is_multiple_of(group_size) {
+        problems.push(format!(
+            "meshes[{mesh_index}].primitives[{index}] has {element_count} elements, which isn't a multiple of {group_size}"
+        ));
+    }
+}
@@ -0,0 +1,46 @@
+use wasm_bindgen::prelude::*;
+
+use crate::options::hex_color;
+use crate::{
+    create_glb, create_gltf_root, glb_to_vec, image_from_bytes, image_to_vertices_with_options,
+    Background, VoxelifyOptions,
+};
+
+/// Converts an encoded image into a 3D object without touching the filesystem
+/// The result is returned to JavaScript as a `Uint8Array`
+/// The pixels within `tolerance` of the `RRGGBB` background color are culled like the transparent ones, both can be
+/// left out.
+#[wasm_bindgen]
+pub fn voxelify(
+    image_bytes: &[u8],
+    height: f32,
+    format: &str,
+    background: Option<String>,
+    tolerance: Option<u8>,
+) -> Result<Vec<u8>, JsError> {
+    if format != "glb" {
+        return Err(JsError::new(&format!(
+            "Unsupported output format: {format}"
+        )));
+    }
+
+    let background = background
+        .map(|color| hex_color::parse(&color).map_err(|error| JsError::new(&error)))
+        .transpose()?
+        .map(|color| Background {
+            color,
+            tolerance: tolerance.unwrap_or(0),
+        });
+
+    let img = image_from_bytes(image_bytes)?;
+    let options = VoxelifyOptions {
+        z_ceiling: height,
+        background,
+        ..Default::default()
+    };
+    let vertices = image_to_vertices_with_options(&img, &options);
+    let root = create_gltf_root(&vertices, None, &options);
+    let glb = create_glb(&root, &vertices, &options)?;
+
+    Ok(glb_to_vec(&glb)?)
+}